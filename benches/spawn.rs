@@ -1,8 +1,12 @@
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
 use procedural_generation::*;
 use std::time::Duration;
 
-fn perlin_gen(width: usize, height: usize) {
+const SIZES: [usize; 3] = [256, 512, 1024];
+// for stages that iterate or search over the whole map and get slow on large maps
+const SMALL_SIZES: [usize; 3] = [32, 64, 128];
+
+fn perlin_gen(width: usize, height: usize) -> Generator {
     Generator::new()
         .with_size(width, height)
         .spawn_perlin(|value| {
@@ -13,22 +17,113 @@ fn perlin_gen(width: usize, height: usize) {
             } else {
                 0
             }
-        });
+        })
 }
 
-fn room_gen(width: usize, height: usize) {
+fn room_gen(width: usize, height: usize) -> Generator {
     let size = Size::new((10, 10), (100, 100));
     Generator::new()
         .with_size(width, height)
-        .spawn_rooms(1, 1000, &size);
+        .spawn_rooms(1, 1000, &size)
+}
+
+// a fresh generator with the map and fields of `generator`, for benchmarking a
+// stage on the same input every iteration
+fn copy(generator: &Generator) -> Generator {
+    let mut copy = Generator::new().with_size(generator.width, generator.height);
+    copy.map = generator.map.clone();
+    for name in generator.field_names() {
+        copy.set_field(name, generator.field(name).unwrap().to_vec());
+    }
+    copy
 }
 
 fn criterion_benchmark(c: &mut Criterion) {
-    let mut group = c.benchmark_group("sample-size-example");
+    let mut group = c.benchmark_group("spawn");
     group.warm_up_time(Duration::from_secs(1));
     group.sample_size(10);
-    group.bench_function("perlin_gen 1000 1000", |b| b.iter(|| perlin_gen(black_box(1000), black_box(1000))));
-    group.bench_function("room_gen 1000 1000", |b| b.iter(|| room_gen(black_box(1000), black_box(1000))));
+    for &size in SIZES.iter() {
+        // report cells per second so different map sizes are comparable
+        group.throughput(Throughput::Elements((size * size) as u64));
+        group.bench_with_input(BenchmarkId::new("perlin", size), &size, |b, &size| {
+            b.iter(|| perlin_gen(black_box(size), black_box(size)))
+        });
+        group.bench_with_input(BenchmarkId::new("rooms", size), &size, |b, &size| {
+            b.iter(|| room_gen(black_box(size), black_box(size)))
+        });
+        let generator = perlin_gen(size, size);
+        group.bench_with_input(BenchmarkId::new("label_regions", size), &generator, |b, generator| {
+            b.iter(|| generator.label_regions())
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("heightmap");
+    group.warm_up_time(Duration::from_secs(1));
+    group.sample_size(10);
+    for &size in SIZES.iter() {
+        group.throughput(Throughput::Elements((size * size) as u64));
+        group.bench_with_input(BenchmarkId::new("faults", size), &size, |b, &size| {
+            b.iter(|| Generator::new().with_size(black_box(size), black_box(size)).spawn_faults(100))
+        });
+        group.bench_with_input(BenchmarkId::new("deposition", size), &size, |b, &size| {
+            b.iter(|| {
+                Generator::new()
+                    .with_size(black_box(size), black_box(size))
+                    .spawn_deposition(size * size / 16, DepositionOptions::default())
+            })
+        });
+        let generator = Generator::new().with_size(size, size).spawn_faults(100);
+        group.bench_with_input(BenchmarkId::new("carve_rivers", size), &generator, |b, generator| {
+            b.iter(|| copy(generator).carve_rivers(RiverOptions::default()))
+        });
+        // throughput counts the cells of the grown map
+        let small = perlin_gen(size / 4, size / 4);
+        group.bench_with_input(BenchmarkId::new("subdivide", size), &small, |b, small| {
+            b.iter(|| copy(small).subdivide(2, 0.5))
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("iterative");
+    group.warm_up_time(Duration::from_secs(1));
+    group.sample_size(10);
+    let tileset = Tileset::new()
+        .with_tile(0, 1.)
+        .with_tile(1, 1.)
+        .with_tile(2, 1.)
+        .with_neighbors(0, 0)
+        .with_neighbors(0, 1)
+        .with_neighbors(1, 1)
+        .with_neighbors(1, 2)
+        .with_neighbors(2, 2);
+    let grow = Rule::new(3, 1, vec![Some(1), Some(0), Some(0)], vec![None, Some(1), Some(1)]).rotations();
+    for &size in SMALL_SIZES.iter() {
+        group.throughput(Throughput::Elements((size * size) as u64));
+        group.bench_with_input(BenchmarkId::new("collapse_tiles", size), &size, |b, &size| {
+            b.iter(|| Generator::new().with_size(black_box(size), black_box(size)).collapse_tiles(&tileset))
+        });
+        group.bench_with_input(BenchmarkId::new("rewrite", size), &size, |b, &size| {
+            b.iter(|| {
+                Generator::new()
+                    .with_size(black_box(size), black_box(size))
+                    .draw_line((size / 2, size / 2), (size / 2, size / 2), 1)
+                    .rewrite(&grow, size)
+            })
+        });
+        let sand = perlin_gen(size, size).with_seed(1);
+        group.bench_with_input(BenchmarkId::new("settle", size), &sand, |b, sand| {
+            b.iter(|| copy(sand).settle(2, 0, true, size))
+        });
+        let terrain = Generator::new()
+            .with_size(size, size)
+            .spawn_faults(100)
+            .classify("elevation", &[(0.5, 2), (0., 1)]);
+        group.bench_with_input(BenchmarkId::new("flow_water", size), &terrain, |b, terrain| {
+            b.iter(|| copy(terrain).flow_water(2, 1, size))
+        });
+    }
+    group.finish();
 }
 
 criterion_group!(benches, criterion_benchmark);
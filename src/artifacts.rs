@@ -0,0 +1,77 @@
+//! Structured output of stages that place things instead of, or as well as,
+//! changing tile values.
+
+use crate::{Generator, MissionRoom};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// An enemy encounter placed by
+/// [Generator::place_encounters](struct.Generator.html#method.place_encounters).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Encounter {
+    pub position: (usize, usize),
+    /// Difficulty tier, as returned by the budget closure.
+    pub tier: usize,
+}
+
+/// A village built by
+/// [Generator::spawn_villages](struct.Generator.html#method.spawn_villages).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Village {
+    /// Position of the well.
+    pub center: (usize, usize),
+    /// Top left corner of every 2x2 building.
+    pub buildings: Vec<(usize, usize)>,
+}
+
+/// A road crossing water, made by
+/// [Generator::place_bridges](struct.Generator.html#method.place_bridges).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Bridge {
+    /// Cells that were water, from one bank to the other.
+    pub cells: Vec<(usize, usize)>,
+    /// Whether the water was all shallow, making it a ford instead of a bridge.
+    pub ford: bool,
+}
+
+/// A prefab stamped by
+/// [Generator::stamp_poisson](struct.Generator.html#method.stamp_poisson).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Stamp {
+    /// Name of the prefab.
+    pub prefab: String,
+    /// Top left corner of the prefab.
+    pub position: (usize, usize),
+}
+
+/// Everything placed on the map by stages so far, see
+/// [Generator::artifacts](struct.Generator.html#method.artifacts).
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Artifacts {
+    pub encounters: Vec<Encounter>,
+    /// Exit placed by `place_exit`.
+    pub exit: Option<(usize, usize)>,
+    pub villages: Vec<Village>,
+    /// Closed patrol routes made by `generate_patrols`, as lists of cells.
+    pub patrols: Vec<Vec<(usize, usize)>>,
+    /// Rooms drawn by `embed_mission`, in the order of the mission graph.
+    pub mission: Vec<MissionRoom>,
+    /// Bridges and fords placed by `place_bridges`.
+    pub bridges: Vec<Bridge>,
+    /// Prefabs stamped by `stamp_poisson`.
+    pub stamps: Vec<Stamp>,
+}
+
+impl Generator {
+    /// Returns what stages placed on the map besides tile values, such as encounters,
+    /// exits, villages, patrols, mission rooms, bridges and stamped prefabs.
+    /// Like the recorded stages, artifacts are cleared by `with_size`.
+    pub fn artifacts(&self) -> &Artifacts {
+        &self.artifacts
+    }
+}
@@ -0,0 +1,322 @@
+//! Generates maps from the terminal, see `procgen --help`.
+
+use procedural_generation::*;
+use std::fs::File;
+use std::io::BufWriter;
+use std::{env, process};
+
+#[cfg(feature = "tui")]
+mod tui;
+#[cfg(feature = "preview")]
+mod window;
+
+const USAGE: &str = "\
+Generates maps from a config file or from flags.
+
+USAGE:
+    procgen [OPTIONS] [CONFIG]
+
+ARGS:
+    CONFIG                 TOML or JSON config, see `Config`
+
+OPTIONS:
+    --size <WIDTHxHEIGHT>  size of the map, such as 200x100
+    --seed <SEED>          number or text to seed the map with
+    --stage <STAGE>        stage to run after the ones in CONFIG, can be repeated:
+                             perlin                 land above 0.5 as 1
+                             perlin:0.66=2,0.33=1   noise thresholds and their values
+                             rooms:5                5 rooms between 4x4 and 10x10
+                             {\"stage\": ...}         any stage written as JSON
+    --out <FILE>           write .png or .json instead of printing to the terminal
+    --scale <PIXELS>       pixels per cell in PNG output, default is 4
+    --field <NAME>         show or write the field called NAME in grayscale
+                           instead of the map, such as hillshade
+    --palette <NAME>       colors to use everywhere: viridis, okabe-ito,
+                           high-contrast or grayscale
+    --tui                  preview the map interactively, needs the `tui` feature
+    --window               preview the map in a window scaled by --scale, needs
+                           the `preview` feature
+    -h, --help             print this message";
+
+struct Options {
+    config: Option<String>,
+    size: Option<(usize, usize)>,
+    seed: Option<String>,
+    stages: Vec<Stage>,
+    out: Option<String>,
+    scale: usize,
+    field: Option<String>,
+    palette: Palette,
+    tui: bool,
+    window: bool,
+}
+
+fn main() {
+    if let Err(error) = run() {
+        eprintln!("procgen: {}", error);
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let options = parse(env::args().skip(1))?;
+    let mut config = match &options.config {
+        Some(path) => Config::from_file(path).map_err(|error| error.to_string())?,
+        None => Config {
+            width: 60,
+            height: 30,
+            seed: None,
+            stages: vec![],
+        },
+    };
+    if let Some((width, height)) = options.size {
+        config.width = width;
+        config.height = height;
+    }
+    config.stages.extend(options.stages);
+    if let Some(seed) = &options.seed {
+        // numbers are used as they are, anything else is hashed like `with_seed_str`
+        config.seed = Some(seed.parse().unwrap_or_else(|_| Generator::new().with_seed_str(seed).seed()));
+    }
+    if options.tui {
+        return preview(config, options.palette);
+    }
+    if options.window {
+        return preview_window(config, options.palette, options.scale);
+    }
+    let generator = config.generate().map_err(|error| error.to_string())?.with_palette(options.palette.clone());
+    let field = match &options.field {
+        Some(name) => Some(generator.field(name).ok_or(format!("there is no field called {}", name))?),
+        None => None,
+    };
+
+    match options.out {
+        Some(path) if path.ends_with(".png") => write_png(&generator, &options.palette, field, &path, options.scale),
+        Some(path) if path.ends_with(".json") => write_json(&generator, &path),
+        Some(path) => Err(format!("don't know how to write {}, use .png or .json", path)),
+        None => {
+            match field {
+                Some(field) => generator.show_shaded(field),
+                None => generator.show(),
+            }
+            println!("seed: {}", generator.seed());
+            Ok(())
+        }
+    }
+}
+
+#[cfg(feature = "tui")]
+fn preview(config: Config, palette: Palette) -> Result<(), String> {
+    tui::run(config, palette).map_err(|error| error.to_string())
+}
+
+#[cfg(not(feature = "tui"))]
+fn preview(_: Config, _: Palette) -> Result<(), String> {
+    Err("--tui needs procgen to be built with the `tui` feature".into())
+}
+
+#[cfg(feature = "preview")]
+fn preview_window(config: Config, palette: Palette, scale: usize) -> Result<(), String> {
+    window::run(config, palette, scale)
+}
+
+#[cfg(not(feature = "preview"))]
+fn preview_window(_: Config, _: Palette, _: usize) -> Result<(), String> {
+    Err("--window needs procgen to be built with the `preview` feature".into())
+}
+
+fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let mut options = Options {
+        config: None,
+        size: None,
+        seed: None,
+        stages: vec![],
+        out: None,
+        scale: 4,
+        field: None,
+        palette: Palette::new(),
+        tui: false,
+        window: false,
+    };
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| args.next().ok_or(format!("{} needs a value", flag));
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            "--size" => options.size = Some(parse_size(&value("--size")?)?),
+            "--seed" => options.seed = Some(value("--seed")?),
+            "--stage" => options.stages.push(parse_stage(&value("--stage")?)?),
+            "--tui" => options.tui = true,
+            "--window" => options.window = true,
+            "--out" => options.out = Some(value("--out")?),
+            "--field" => options.field = Some(value("--field")?),
+            "--palette" => {
+                let name = value("--palette")?;
+                options.palette = Palette::preset(&name).ok_or(format!(
+                    "unknown palette {}, expected one of {}",
+                    name,
+                    Palette::preset_names().collect::<Vec<_>>().join(", ")
+                ))?
+            }
+            "--scale" => {
+                options.scale = value("--scale")?
+                    .parse()
+                    .map_err(|_| "--scale must be a positive number".to_string())?
+            }
+            flag if flag.starts_with('-') => return Err(format!("unknown option {}, see --help", flag)),
+            _ => options.config = Some(arg),
+        }
+    }
+    Ok(options)
+}
+
+fn parse_size(size: &str) -> Result<(usize, usize), String> {
+    let invalid = || format!("invalid size {}, expected WIDTHxHEIGHT", size);
+    let mut parts = size.split('x').map(|part| part.parse::<usize>());
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(Ok(width)), Some(Ok(height)), None) => Ok((width, height)),
+        _ => Err(invalid()),
+    }
+}
+
+fn parse_stage(stage: &str) -> Result<Stage, String> {
+    if stage.starts_with('{') {
+        return serde_json::from_str(stage).map_err(|error| format!("invalid stage {}: {}", stage, error));
+    }
+    let invalid = || format!("invalid stage {}, see --help", stage);
+    let (name, arguments) = match stage.find(':') {
+        Some(colon) => (&stage[..colon], Some(&stage[colon + 1..])),
+        None => (stage, None),
+    };
+    match (name, arguments) {
+        ("perlin", None) => Ok(Stage::PerlinBands {
+            options: NoiseOptions::default(),
+            bands: vec![(0.5, 1)],
+        }),
+        ("perlin", Some(arguments)) => {
+            let bands = arguments
+                .split(',')
+                .map(|band| {
+                    let mut parts = band.split('=');
+                    match (parts.next(), parts.next(), parts.next()) {
+                        (Some(threshold), Some(value), None) => {
+                            Some((threshold.parse().ok()?, value.parse().ok()?))
+                        }
+                        _ => None,
+                    }
+                })
+                .collect::<Option<Vec<(f64, usize)>>>()
+                .ok_or_else(invalid)?;
+            Ok(Stage::PerlinBands {
+                options: NoiseOptions::default(),
+                bands,
+            })
+        }
+        ("rooms", Some(rooms)) => Ok(Stage::Rooms {
+            number: 1,
+            rooms: rooms.parse().map_err(|_| invalid())?,
+            min_size: (4, 4),
+            max_size: (10, 10),
+        }),
+        _ => Err(invalid()),
+    }
+}
+
+// draws `field` in grayscale when given, from black at its lowest value to white at
+// its highest, and the map in the colors of `palette` otherwise
+fn write_png(
+    generator: &Generator,
+    palette: &Palette,
+    field: Option<&[f64]>,
+    path: &str,
+    scale: usize,
+) -> Result<(), String> {
+    let scale = scale.max(1);
+    let (width, height) = (generator.width * scale, generator.height * scale);
+    let (min, range) = field.map_or((0., 1.), |field| {
+        let min = field.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = field.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        (min, if max > min { max - min } else { 1. })
+    });
+    let mut pixels = Vec::with_capacity(width * height * 3);
+    for y in 0..height {
+        for x in 0..width {
+            let (cx, cy) = (x / scale, y / scale);
+            let (r, g, b) = match field {
+                Some(field) => {
+                    let gray = ((field[cx + cy * generator.width] - min) / range * 255.).round() as u8;
+                    (gray, gray, gray)
+                }
+                None => palette.rgb(generator.get(cx, cy)),
+            };
+            pixels.extend_from_slice(&[r, g, b]);
+        }
+    }
+    let file = File::create(path).map_err(|error| format!("can't create {}: {}", path, error))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&pixels))
+        .map_err(|error| format!("can't write {}: {}", path, error))
+}
+
+fn write_json(generator: &Generator, path: &str) -> Result<(), String> {
+    let json = serde_json::json!({
+        "width": generator.width,
+        "height": generator.height,
+        "seed": generator.seed(),
+        "map": generator.map,
+        "recipe": generator.recipe(),
+    });
+    let file = File::create(path).map_err(|error| format!("can't create {}: {}", path, error))?;
+    serde_json::to_writer(BufWriter::new(file), &json).map_err(|error| format!("can't write {}: {}", path, error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn size() {
+        assert_eq!(parse_size("200x100"), Ok((200, 100)));
+        for size in ["200", "200x", "x100", "200x100x3", "wide", "-1x5"].iter() {
+            assert_eq!(parse_size(size), Err(format!("invalid size {}, expected WIDTHxHEIGHT", size)));
+        }
+    }
+
+    #[test]
+    fn stage() {
+        assert_eq!(
+            parse_stage("perlin:0.66=2,0.33=1"),
+            Ok(Stage::PerlinBands {
+                options: NoiseOptions::default(),
+                bands: vec![(0.66, 2), (0.33, 1)],
+            })
+        );
+        assert_eq!(
+            parse_stage("rooms:5"),
+            Ok(Stage::Rooms {
+                number: 1,
+                rooms: 5,
+                min_size: (4, 4),
+                max_size: (10, 10),
+            })
+        );
+        assert_eq!(
+            parse_stage(r#"{"stage": "line", "from": [0, 0], "to": [3, 3], "value": 2}"#),
+            Ok(Stage::Line {
+                from: (0, 0),
+                to: (3, 3),
+                value: 2,
+            })
+        );
+        for stage in ["perlin:0.5", "perlin:0.5=a", "perlin:0.5=1=2", "rooms", "rooms:many", "caves"].iter() {
+            assert_eq!(parse_stage(stage), Err(format!("invalid stage {}, see --help", stage)));
+        }
+        assert!(parse_stage(r#"{"stage": "nowhere"}"#).unwrap_err().starts_with("invalid stage {"));
+    }
+}
@@ -0,0 +1,143 @@
+//! Interactive preview, regenerating the map on every key press.
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use crossterm::style::{Color, Print, SetForegroundColor};
+use crossterm::{cursor, execute, queue, terminal};
+use procedural_generation::*;
+use std::io::{self, Write};
+
+const HELP: &str = "r reroll  f/F frequency  e/E redistribution  o/O octaves  up/down select  space toggle  q quit";
+
+struct Preview {
+    config: Config,
+    palette: Palette,
+    options: NoiseOptions,
+    enabled: Vec<bool>,
+    selected: usize,
+}
+
+impl Preview {
+    // generates the map with the enabled stages, using the tuned noise options
+    fn generate(&self) -> Result<Generator, GenerationError> {
+        let stages = self
+            .config
+            .stages
+            .iter()
+            .zip(&self.enabled)
+            .filter(|&(_, &enabled)| enabled)
+            .map(|(stage, _)| match stage {
+                Stage::PerlinBands { bands, .. } => Stage::PerlinBands {
+                    options: self.options.clone(),
+                    bands: bands.clone(),
+                },
+                stage => stage.clone(),
+            })
+            .collect();
+        Config { stages, ..self.config.clone() }.generate()
+    }
+    // returns false once the preview should close
+    fn handle(&mut self, key: KeyEvent) -> bool {
+        let options = &mut self.options;
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Char('r') => self.config.seed = Some(Generator::new().seed()),
+            KeyCode::Char('f') => options.frequency = (options.frequency - 0.5).max(0.5),
+            KeyCode::Char('F') => options.frequency += 0.5,
+            KeyCode::Char('e') => options.redistribution = (options.redistribution - 0.1).max(0.1),
+            KeyCode::Char('E') => options.redistribution += 0.1,
+            KeyCode::Char('o') => options.octaves = options.octaves.saturating_sub(1).max(1),
+            KeyCode::Char('O') => options.octaves += 1,
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected = (self.selected + 1).min(self.enabled.len().saturating_sub(1)),
+            KeyCode::Char(' ') => {
+                if let Some(enabled) = self.enabled.get_mut(self.selected) {
+                    *enabled = !*enabled;
+                }
+            }
+            _ => {}
+        }
+        true
+    }
+    fn draw<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let (columns, rows) = terminal::size()?;
+        let status_lines = self.enabled.len() + 3;
+        queue!(out, terminal::Clear(terminal::ClearType::All), cursor::MoveTo(0, 0))?;
+
+        match self.generate() {
+            Ok(generator) => {
+                let visible_rows = (rows as usize).saturating_sub(status_lines).min(generator.height);
+                let visible_columns = (columns as usize / 2).min(generator.width);
+                for y in 0..visible_rows {
+                    queue!(out, cursor::MoveTo(0, y as u16))?;
+                    for x in 0..visible_columns {
+                        let value = generator.get(x, y);
+                        let (r, g, b) = self.palette.rgb(value);
+                        queue!(out, SetForegroundColor(Color::Rgb { r, g, b }), Print(format!("{} ", value)))?;
+                    }
+                }
+                queue!(
+                    out,
+                    SetForegroundColor(Color::Reset),
+                    cursor::MoveTo(0, visible_rows as u16),
+                    Print(format!(
+                        "seed {}  frequency {:.1}  redistribution {:.1}  octaves {}",
+                        generator.seed(),
+                        self.options.frequency,
+                        self.options.redistribution,
+                        self.options.octaves
+                    ))
+                )?;
+            }
+            Err(error) => queue!(out, SetForegroundColor(Color::Reset), Print(error))?,
+        }
+
+        let top = rows.saturating_sub(status_lines as u16 - 1);
+        for (i, stage) in self.config.stages.iter().enumerate() {
+            let marker = if i == self.selected { '>' } else { ' ' };
+            let check = if self.enabled[i] { 'x' } else { ' ' };
+            queue!(
+                out,
+                cursor::MoveTo(0, top + i as u16),
+                Print(format!("{} [{}] {}", marker, check, stage))
+            )?;
+        }
+        queue!(out, cursor::MoveTo(0, rows.saturating_sub(1)), Print(HELP))?;
+        out.flush()
+    }
+}
+
+/// Shows the map described by `config` until `q` is pressed. The seed is fixed
+/// up front so toggling stages and tuning options keep showing the same world.
+pub fn run(mut config: Config, palette: Palette) -> io::Result<()> {
+    config.seed = Some(config.seed.unwrap_or_else(|| Generator::new().seed()));
+    let options = config
+        .stages
+        .iter()
+        .find_map(|stage| match stage {
+            Stage::PerlinBands { options, .. } => Some(options.clone()),
+            _ => None,
+        })
+        .unwrap_or_default();
+    let mut preview = Preview {
+        enabled: vec![true; config.stages.len()],
+        config,
+        palette,
+        options,
+        selected: 0,
+    };
+
+    let mut out = io::stdout();
+    terminal::enable_raw_mode()?;
+    execute!(out, terminal::EnterAlternateScreen, cursor::Hide)?;
+    let result = (|| loop {
+        preview.draw(&mut out)?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press && !preview.handle(key) {
+                return Ok(());
+            }
+        }
+    })();
+    execute!(out, cursor::Show, terminal::LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+    result
+}
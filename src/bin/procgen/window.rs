@@ -0,0 +1,51 @@
+//! Native preview window, regenerating the map with a new seed on `r`.
+
+use minifb::{Key, KeyRepeat, Window, WindowOptions};
+use procedural_generation::*;
+
+// one pixel per cell in 0RGB, scaled up by the window
+fn pixels(generator: &Generator, palette: &Palette) -> Vec<u32> {
+    generator
+        .map
+        .iter()
+        .map(|&value| {
+            let (r, g, b) = palette.rgb(value);
+            u32::from_be_bytes([0, r, g, b])
+        })
+        .collect()
+}
+
+/// Shows the map described by `config` in a window until it's closed or `q` or
+/// escape is pressed, `r` rerolls the seed.
+pub fn run(mut config: Config, palette: Palette, scale: usize) -> Result<(), String> {
+    let mut generator = config.generate().map_err(|error| error.to_string())?;
+    let options = WindowOptions {
+        scale: match scale {
+            0..=1 => minifb::Scale::X1,
+            2..=3 => minifb::Scale::X2,
+            4..=7 => minifb::Scale::X4,
+            8..=15 => minifb::Scale::X8,
+            16..=31 => minifb::Scale::X16,
+            _ => minifb::Scale::X32,
+        },
+        ..WindowOptions::default()
+    };
+    let title = |generator: &Generator| format!("procgen, seed {} (r reroll, q quit)", generator.seed());
+    let mut window = Window::new(&title(&generator), generator.width, generator.height, options)
+        .map_err(|error| error.to_string())?;
+    window.set_target_fps(30);
+
+    let mut buffer = pixels(&generator, &palette);
+    while window.is_open() && !window.is_key_down(Key::Escape) && !window.is_key_down(Key::Q) {
+        if window.is_key_pressed(Key::R, KeyRepeat::No) {
+            config.seed = Some(Generator::new().seed());
+            generator = config.generate().map_err(|error| error.to_string())?;
+            buffer = pixels(&generator, &palette);
+            window.set_title(&title(&generator));
+        }
+        window
+            .update_with_buffer(&buffer, generator.width, generator.height)
+            .map_err(|error| error.to_string())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,141 @@
+//! Loading whole pipelines from TOML or JSON files.
+
+use crate::{GenerationError, Generator, Recipe, Stage};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// A pipeline as written by hand in a config file: the map size, an optional seed
+/// and the stages to run, see [Generator::from_config](struct.Generator.html#method.from_config).
+/// Unlike a [Recipe](struct.Recipe.html) the seed may be left out for a random map,
+/// and noise options of `perlin_bands` stages default to `NoiseOptions::default()`.
+///
+/// ```toml
+/// width = 60
+/// height = 30
+/// seed = 42
+///
+/// [[stages]]
+/// stage = "perlin_bands"
+/// bands = [[0.66, 2], [0.33, 1]]
+///
+/// [[stages]]
+/// stage = "replace_weighted"
+/// from = 1
+/// choices = [[3, 0.05]]
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Config {
+    pub width: usize,
+    pub height: usize,
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub stages: Vec<Stage>,
+}
+
+impl Config {
+    /// Parses a config written in JSON.
+    pub fn from_json(text: &str) -> Result<Self, GenerationError> {
+        serde_json::from_str(text).map_err(|error| GenerationError::InvalidConfig(error.to_string()))
+    }
+    /// Parses a config written in TOML.
+    pub fn from_toml(text: &str) -> Result<Self, GenerationError> {
+        toml::from_str(text).map_err(|error| GenerationError::InvalidConfig(error.to_string()))
+    }
+    /// Reads the config file at `path`, files ending in `.json` are read as JSON and
+    /// everything else as TOML.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, GenerationError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|error| GenerationError::InvalidConfig(format!("can't read {}: {}", path.display(), error)))?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Config::from_json(&text),
+            _ => Config::from_toml(&text),
+        }
+    }
+    /// Generates the map described by the config.
+    pub fn generate(&self) -> Result<Generator, GenerationError> {
+        let generator = Generator::new();
+        let recipe = Recipe {
+            width: self.width,
+            height: self.height,
+            seed: self.seed.unwrap_or_else(|| generator.seed()),
+            stages: self.stages.clone(),
+        };
+        generator
+            .with_size(recipe.width, recipe.height)
+            .with_seed(recipe.seed)
+            .replay_stages(&recipe, None)
+    }
+}
+
+impl Generator {
+    /// Generates the map described by the config file at `path`, so designers can
+    /// iterate on maps without recompiling. Files ending in `.json` are read as JSON,
+    /// everything else as TOML. Requires the `config` feature.
+    ///
+    /// Fails with [GenerationError::InvalidConfig](enum.GenerationError.html) if the
+    /// file can't be read or parsed.
+    ///
+    /// ```rust,no_run
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     Generator::from_config("maps/island.toml").unwrap().show();
+    /// }
+    /// ```
+    pub fn from_config<P: AsRef<Path>>(path: P) -> Result<Self, GenerationError> {
+        Config::from_file(path)?.generate()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_config() {
+        let toml = Config::from_toml(
+            r#"
+            width = 20
+            height = 10
+            seed = 42
+
+            [[stages]]
+            stage = "perlin_bands"
+            bands = [[0.5, 1]]
+
+            [[stages]]
+            stage = "rect"
+            x = 2
+            y = 2
+            width = 3
+            height = 3
+            value = 2
+            filled = true
+            "#,
+        )
+        .unwrap();
+        let json = Config::from_json(
+            r#"{
+                "width": 20,
+                "height": 10,
+                "seed": 42,
+                "stages": [
+                    { "stage": "perlin_bands", "bands": [[0.5, 1]] },
+                    { "stage": "rect", "x": 2, "y": 2, "width": 3, "height": 3, "value": 2, "filled": true }
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(toml, json);
+        let expected = Generator::new()
+            .with_size(20, 10)
+            .with_seed(42)
+            .spawn_perlin_bands(&[(0.5, 1)])
+            .fill_rect(2, 2, 3, 3, 2);
+        assert_eq!(toml.generate().unwrap().map, expected.map);
+        assert!(Config::from_json("{}").is_err());
+    }
+}
@@ -0,0 +1,186 @@
+//! Reporting progress of long running stages, cancelling them and limiting how
+//! long they may run.
+
+use crate::{GenerationError, Generator};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+type ProgressFn = dyn Fn(&str, f32) + Send + Sync;
+
+/// Callback set by `with_progress`, boxed so `Generator` can hold it.
+pub(crate) struct Progress(Box<ProgressFn>);
+
+impl fmt::Debug for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Progress")
+    }
+}
+
+/// Shared flag for aborting generation from another thread, see
+/// [Generator::with_cancel_token](struct.Generator.html#method.with_cancel_token).
+/// Clones share the same flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Asks every generator holding this token to stop as soon as possible.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+    /// Returns true once `cancel()` has been called on this token or one of its clones.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl Generator {
+    /// Calls `f(stage, fraction)` while stages run, so games can show a loading bar
+    /// instead of a frozen screen. Every stage reports 0.0 when it starts and 1.0 when
+    /// it's done, expensive stages such as `spawn_perlin` and `rewrite` report in
+    /// between as well. The callback may be called from several threads at once.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     Generator::new()
+    ///         .with_size(400, 400)
+    ///         .with_progress(|stage, fraction| println!("{}: {:.0}%", stage, fraction * 100.))
+    ///         .spawn_perlin_bands(&[(0.5, 1)]);
+    /// }
+    /// ```
+    pub fn with_progress<F: Fn(&str, f32) + Send + Sync + 'static>(mut self, f: F) -> Self {
+        self.progress = Some(Progress(Box::new(f)));
+        self
+    }
+    /// Lets `token` abort generation, for a "Generating world... Cancel" button. Long
+    /// running stages check the token as they go and stop mid-run, and once it is
+    /// cancelled every following stage is skipped. The map is left half finished, so
+    /// check `try_finish()` before using it.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let token = CancelToken::new();
+    ///     let generator = Generator::new()
+    ///         .with_size(40, 10)
+    ///         .with_cancel_token(token.clone())
+    ///         .spawn_perlin_bands(&[(0.5, 1)]);
+    ///     // usually called from a UI thread
+    ///     token.cancel();
+    ///     let result = generator.draw_line((0, 0), (39, 9), 2).try_finish();
+    ///     assert_eq!(result.unwrap_err(), GenerationError::Cancelled);
+    /// }
+    /// ```
+    pub fn with_cancel_token(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+    /// Returns true if generation has been cancelled through the token passed to
+    /// `with_cancel_token`.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.as_ref().is_some_and(CancelToken::is_cancelled)
+    }
+    /// Returns the generator, or [GenerationError::Cancelled](enum.GenerationError.html)
+    /// if generation was cancelled and the map may be incomplete.
+    pub fn try_finish(self) -> Result<Self, GenerationError> {
+        if self.is_cancelled() {
+            Err(GenerationError::Cancelled)
+        } else {
+            Ok(self)
+        }
+    }
+    /// Gives expensive stages a wall-clock budget starting now, so loading screens
+    /// have a bounded length. Once it runs out, iterative stages such as `rewrite`
+    /// stop early and keep what they have so far, while `generate_until` fails with
+    /// [GenerationError::TimedOut](enum.GenerationError.html) instead of trying
+    /// another map.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    /// use std::time::Duration;
+    ///
+    /// fn main() {
+    ///     let grow = Rule::new(2, 1, vec![Some(1), Some(0)], vec![None, Some(1)]);
+    ///     Generator::new()
+    ///         .with_size(200, 200)
+    ///         .with_time_budget(Duration::from_millis(50))
+    ///         .draw_line((100, 100), (100, 100), 1)
+    ///         .rewrite(&grow.rotations(), 1000)
+    ///         .show();
+    /// }
+    /// ```
+    pub fn with_time_budget(mut self, budget: Duration) -> Self {
+        self.deadline = Some(Instant::now() + budget);
+        self
+    }
+    /// Returns true if the budget set by `with_time_budget` has run out.
+    pub fn is_out_of_time(&self) -> bool {
+        self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+    /// Reports how far the running stage has come, `fraction` goes from 0.0 to 1.0.
+    pub(crate) fn report(&self, fraction: f32) {
+        if let Some(progress) = &self.progress {
+            (progress.0)(&self.current_stage, fraction);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn progress() {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let sink = reports.clone();
+        Generator::new()
+            .with_size(10, 400)
+            .with_progress(move |stage, fraction| sink.lock().unwrap().push((stage.to_string(), fraction)))
+            .spawn_perlin_bands(&[(0.5, 1)])
+            .draw_line((0, 0), (9, 9), 2);
+        let reports = reports.lock().unwrap();
+        // start and end of both stages plus one report per chunk of 1024 cells
+        assert_eq!(reports.len(), 2 + 4 + 2);
+        assert_eq!(reports[0], ("perlin_bands".to_string(), 0.));
+        assert!(reports[1..5].iter().all(|(stage, _)| stage == "perlin_bands"));
+        assert_eq!(reports.last().unwrap(), &("line".to_string(), 1.));
+    }
+
+    #[test]
+    fn cancel() {
+        let token = CancelToken::new();
+        let generator = Generator::new()
+            .with_size(10, 10)
+            .with_cancel_token(token.clone())
+            .fill_rect(0, 0, 5, 5, 1);
+        token.cancel();
+        let generator = generator.fill_rect(5, 5, 5, 5, 2);
+        assert_eq!(generator.stages().len(), 1);
+        assert_eq!(generator.map.iter().filter(|&&value| value == 2).count(), 0);
+        assert_eq!(generator.try_finish().unwrap_err(), GenerationError::Cancelled);
+    }
+
+    #[test]
+    fn time_budget() {
+        let grow = crate::Rule::new(2, 1, vec![Some(1), Some(0)], vec![None, Some(1)]);
+        let generator = Generator::new()
+            .with_size(50, 1)
+            .with_time_budget(Duration::from_secs(0))
+            .draw_line((0, 0), (0, 0), 1)
+            .rewrite(&[grow], 10);
+        assert!(generator.is_out_of_time());
+        assert_eq!(generator.positions_of(1).len(), 1);
+        assert_eq!(
+            generator.generate_until(|_| false, 10).unwrap_err(),
+            GenerationError::TimedOut
+        );
+    }
+}
@@ -0,0 +1,200 @@
+//! Sprinkling small details over a map based on what surrounds each cell.
+
+use crate::{Generator, Stage, NEIGHBORS_8};
+use rand::prelude::*;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+
+/// What the surroundings of a cell must look like for a
+/// [Decoration](struct.Decoration.html) to go there.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Condition {
+    /// A cell holding `value` is at most `distance` cells away, diagonal steps
+    /// counting as one.
+    Near { value: usize, distance: usize },
+    /// No cell holding `value` is within `distance` cells.
+    Far { value: usize, distance: usize },
+    /// Between `min` and `max` of the eight neighbors hold `value`.
+    Neighbors { value: usize, min: usize, max: usize },
+}
+
+/// A detail placed by [Generator::decorate](struct.Generator.html#method.decorate),
+/// such as mushrooms near water or crystals in the corners of a cave.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Decoration {
+    /// Value of the decoration.
+    pub value: usize,
+    /// Value of the cells it's placed on.
+    pub on: usize,
+    /// Chance of a cell meeting every condition getting the decoration.
+    pub chance: f64,
+    pub conditions: Vec<Condition>,
+}
+
+impl Decoration {
+    /// Creates a decoration of `value` on every cell holding `on`, until
+    /// conditions are added or the chance is lowered.
+    pub fn new(value: usize, on: usize) -> Self {
+        Decoration {
+            value,
+            on,
+            chance: 1.,
+            conditions: vec![],
+        }
+    }
+    /// Places the decoration on a `chance` fraction of the cells it fits on.
+    pub fn with_chance(mut self, chance: f64) -> Self {
+        self.chance = chance;
+        self
+    }
+    /// Only places the decoration within `distance` cells of `value`.
+    pub fn near(mut self, value: usize, distance: usize) -> Self {
+        self.conditions.push(Condition::Near { value, distance });
+        self
+    }
+    /// Only places the decoration farther than `distance` cells from `value`.
+    pub fn far(mut self, value: usize, distance: usize) -> Self {
+        self.conditions.push(Condition::Far { value, distance });
+        self
+    }
+    /// Only places the decoration where between `min` and `max` of the eight
+    /// neighbors hold `value`.
+    pub fn neighbors(mut self, value: usize, min: usize, max: usize) -> Self {
+        self.conditions.push(Condition::Neighbors { value, min, max });
+        self
+    }
+}
+
+// steps from every cell to the closest cell holding `value`, diagonal steps
+// counting as one, u32::MAX when there is none
+fn distances(map: &[usize], width: usize, height: usize, value: usize) -> Vec<u32> {
+    let mut distances = vec![u32::MAX; map.len()];
+    let mut open = VecDeque::new();
+    for (pos, &other) in map.iter().enumerate() {
+        if other == value {
+            distances[pos] = 0;
+            open.push_back(pos);
+        }
+    }
+    while let Some(pos) = open.pop_front() {
+        let (x, y) = ((pos % width) as isize, (pos / width) as isize);
+        for &(dx, dy) in NEIGHBORS_8.iter() {
+            let (nx, ny) = (x + dx, y + dy);
+            if nx < 0 || ny < 0 || nx >= width as isize || ny >= height as isize {
+                continue;
+            }
+            let next = nx as usize + ny as usize * width;
+            if distances[next] == u32::MAX {
+                distances[next] = distances[pos] + 1;
+                open.push_back(next);
+            }
+        }
+    }
+    distances
+}
+
+impl Generator {
+    /// Places `decorations` on the map, such as mushrooms within 3 cells of water
+    /// and crystals on floor next to exactly one wall. Every cell gets the first
+    /// decoration whose conditions it meets, if its chance comes up. Conditions look
+    /// at the map as it was before decorating, so decorations don't depend on each
+    /// other, and distances are worked out once for the whole map, however many
+    /// decorations use them.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let decorations = [
+    ///         // crystals in corners of the cave
+    ///         Decoration::new(4, 1).neighbors(0, 5, 5).with_chance(0.5),
+    ///         // mushrooms near water, away from the walls
+    ///         Decoration::new(5, 1).near(2, 3).far(0, 1).with_chance(0.3),
+    ///     ];
+    ///     Generator::new()
+    ///         .with_size(60, 20)
+    ///         .spawn_perlin_bands(&[(0.75, 2), (0.45, 1)])
+    ///         .decorate(&decorations)
+    ///         .show();
+    /// }
+    /// ```
+    pub fn decorate(self, decorations: &[Decoration]) -> Self {
+        let stage = Stage::Decorate {
+            decorations: decorations.to_vec(),
+        };
+        self.stage(stage, |mut generator| {
+            let (width, height) = (generator.width, generator.height);
+            let map = generator.map.clone();
+            let mut fields = BTreeMap::new();
+            for condition in decorations.iter().flat_map(|decoration| &decoration.conditions) {
+                if let Condition::Near { value, .. } | Condition::Far { value, .. } = *condition {
+                    fields.entry(value).or_insert_with(|| distances(&map, width, height, value));
+                }
+            }
+            let neighbors = |pos: usize, value: usize| {
+                let (x, y) = ((pos % width) as isize, (pos / width) as isize);
+                NEIGHBORS_8
+                    .iter()
+                    .map(|&(dx, dy)| (x + dx, y + dy))
+                    .filter(|&(nx, ny)| nx >= 0 && ny >= 0 && nx < width as isize && ny < height as isize)
+                    .filter(|&(nx, ny)| map[nx as usize + ny as usize * width] == value)
+                    .count()
+            };
+            let fits = |decoration: &Decoration, pos: usize| {
+                map[pos] == decoration.on
+                    && decoration.conditions.iter().all(|condition| match *condition {
+                        Condition::Near { value, distance } => fields[&value][pos] <= distance as u32,
+                        Condition::Far { value, distance } => fields[&value][pos] > distance as u32,
+                        Condition::Neighbors { value, min, max } => (min..=max).contains(&neighbors(pos, value)),
+                    })
+            };
+            generator.with_stage_rng("decorate", |generator, rng| {
+                for (pos, value) in generator.map.iter_mut().enumerate() {
+                    // rolled for every cell, so one decoration doesn't shift the others
+                    let roll: f64 = rng.gen();
+                    if let Some(decoration) = decorations.iter().find(|decoration| fits(decoration, pos)) {
+                        if roll < decoration.chance {
+                            *value = decoration.value;
+                        }
+                    }
+                }
+            });
+            generator
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decorate() {
+        // floor with a pool in the top left corner, walls along the bottom row
+        let generator = Generator::new()
+            .with_size(10, 6)
+            .fill_rect(0, 0, 10, 5, 1)
+            .fill_rect(0, 0, 2, 2, 2)
+            .decorate(&[
+                Decoration::new(4, 1).neighbors(0, 3, 3),
+                Decoration::new(5, 1).near(2, 2).far(0, 2),
+            ]);
+        for (x, y, value) in generator.iter_cells() {
+            match value {
+                // three walls below
+                4 => assert!(y == 4 && x > 0 && x < 9),
+                // close to the pool, away from the walls
+                5 => assert!(x <= 3 && y < 3),
+                0 => assert_eq!(y, 5),
+                2 => assert!(x < 2 && y < 2),
+                _ => assert!(value == 1),
+            }
+        }
+        assert_eq!(generator.positions_of(4).len(), 8);
+        assert_eq!(generator.positions_of(5).len(), 8);
+    }
+}
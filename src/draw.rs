@@ -0,0 +1,175 @@
+//! Drawing primitives for carving shapes into the map by hand.
+
+use crate::{Generator, Stage};
+
+impl Generator {
+    // sets a cell, ignoring coordinates outside the map so shapes are clipped
+    fn plot(&mut self, x: isize, y: isize, value: usize) {
+        if x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height {
+            self.set(x as usize, y as usize, value);
+        }
+    }
+    /// Draws a line from `from` to `to` (both included) with Bresenham's algorithm.
+    /// Like every drawing method, parts outside the map are clipped.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     Generator::new()
+    ///         .with_size(30, 20)
+    ///         .draw_rect(2, 2, 26, 16, 1)
+    ///         .draw_line((2, 10), (27, 10), 2)
+    ///         .fill_circle((15, 10), 4, 3)
+    ///         .show();
+    /// }
+    /// ```
+    pub fn draw_line(self, from: (usize, usize), to: (usize, usize), value: usize) -> Self {
+        self.stage(Stage::Line { from, to, value }, |mut generator| {
+            let (mut x, mut y) = (from.0 as isize, from.1 as isize);
+            let (x2, y2) = (to.0 as isize, to.1 as isize);
+            let dx = (x2 - x).abs();
+            let dy = -(y2 - y).abs();
+            let sx = if x < x2 { 1 } else { -1 };
+            let sy = if y < y2 { 1 } else { -1 };
+            let mut error = dx + dy;
+            loop {
+                generator.plot(x, y, value);
+                if x == x2 && y == y2 {
+                    break;
+                }
+                let doubled = 2 * error;
+                if doubled >= dy {
+                    error += dy;
+                    x += sx;
+                }
+                if doubled <= dx {
+                    error += dx;
+                    y += sy;
+                }
+            }
+            generator
+        })
+    }
+    /// Draws the outline of a `width` by `height` rectangle with its top left corner at (x, y).
+    pub fn draw_rect(self, x: usize, y: usize, width: usize, height: usize, value: usize) -> Self {
+        let stage = Stage::Rect {
+            x,
+            y,
+            width,
+            height,
+            value,
+            filled: false,
+        };
+        self.stage(stage, |mut generator| {
+            if width == 0 || height == 0 {
+                return generator;
+            }
+            let (x, y) = (x as isize, y as isize);
+            let (x2, y2) = (x + width as isize - 1, y + height as isize - 1);
+            for col in x..=x2 {
+                generator.plot(col, y, value);
+                generator.plot(col, y2, value);
+            }
+            for row in y..=y2 {
+                generator.plot(x, row, value);
+                generator.plot(x2, row, value);
+            }
+            generator
+        })
+    }
+    /// Same as `draw_rect(...)`, except the rectangle is filled.
+    pub fn fill_rect(self, x: usize, y: usize, width: usize, height: usize, value: usize) -> Self {
+        let stage = Stage::Rect {
+            x,
+            y,
+            width,
+            height,
+            value,
+            filled: true,
+        };
+        self.stage(stage, |mut generator| {
+            for row in y..(y + height).min(generator.height) {
+                for col in x..(x + width).min(generator.width) {
+                    generator.set(col, row, value);
+                }
+            }
+            generator
+        })
+    }
+    /// Draws the outline of a circle around `center` with the midpoint circle algorithm.
+    pub fn draw_circle(self, center: (usize, usize), radius: usize, value: usize) -> Self {
+        let stage = Stage::Circle {
+            center,
+            radius,
+            value,
+            filled: false,
+        };
+        self.stage(stage, |mut generator| {
+            let (cx, cy) = (center.0 as isize, center.1 as isize);
+            let (mut x, mut y) = (radius as isize, 0);
+            let mut error = 1 - x;
+            while x >= y {
+                for &(dx, dy) in [(x, y), (y, x), (-y, x), (-x, y), (-x, -y), (-y, -x), (y, -x), (x, -y)].iter() {
+                    generator.plot(cx + dx, cy + dy, value);
+                }
+                y += 1;
+                if error < 0 {
+                    error += 2 * y + 1;
+                } else {
+                    x -= 1;
+                    error += 2 * (y - x) + 1;
+                }
+            }
+            generator
+        })
+    }
+    /// Same as `draw_circle(...)`, except the circle is filled.
+    pub fn fill_circle(self, center: (usize, usize), radius: usize, value: usize) -> Self {
+        let stage = Stage::Circle {
+            center,
+            radius,
+            value,
+            filled: true,
+        };
+        self.stage(stage, |mut generator| {
+            let (cx, cy) = (center.0 as isize, center.1 as isize);
+            let radius = radius as isize;
+            for dy in -radius..=radius {
+                for dx in -radius..=radius {
+                    if dx * dx + dy * dy <= radius * radius + radius {
+                        generator.plot(cx + dx, cy + dy, value);
+                    }
+                }
+            }
+            generator
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shapes() {
+        let generator = Generator::new()
+            .with_size(5, 5)
+            .draw_rect(0, 0, 5, 5, 1)
+            .draw_line((0, 0), (4, 4), 2)
+            .fill_circle((2, 2), 1, 3);
+        assert_eq!(generator.map, vec![
+            2,1,1,1,1,
+            1,3,3,3,1,
+            1,3,3,3,1,
+            1,3,3,3,1,
+            1,1,1,1,2,
+        ]);
+        let generator = Generator::new().with_size(3, 3).draw_circle((1, 1), 1, 1);
+        assert_eq!(generator.map, vec![
+            0,1,0,
+            1,0,1,
+            0,1,0,
+        ]);
+    }
+}
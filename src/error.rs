@@ -0,0 +1,70 @@
+//! Errors reported by the fallible `try_*` methods.
+
+use std::error::Error;
+use std::fmt;
+
+/// Everything that can go wrong while generating or reading a map.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GenerationError {
+    /// The map has no cells, usually because `with_size` was never called.
+    EmptyMap,
+    /// The coordinate lies outside the map.
+    OutOfBounds { x: usize, y: usize },
+    /// The minimum room size is not smaller than the maximum room size.
+    InvalidRoomSize,
+    /// The smallest possible room doesn't fit inside the map.
+    RoomTooLarge,
+    /// Two maps that must be the same size are not.
+    SizeMismatch {
+        expected: (usize, usize),
+        found: (usize, usize),
+    },
+    /// The recipe holds a stage driven by a closure, which can't be replayed.
+    UnreplayableStage(String),
+    /// Nothing is registered under this name.
+    UnknownGenerator(String),
+    /// A spawner is registered under this name, but a recipe is needed.
+    NotARecipe(String),
+    /// A quota must be a fraction between 0 and 1.
+    InvalidQuota(f64),
+    /// No map was accepted within this many attempts.
+    AttemptsExhausted(usize),
+    /// The budget set with `with_time_budget` ran out.
+    TimedOut,
+    /// No cell is this many steps away from the start.
+    ExitTooFar { min_steps: u32 },
+    /// A config file couldn't be read or parsed.
+    InvalidConfig(String),
+    /// A mission graph can't be laid out on the map, for this reason.
+    InvalidMission(String),
+    /// Generation was cancelled through a [CancelToken](struct.CancelToken.html).
+    Cancelled,
+}
+
+impl fmt::Display for GenerationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GenerationError::EmptyMap => write!(f, "map is empty, set a size with `with_size` first"),
+            GenerationError::OutOfBounds { x, y } => write!(f, "({}, {}) is outside the map", x, y),
+            GenerationError::InvalidRoomSize => write!(f, "minimum room size must be smaller than maximum room size"),
+            GenerationError::RoomTooLarge => write!(f, "minimum room size doesn't fit inside the map"),
+            GenerationError::SizeMismatch { expected, found } => write!(
+                f,
+                "expected a {}x{} map, found {}x{}",
+                expected.0, expected.1, found.0, found.1
+            ),
+            GenerationError::UnreplayableStage(name) => write!(f, "stage `{}` can't be replayed", name),
+            GenerationError::UnknownGenerator(name) => write!(f, "nothing is registered as `{}`", name),
+            GenerationError::NotARecipe(name) => write!(f, "`{}` is registered as a spawner, not a recipe", name),
+            GenerationError::InvalidQuota(fraction) => write!(f, "quota {} is not between 0 and 1", fraction),
+            GenerationError::AttemptsExhausted(attempts) => write!(f, "no map was accepted in {} attempts", attempts),
+            GenerationError::TimedOut => write!(f, "generation ran out of time"),
+            GenerationError::ExitTooFar { min_steps } => write!(f, "no cell is {} steps from the start", min_steps),
+            GenerationError::InvalidConfig(reason) => write!(f, "invalid config: {}", reason),
+            GenerationError::InvalidMission(reason) => write!(f, "invalid mission: {}", reason),
+            GenerationError::Cancelled => write!(f, "generation was cancelled"),
+        }
+    }
+}
+
+impl Error for GenerationError {}
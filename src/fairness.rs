@@ -0,0 +1,112 @@
+//! Balance checks for competitive maps.
+
+use crate::Generator;
+
+/// What one spawn gets out of the cells closest to it, see
+/// [Generator::fairness](struct.Generator.html#method.fairness).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SpawnRegion {
+    /// Amount of reachable cells closer to this spawn than to any other.
+    pub cells: usize,
+    /// Amount of resources among those cells.
+    pub resources: usize,
+    /// Mean path distance from the spawn to its resources, 0 without resources.
+    pub mean_distance: f64,
+}
+
+/// Resources and distances within each spawn's region, and how much they differ.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Fairness {
+    /// One region per spawn, in the order the spawns were given.
+    pub regions: Vec<SpawnRegion>,
+    /// Largest relative spread between spawns of region size, resource count or mean
+    /// distance to resources. 0 is perfectly fair, 1 means some spawn has nothing.
+    pub imbalance: f64,
+}
+
+impl Generator {
+    /// Splits the map between `spawns` by path distance, every reachable cell going
+    /// to its nearest spawn, then compares what each spawn gets: the cells holding a
+    /// value for which `is_resource` returns true, and how far away they are. Paths
+    /// use the same `cost` closure as `path(...)`. Competitive maps with an
+    /// `imbalance` above some limit can be rejected automatically.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let fairness = Generator::new()
+    ///         .with_size(40, 20)
+    ///         .spawn_perlin_bands(&[(0.7, 2)])
+    ///         .fairness(&[(0, 10), (39, 10)], |_| Some(1), |value| value == 2);
+    ///     if fairness.imbalance > 0.2 {
+    ///         println!("unfair map: {:?}", fairness.regions);
+    ///     }
+    /// }
+    /// ```
+    pub fn fairness<C, R>(&self, spawns: &[(usize, usize)], cost: C, is_resource: R) -> Fairness
+    where
+        C: Fn(usize) -> Option<u32>,
+        R: Fn(usize) -> bool,
+    {
+        let distances: Vec<Vec<u32>> = spawns.iter().map(|&spawn| self.dijkstra_map(&[spawn], &cost)).collect();
+        let mut regions = vec![SpawnRegion::default(); spawns.len()];
+        let mut total_distances = vec![0u64; spawns.len()];
+        for (pos, &value) in self.map.iter().enumerate() {
+            // nearest spawn, ties go to the spawn listed first
+            let nearest = (0..spawns.len())
+                .filter(|&spawn| distances[spawn][pos] != u32::MAX)
+                .min_by_key(|&spawn| distances[spawn][pos]);
+            if let Some(spawn) = nearest {
+                regions[spawn].cells += 1;
+                if is_resource(value) {
+                    regions[spawn].resources += 1;
+                    total_distances[spawn] += distances[spawn][pos] as u64;
+                }
+            }
+        }
+        for (region, &total) in regions.iter_mut().zip(&total_distances) {
+            if region.resources > 0 {
+                region.mean_distance = total as f64 / region.resources as f64;
+            }
+        }
+        let imbalance = [
+            spread(regions.iter().map(|region| region.cells as f64)),
+            spread(regions.iter().map(|region| region.resources as f64)),
+            spread(regions.iter().map(|region| region.mean_distance)),
+        ]
+        .iter()
+        .cloned()
+        .fold(0., f64::max);
+        Fairness { regions, imbalance }
+    }
+}
+
+// (max - min) / max, 0 when everything is 0
+fn spread<I: Iterator<Item = f64>>(values: I) -> f64 {
+    let (min, max) = values.fold((f64::INFINITY, 0f64), |(min, max), value| (min.min(value), max.max(value)));
+    if max > 0. {
+        (max - min) / max
+    } else {
+        0.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fairness() {
+        let mut generator = Generator::new().with_size(6, 1);
+        generator.map = vec![0, 2, 0, 0, 2, 0];
+        let fair = generator.fairness(&[(0, 0), (5, 0)], |_| Some(1), |value| value == 2);
+        assert_eq!(fair.regions[0], SpawnRegion { cells: 3, resources: 1, mean_distance: 1. });
+        assert_eq!(fair.imbalance, 0.);
+
+        generator.set(4, 0, 0);
+        let unfair = generator.fairness(&[(0, 0), (5, 0)], |_| Some(1), |value| value == 2);
+        assert_eq!(unfair.regions[1].resources, 0);
+        assert_eq!(unfair.imbalance, 1.);
+    }
+}
@@ -0,0 +1,63 @@
+//! Vector fields built from noise.
+
+use crate::{terrain, Generator, Stage};
+
+impl Generator {
+    /// Builds a divergence-free flow field from curl noise and stores it as the
+    /// `"flow_x"` and `"flow_y"` fields, see `field()`. The flow swirls around
+    /// without sources or sinks, which makes it a good wind map, a bias for
+    /// meandering rivers or a direction for particles to drift in. Noise is
+    /// shaped by the frequency, octaves and sampling of the noise options, and
+    /// the longest vector has length 1.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let generator = Generator::new().with_size(40, 20).spawn_flow_field();
+    ///     let (x, y) = (generator.field("flow_x").unwrap(), generator.field("flow_y").unwrap());
+    ///     println!("wind at the center blows towards ({:.2}, {:.2})", x[10 * 40 + 20], y[10 * 40 + 20]);
+    /// }
+    /// ```
+    pub fn spawn_flow_field(self) -> Self {
+        let stage = Stage::FlowField {
+            options: self.noise_options.clone(),
+        };
+        self.stage(stage, |mut generator| {
+            if generator.map.is_empty() {
+                return generator;
+            }
+            // the curl of a potential, rotating its gradient by 90 degrees
+            let potential = generator.noise_field("flow_field");
+            let gradient = terrain::gradient(&potential, generator.width, generator.height);
+            let longest = gradient.iter().map(|(dx, dy)| dx.hypot(*dy)).fold(0., f64::max);
+            let scale = if longest > 0. { 1. / longest } else { 0. };
+            let (flow_x, flow_y) = gradient.into_iter().map(|(dx, dy)| (dy * scale, -dx * scale)).unzip();
+            generator.set_field("flow_x", flow_x);
+            generator.set_field("flow_y", flow_y);
+            generator
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flow_field() {
+        let generator = Generator::new().with_size(30, 20).spawn_flow_field();
+        let (flow_x, flow_y) = (generator.field("flow_x").unwrap(), generator.field("flow_y").unwrap());
+        let longest = flow_x.iter().zip(flow_y).map(|(x, y)| x.hypot(*y)).fold(0., f64::max);
+        assert!((longest - 1.).abs() < 1e-9);
+
+        // central differences of the flow cancel out away from the edges
+        let at = |field: &[f64], x: usize, y: usize| field[x + y * 30];
+        for y in 2..18 {
+            for x in 2..28 {
+                let divergence = (at(flow_x, x + 1, y) - at(flow_x, x - 1, y)) + (at(flow_y, x, y + 1) - at(flow_y, x, y - 1));
+                assert!(divergence.abs() < 1e-9);
+            }
+        }
+    }
+}
@@ -0,0 +1,238 @@
+//! Field of view with recursive shadowcasting.
+
+use crate::{Generator, Stage};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use smart_default::*;
+
+/// Transforms mapping the first octant onto each of the eight octants.
+const OCTANTS: [(isize, isize, isize, isize); 8] = [
+    (1, 0, 0, 1),
+    (0, 1, 1, 0),
+    (0, -1, 1, 0),
+    (-1, 0, 0, 1),
+    (-1, 0, 0, -1),
+    (0, -1, -1, 0),
+    (0, 1, -1, 0),
+    (1, 0, 0, -1),
+];
+
+/// How [Generator::light](struct.Generator.html#method.light) lights the map.
+#[derive(Debug, Clone, PartialEq, SmartDefault)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LightOptions {
+    /// Value of light sources, such as torches. Default is 5.
+    #[default = 5]
+    pub source: usize,
+    /// Value of cells light doesn't pass through, which are still lit themselves.
+    /// Default is 0.
+    pub wall: usize,
+    /// How far light reaches from a source, in cells. Default is 8.
+    #[default = 8]
+    pub radius: usize,
+    /// Brightest light level, right at a source. Default is 9.
+    #[default = 9]
+    pub levels: usize,
+}
+
+struct Shadowcast<'a, F> {
+    generator: &'a Generator,
+    origin: (isize, isize),
+    radius: isize,
+    blocks_sight: F,
+    visible: Vec<bool>,
+}
+
+impl<'a, F: Fn(usize) -> bool> Shadowcast<'a, F> {
+    fn blocks(&self, x: isize, y: isize) -> bool {
+        if x < 0 || y < 0 {
+            return true;
+        }
+        match self.generator.get_checked(x as usize, y as usize) {
+            Some(value) => (self.blocks_sight)(value),
+            None => true,
+        }
+    }
+    fn reveal(&mut self, x: isize, y: isize) {
+        if x >= 0 && y >= 0 && (x as usize) < self.generator.width && (y as usize) < self.generator.height {
+            self.visible[x as usize + y as usize * self.generator.width] = true;
+        }
+    }
+    // scans one octant row by row, narrowing the visible slopes as walls are found
+    fn cast(&mut self, row: isize, mut start: f64, end: f64, octant: (isize, isize, isize, isize)) {
+        if start < end {
+            return;
+        }
+        let (xx, xy, yx, yy) = octant;
+        let (ox, oy) = self.origin;
+        let mut next_start = start;
+        for distance in row..=self.radius {
+            let dy = -distance;
+            let mut blocked = false;
+            for dx in -distance..=0 {
+                let left_slope = (dx as f64 - 0.5) / (dy as f64 + 0.5);
+                let right_slope = (dx as f64 + 0.5) / (dy as f64 - 0.5);
+                if start < right_slope {
+                    continue;
+                } else if end > left_slope {
+                    break;
+                }
+
+                let x = ox + dx * xx + dy * xy;
+                let y = oy + dx * yx + dy * yy;
+                if dx * dx + dy * dy <= self.radius * self.radius {
+                    self.reveal(x, y);
+                }
+
+                let blocks = self.blocks(x, y);
+                if blocked {
+                    if blocks {
+                        next_start = right_slope;
+                    } else {
+                        blocked = false;
+                        start = next_start;
+                    }
+                } else if blocks && distance < self.radius {
+                    blocked = true;
+                    self.cast(distance + 1, start, left_slope, octant);
+                    next_start = right_slope;
+                }
+            }
+            if blocked {
+                break;
+            }
+        }
+    }
+}
+
+impl Generator {
+    /// Returns every cell visible from `origin` within `radius` cells, using
+    /// recursive shadowcasting. `blocks_sight(value)` decides which cells can't be seen
+    /// through, those cells are still visible themselves. Cells outside the map block
+    /// sight, and `origin` is always visible.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let size = Size::new((4, 4), (10, 10));
+    ///     let generator = Generator::new()
+    ///         .with_size(30, 20)
+    ///         .spawn_rooms(1, 3, &size);
+    ///     let visible = generator.fov((15, 10), 8, |value| value == 1);
+    ///     println!("{} cells visible", visible.len());
+    /// }
+    /// ```
+    pub fn fov<F: Fn(usize) -> bool>(&self, origin: (usize, usize), radius: usize, blocks_sight: F) -> Vec<(usize, usize)> {
+        let mut shadowcast = Shadowcast {
+            generator: self,
+            origin: (origin.0 as isize, origin.1 as isize),
+            radius: radius as isize,
+            blocks_sight,
+            visible: vec![false; self.map.len()],
+        };
+        shadowcast.reveal(origin.0 as isize, origin.1 as isize);
+        for &octant in OCTANTS.iter() {
+            shadowcast.cast(1, 1., 0., octant);
+        }
+        shadowcast
+            .visible
+            .iter()
+            .enumerate()
+            .filter(|&(_, &visible)| visible)
+            .map(|(pos, _)| (pos % self.width, pos / self.width))
+            .collect()
+    }
+    /// Works out how brightly lit every cell is by the light sources on the map,
+    /// and stores it as the `"light"` layer, for ambient lighting in roguelikes.
+    /// Every source lights the cells it can see within `radius`, dimming linearly
+    /// with distance from `levels` at the source to nothing just past `radius`,
+    /// and walls cast shadows like in `fov(...)`. Light from several sources adds
+    /// up, up to `levels`.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let generator = Generator::new()
+    ///         .with_size(40, 20)
+    ///         .spawn_perlin_bands(&[(0.4, 1)])
+    ///         .fill_weighted(&[(1, 99.), (5, 1.)])
+    ///         .light(LightOptions::default());
+    ///     generator.show_layers(&["light"]);
+    /// }
+    /// ```
+    pub fn light(self, options: LightOptions) -> Self {
+        self.stage(Stage::Light { options: options.clone() }, |mut generator| {
+            let LightOptions {
+                source,
+                wall,
+                radius,
+                levels,
+            } = options;
+            let mut light = vec![0.; generator.map.len()];
+            for (x, y) in generator.positions_of(source) {
+                for (lx, ly) in generator.fov((x, y), radius, |value| value == wall) {
+                    let distance = ((lx as f64 - x as f64).powi(2) + (ly as f64 - y as f64).powi(2)).sqrt();
+                    light[lx + ly * generator.width] += (1. - distance / (radius as f64 + 1.)).max(0.);
+                }
+            }
+            let light = light
+                .into_iter()
+                .map(|amount: f64| (amount.min(1.) * levels as f64).round() as usize)
+                .collect();
+            generator.set_layer("light", light);
+            generator
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fov_behind_wall() {
+        let mut generator = Generator::new().with_size(7, 3);
+        generator.map = vec![
+            0,0,0,0,0,0,0,
+            0,0,0,1,0,0,0,
+            0,0,0,0,0,0,0,
+        ];
+        let visible = generator.fov((1, 1), 10, |value| value == 1);
+        assert!(visible.contains(&(1, 1)));
+        assert!(visible.contains(&(3, 1)));
+        assert!(visible.contains(&(6, 0)));
+        assert!(!visible.contains(&(5, 1)));
+
+        let visible = generator.fov((1, 1), 1, |value| value == 1);
+        assert_eq!(visible.len(), 5);
+    }
+    #[test]
+    fn light() {
+        // a torch left of a wall in an open room
+        let mut generator = Generator::new().with_size(7, 3).fill_rect(0, 0, 7, 3, 1);
+        generator.set(3, 1, 0);
+        generator.set(1, 1, 5);
+        let options = LightOptions {
+            radius: 4,
+            levels: 5,
+            ..LightOptions::default()
+        };
+        let generator = generator.light(options.clone());
+        let light = generator.layer("light").unwrap();
+        assert_eq!(light[1 + 7], 5);
+        assert_eq!(light[2 + 7], 4);
+        // the wall is lit, the cells behind it are in its shadow
+        assert!(light[3 + 7] > 0);
+        assert_eq!(light[4 + 7], 0);
+        assert_eq!(light[6], 0);
+
+        // a second torch right behind the wall
+        let mut generator = generator;
+        generator.set(4, 1, 5);
+        let light = generator.light(options).layer("light").unwrap().to_vec();
+        assert_eq!(light[4 + 7], 5);
+        assert_eq!(light[2 + 7], 4);
+    }
+}
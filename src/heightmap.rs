@@ -0,0 +1,270 @@
+//! Heightmap sources writing the `"elevation"` field, and turning fields into tiles.
+
+use crate::scatter::pick_weighted;
+use crate::{classifier, Generator, Stage, NEIGHBORS_8};
+use rand::prelude::*;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use smart_default::*;
+use std::ops::RangeInclusive;
+
+/// How [Generator::spawn_deposition](struct.Generator.html#method.spawn_deposition)
+/// drops its particles.
+#[derive(Debug, Clone, PartialEq, SmartDefault)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DepositionOptions {
+    /// Number of places particles are dropped around, each grows a peak. Default is 3.
+    #[default = 3]
+    pub vents: usize,
+    /// Greatest distance in cells from its vent a particle is dropped at. Default is 2.
+    #[default = 2]
+    pub spread: usize,
+    /// Height difference, in particles, a particle rests on without rolling further
+    /// down. Higher values make steeper peaks. Default is 1.
+    #[default = 1.0]
+    pub roughness: f64,
+}
+
+/// A range of field values and the `(value, weight)` pairs picked inside it, see
+/// [Generator::classify_banded](struct.Generator.html#method.classify_banded).
+pub type Band<'a> = (RangeInclusive<f64>, &'a [(usize, f64)]);
+
+// stretches `values` to lie between 0 and 1
+fn normalize(values: &mut [f64]) {
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let range = if max > min { max - min } else { 1. };
+    values.iter_mut().for_each(|value| *value = (*value - min) / range);
+}
+
+impl Generator {
+    /// Builds a heightmap by fault formation and stores it as the `"elevation"`
+    /// field, between 0 and 1. Each iteration cuts the map along a random line,
+    /// raising one side and lowering the other, by less and less as iterations go
+    /// on. Compared to noise it gives long straight ridges and escarpments. Turn it
+    /// into tiles with `classify("elevation", ...)`.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     Generator::new()
+    ///         .with_size(40, 20)
+    ///         .spawn_faults(200)
+    ///         .classify("elevation", &[(0.7, 2), (0.4, 1)])
+    ///         .show();
+    /// }
+    /// ```
+    pub fn spawn_faults(self, iterations: usize) -> Self {
+        self.stage(Stage::Faults { iterations }, |mut generator| {
+            if generator.map.is_empty() {
+                return generator;
+            }
+            let (width, height) = (generator.width, generator.height);
+            let mut elevation: Vec<f64> = vec![0.; generator.map.len()];
+            generator.with_stage_rng("faults", |generator, rng| {
+                for iteration in 0..iterations {
+                    if generator.is_cancelled() || generator.is_out_of_time() {
+                        break;
+                    }
+                    generator.report(iteration as f32 / iterations as f32);
+                    let (px, py) = (rng.gen_range(0., width as f64), rng.gen_range(0., height as f64));
+                    let angle = rng.gen_range(0., std::f64::consts::TAU);
+                    let (dx, dy) = (angle.cos(), angle.sin());
+                    let displacement = 1. - iteration as f64 / iterations as f64;
+                    for (pos, value) in elevation.iter_mut().enumerate() {
+                        let (x, y) = ((pos % width) as f64 - px, (pos / width) as f64 - py);
+                        if dx * y - dy * x > 0. {
+                            *value += displacement;
+                        } else {
+                            *value -= displacement;
+                        }
+                    }
+                }
+            });
+            normalize(&mut elevation);
+            generator.set_field("elevation", elevation);
+            generator
+        })
+    }
+    /// Builds a heightmap by particle deposition and stores it as the `"elevation"`
+    /// field, between 0 and 1. Each of the `particles` is dropped near a random vent
+    /// and rolls downhill until it comes to rest, where it stacks on top of the
+    /// others, piling up into volcanic islands with gentle slopes.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     Generator::new()
+    ///         .with_size(40, 20)
+    ///         .spawn_deposition(1500, DepositionOptions::default())
+    ///         .classify("elevation", &[(0.6, 2), (0.1, 1)])
+    ///         .show();
+    /// }
+    /// ```
+    pub fn spawn_deposition(self, particles: usize, options: DepositionOptions) -> Self {
+        let stage = Stage::Deposition {
+            particles,
+            options: options.clone(),
+        };
+        self.stage(stage, |mut generator| {
+            if generator.map.is_empty() {
+                return generator;
+            }
+            let (width, height) = (generator.width, generator.height);
+            let mut elevation: Vec<f64> = vec![0.; generator.map.len()];
+            generator.with_stage_rng("deposition", |generator, rng| {
+                let vents: Vec<(usize, usize)> = (0..options.vents.max(1))
+                    .map(|_| (rng.gen_range(0, width), rng.gen_range(0, height)))
+                    .collect();
+                let spread = options.spread as isize;
+                for particle in 0..particles {
+                    if particle % 1024 == 0 {
+                        if generator.is_cancelled() || generator.is_out_of_time() {
+                            break;
+                        }
+                        generator.report(particle as f32 / particles as f32);
+                    }
+                    let (vx, vy) = vents[rng.gen_range(0, vents.len())];
+                    let mut x = (vx as isize + rng.gen_range(-spread, spread + 1)).clamp(0, width as isize - 1) as usize;
+                    let mut y = (vy as isize + rng.gen_range(-spread, spread + 1)).clamp(0, height as isize - 1) as usize;
+                    // roll to a random neighbour that's more than `roughness` lower
+                    loop {
+                        let here = elevation[x + y * width];
+                        let lower: Vec<(usize, usize)> = NEIGHBORS_8
+                            .iter()
+                            .map(|&(dx, dy)| (x as isize + dx, y as isize + dy))
+                            .filter(|&(nx, ny)| nx >= 0 && ny >= 0 && nx < width as isize && ny < height as isize)
+                            .map(|(nx, ny)| (nx as usize, ny as usize))
+                            .filter(|&(nx, ny)| here - elevation[nx + ny * width] > options.roughness)
+                            .collect();
+                        match lower.choose(rng) {
+                            Some(&(nx, ny)) => {
+                                x = nx;
+                                y = ny;
+                            }
+                            None => break,
+                        }
+                    }
+                    elevation[x + y * width] += 1.;
+                }
+            });
+            normalize(&mut elevation);
+            generator.set_field("elevation", elevation);
+            generator
+        })
+    }
+    /// Same as `spawn_perlin_bands(...)`, except the values come from the field
+    /// called `name`, such as the `"elevation"` of a heightmap source. Leaves the
+    /// map as it is if there's no such field.
+    pub fn classify(self, name: &str, bands: &[(f64, usize)]) -> Self {
+        let classify = classifier(bands);
+        let stage = Stage::Classify {
+            field: name.to_string(),
+            bands: bands.to_vec(),
+        };
+        self.stage(stage, |mut generator| {
+            if let Some(field) = generator.fields.remove(name) {
+                generator.map.iter_mut().zip(&field).for_each(|(value, &height)| *value = classify(height));
+                generator.fields.insert(name.to_string(), field);
+            }
+            generator
+        })
+    }
+    /// Like `classify(...)`, except every band picks its tile at random from a
+    /// weighted table instead of always giving the same one. `bands` lists the range
+    /// of every band with its `(value, weight)` pairs, so
+    /// `(0.7..=1., &[(3, 70.), (5, 25.), (4, 5.)])` turns 70% of the peaks into rock,
+    /// 25% into snow and 5% into ore. A value inside several ranges uses the first,
+    /// and cells outside every range keep their value.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     Generator::new()
+    ///         .with_size(40, 20)
+    ///         .spawn_faults(100)
+    ///         .classify_banded(
+    ///             "elevation",
+    ///             &[
+    ///                 (0.0..=0.4, &[(0, 1.)]),
+    ///                 (0.4..=0.7, &[(2, 9.), (6, 1.)]),
+    ///                 (0.7..=1.0, &[(3, 70.), (5, 25.), (4, 5.)]),
+    ///             ],
+    ///         )
+    ///         .show();
+    /// }
+    /// ```
+    pub fn classify_banded(self, name: &str, bands: &[Band]) -> Self {
+        let stage = Stage::ClassifyBanded {
+            field: name.to_string(),
+            bands: bands.iter().map(|(range, choices)| (range.clone(), choices.to_vec())).collect(),
+        };
+        self.stage(stage, |mut generator| {
+            if let Some(field) = generator.fields.remove(name) {
+                generator.with_stage_rng("classify_banded", |generator, rng| {
+                    for (value, height) in generator.map.iter_mut().zip(&field) {
+                        let band = bands.iter().find(|(range, _)| range.contains(height));
+                        if let Some(picked) = band.and_then(|(_, choices)| pick_weighted(choices, rng)) {
+                            *value = picked;
+                        }
+                    }
+                });
+                generator.fields.insert(name.to_string(), field);
+            }
+            generator
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deposition() {
+        let options = DepositionOptions {
+            vents: 1,
+            spread: 0,
+            roughness: 1.,
+        };
+        let generator = Generator::new().with_size(15, 15).spawn_deposition(300, options);
+        let elevation = generator.field("elevation").unwrap();
+        // particles spill over from the peak into a pile around it
+        assert!(elevation.iter().all(|height| (0. ..=1.).contains(height)));
+        let peak = elevation.iter().position(|&height| height == 1.).unwrap();
+        let (px, py) = (peak % 15, peak / 15);
+        assert!(generator.neighbors8(px, py).all(|(x, y, _)| elevation[x + y * 15] > 0.));
+    }
+    #[test]
+    fn faults() {
+        let generator = Generator::new().with_size(20, 10).with_seed(2).spawn_faults(50);
+        let elevation = generator.field("elevation").unwrap();
+        assert!(elevation.iter().all(|height| (0. ..=1.).contains(height)));
+        assert!(elevation.contains(&0.) && elevation.contains(&1.));
+
+        let generator = generator.classify("elevation", &[(0.5, 1)]);
+        let elevation = generator.field("elevation").unwrap();
+        assert!(generator.map.iter().zip(elevation).all(|(&value, &height)| (value == 1) == (height > 0.5)));
+        assert_eq!(generator.recipe().stages.len(), 2);
+    }
+    #[test]
+    fn classify_banded() {
+        let generator = Generator::new()
+            .with_size(20, 10)
+            .spawn_faults(50)
+            .fill_rect(0, 0, 20, 10, 9)
+            .classify_banded("elevation", &[(0.0..=0.5, &[(1, 1.)]), (0.6..=1.0, &[(2, 1.), (3, 1.)])]);
+        let elevation = generator.field("elevation").unwrap();
+        for (&value, &height) in generator.map.iter().zip(elevation) {
+            match height {
+                height if height <= 0.5 => assert_eq!(value, 1),
+                height if height < 0.6 => assert_eq!(value, 9),
+                _ => assert!(value == 2 || value == 3),
+            }
+        }
+        assert!(generator.map.contains(&2) && generator.map.contains(&3));
+    }
+}
@@ -0,0 +1,452 @@
+//! Rivers and drainage following the `"elevation"` field.
+
+use crate::{Bridge, Generator, Stage, NEIGHBORS_4, NEIGHBORS_8};
+use smart_default::*;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, VecDeque};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// How [Generator::carve_rivers](struct.Generator.html#method.carve_rivers) lays
+/// out rivers.
+#[derive(Debug, Clone, PartialEq, SmartDefault)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RiverOptions {
+    /// Value of river cells. Default is 2.
+    #[default = 2]
+    pub water: usize,
+    /// Elevation below which lies the sea, where rivers end. Default is 0.3.
+    #[default = 0.3]
+    pub sea_level: f64,
+    /// Amount of cells that must drain through a cell for a river to run through
+    /// it. Lower values give more and longer rivers. Default is 30.
+    #[default = 30]
+    pub min_flow: usize,
+    /// Rivers draining at least this many cells are 3 cells wide. Default is 300.
+    #[default = 300]
+    pub wide_flow: usize,
+    /// Rivers closer than this to the sea level split into a delta. Default is 0.05.
+    #[default = 0.05]
+    pub delta_height: f64,
+}
+
+/// What [Generator::place_bridges](struct.Generator.html#method.place_bridges)
+/// looks for and builds.
+#[derive(Debug, Clone, PartialEq, SmartDefault)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BridgeOptions {
+    /// Value of roads and corridors. Default is 4, the roads of `spawn_villages`.
+    #[default = 4]
+    pub road: usize,
+    /// Value of deep water, which needs a bridge. Default is 2, the rivers of
+    /// `carve_rivers`.
+    #[default = 2]
+    pub water: usize,
+    /// Value of shallow water, which can be waded through, if any. Default is None.
+    pub shallow: Option<usize>,
+    /// Value of bridge cells. Default is 5.
+    #[default = 5]
+    pub bridge: usize,
+    /// Value of ford cells. Default is 6.
+    #[default = 6]
+    pub ford: usize,
+    /// Longest stretch of water, in cells, that gets bridged. Default is 6.
+    #[default = 6]
+    pub max_span: usize,
+}
+
+// cell on the flood front, the lowest one comes out of the heap first
+struct Front(f64, usize);
+
+impl PartialEq for Front {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Front {}
+
+impl PartialOrd for Front {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Front {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.0.total_cmp(&self.0).then(other.1.cmp(&self.1))
+    }
+}
+
+// raises every pit and flat just enough that water can run off the edge of the
+// map from every cell, by flooding inwards from the edges
+pub(crate) fn fill_depressions(elevation: &[f64], width: usize, height: usize) -> Vec<f64> {
+    let mut filled = elevation.to_vec();
+    let mut done = vec![false; elevation.len()];
+    let mut front = BinaryHeap::new();
+    for pos in 0..elevation.len() {
+        let (x, y) = (pos % width, pos / width);
+        if x == 0 || y == 0 || x + 1 == width || y + 1 == height {
+            done[pos] = true;
+            front.push(Front(elevation[pos], pos));
+        }
+    }
+    while let Some(Front(level, pos)) = front.pop() {
+        let (x, y) = ((pos % width) as isize, (pos / width) as isize);
+        for &(dx, dy) in &NEIGHBORS_8 {
+            let (nx, ny) = (x + dx, y + dy);
+            if nx < 0 || ny < 0 || nx >= width as isize || ny >= height as isize {
+                continue;
+            }
+            let next = nx as usize + ny as usize * width;
+            if !done[next] {
+                done[next] = true;
+                // a tiny rise keeps filled basins sloping towards their outlet
+                filled[next] = filled[next].max(level + 1e-9);
+                front.push(Front(filled[next], next));
+            }
+        }
+    }
+    filled
+}
+
+// neighbor of every cell that lies lower than the cell itself in the steepest
+// direction, diagonal drops divided by their longer distance, None for pits and flats
+pub(crate) fn downstream(elevation: &[f64], width: usize, height: usize) -> Vec<Option<usize>> {
+    (0..elevation.len())
+        .map(|pos| {
+            let steepness = |next: usize| {
+                let diagonal = next % width != pos % width && next / width != pos / width;
+                let distance = if diagonal { std::f64::consts::SQRT_2 } else { 1. };
+                (elevation[pos] - elevation[next]) / distance
+            };
+            // the first of equally steep neighbors
+            lower_neighbors(elevation, width, height, pos).min_by(|&a, &b| steepness(b).total_cmp(&steepness(a)))
+        })
+        .collect()
+}
+
+// sends cells of flats without a lower neighbor towards the nearest cell of the
+// same flat that has one, so flats drain as a whole instead of each cell being a
+// pit of its own
+fn route_flats(elevation: &[f64], downstream: &mut [Option<usize>], width: usize, height: usize) {
+    let mut open: VecDeque<usize> = (0..elevation.len()).filter(|&pos| downstream[pos].is_some()).collect();
+    while let Some(pos) = open.pop_front() {
+        for next in same_height_neighbors(elevation, width, height, pos) {
+            if downstream[next].is_none() {
+                downstream[next] = Some(pos);
+                open.push_back(next);
+            }
+        }
+    }
+}
+
+fn same_height_neighbors(elevation: &[f64], width: usize, height: usize, pos: usize) -> impl Iterator<Item = usize> + '_ {
+    let (x, y) = ((pos % width) as isize, (pos / width) as isize);
+    NEIGHBORS_8
+        .iter()
+        .map(move |&(dx, dy)| (x + dx, y + dy))
+        .filter(move |&(nx, ny)| nx >= 0 && ny >= 0 && nx < width as isize && ny < height as isize)
+        .map(move |(nx, ny)| nx as usize + ny as usize * width)
+        .filter(move |&next| elevation[next] == elevation[pos])
+}
+
+// amount of cells draining through every cell, counting the cell itself
+pub(crate) fn accumulation(elevation: &[f64], downstream: &[Option<usize>]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..elevation.len()).collect();
+    order.sort_by(|&a, &b| elevation[b].total_cmp(&elevation[a]));
+    let mut flow = vec![1; elevation.len()];
+    for pos in order {
+        if let Some(next) = downstream[pos] {
+            flow[next] += flow[pos];
+        }
+    }
+    flow
+}
+
+fn lower_neighbors(elevation: &[f64], width: usize, height: usize, pos: usize) -> impl Iterator<Item = usize> + '_ {
+    let (x, y) = ((pos % width) as isize, (pos / width) as isize);
+    NEIGHBORS_8
+        .iter()
+        .map(move |&(dx, dy)| (x + dx, y + dy))
+        .filter(move |&(nx, ny)| nx >= 0 && ny >= 0 && nx < width as isize && ny < height as isize)
+        .map(move |(nx, ny)| nx as usize + ny as usize * width)
+        .filter(move |&next| elevation[next] < elevation[pos])
+}
+
+impl Generator {
+    /// Carves rivers down the `"elevation"` field of a heightmap source such as
+    /// `spawn_faults()`. Every cell drains into its lowest neighbor, and rivers run
+    /// where enough cells drain through, so streams from several sources merge
+    /// into fewer, wider rivers downstream like a real drainage network. Basins
+    /// are filled up to their lowest rim first, so rivers run through them on
+    /// their way to the sea or the edge of the map. Close to the sea a river
+    /// spreads into its two lowest neighbors instead of one, fanning out into a
+    /// delta. Leaves the map as it is without an elevation field.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     Generator::new()
+    ///         .with_size(60, 30)
+    ///         .spawn_faults(200)
+    ///         .classify("elevation", &[(0.7, 3), (0.3, 1)])
+    ///         .carve_rivers(RiverOptions::default())
+    ///         .show();
+    /// }
+    /// ```
+    pub fn carve_rivers(self, options: RiverOptions) -> Self {
+        self.stage(Stage::Rivers { options: options.clone() }, |mut generator| {
+            let elevation = match generator.fields.remove("elevation") {
+                Some(elevation) => elevation,
+                None => return generator,
+            };
+            let (width, height) = (generator.width, generator.height);
+            let filled = fill_depressions(&elevation, width, height);
+            let downstream = downstream(&filled, width, height);
+            let flow = accumulation(&filled, &downstream);
+
+            // downhill order, so delta branches are known before the cells below
+            let mut order: Vec<usize> = (0..filled.len()).collect();
+            order.sort_by(|&a, &b| filled[b].total_cmp(&filled[a]));
+            let mut river = vec![false; elevation.len()];
+            for pos in order {
+                if elevation[pos] < options.sea_level {
+                    continue;
+                }
+                if flow[pos] >= options.min_flow.max(1) {
+                    river[pos] = true;
+                }
+                if river[pos] && elevation[pos] < options.sea_level + options.delta_height {
+                    let mut lower: Vec<usize> = lower_neighbors(&filled, width, height, pos).collect();
+                    lower.sort_by(|&a, &b| filled[a].total_cmp(&filled[b]));
+                    lower.iter().take(2).for_each(|&next| river[next] = true);
+                }
+            }
+            for pos in (0..river.len()).filter(|&pos| river[pos] && elevation[pos] >= options.sea_level) {
+                generator.map[pos] = options.water;
+                if flow[pos] >= options.wide_flow {
+                    let (x, y) = ((pos % width) as isize, (pos / width) as isize);
+                    for &(dx, dy) in &NEIGHBORS_4 {
+                        let (nx, ny) = (x + dx, y + dy);
+                        if nx >= 0 && ny >= 0 && nx < width as isize && ny < height as isize {
+                            let next = nx as usize + ny as usize * width;
+                            if elevation[next] >= options.sea_level {
+                                generator.map[next] = options.water;
+                            }
+                        }
+                    }
+                }
+            }
+            generator.fields.insert("elevation".into(), elevation);
+            generator
+        })
+    }
+    /// Mends roads cut by water. Wherever a straight stretch of water of at most
+    /// `max_span` cells has a road on both ends, the water is turned into a ford
+    /// if it's all shallow, and into a bridge otherwise, so roads and corridors
+    /// stay connected after rivers are carved through them. Every crossing is
+    /// listed in `artifacts().bridges`.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let generator = Generator::new()
+    ///         .with_size(60, 30)
+    ///         .spawn_faults(200)
+    ///         .classify("elevation", &[(0.3, 1)])
+    ///         .spawn_villages(3, VillageOptions::default())
+    ///         .carve_rivers(RiverOptions::default())
+    ///         .place_bridges(BridgeOptions::default());
+    ///     generator.show();
+    ///     println!("{} bridges", generator.artifacts().bridges.len());
+    /// }
+    /// ```
+    pub fn place_bridges(self, options: BridgeOptions) -> Self {
+        self.stage(Stage::Bridges { options: options.clone() }, |mut generator| {
+            let (width, height) = (generator.width, generator.height);
+            let is_water = |value: usize| value == options.water || Some(value) == options.shallow;
+            let mut bridges = vec![];
+            for (x, y) in generator.positions_of(options.road) {
+                // right and down only, so every crossing is found once
+                for &(dx, dy) in &[(1, 0), (0, 1)] {
+                    let cells: Vec<(usize, usize)> = (1..)
+                        .map(|step| (x + dx * step, y + dy * step))
+                        .take_while(|&(x, y)| x < width && y < height && is_water(generator.get(x, y)))
+                        .take(options.max_span + 1)
+                        .collect();
+                    let end = (x + dx * (cells.len() + 1), y + dy * (cells.len() + 1));
+                    let spanned = !cells.is_empty() && cells.len() <= options.max_span;
+                    if !spanned || generator.get_checked(end.0, end.1) != Some(options.road) {
+                        continue;
+                    }
+                    let ford = cells.iter().all(|&(x, y)| Some(generator.get(x, y)) == options.shallow);
+                    bridges.push(Bridge { cells, ford });
+                }
+            }
+            for bridge in &bridges {
+                let value = if bridge.ford { options.ford } else { options.bridge };
+                for &(x, y) in &bridge.cells {
+                    generator.set(x, y, value);
+                }
+            }
+            generator.artifacts.bridges.extend(bridges);
+            generator
+        })
+    }
+    /// Labels every cell with the drainage basin it drains into, following the
+    /// steepest way down the `"elevation"` field until it reaches a pit, and stores
+    /// the labels as the `"basins"` layer. Flats drain towards their lowest edge,
+    /// and a flat with no way down is one pit, such as the surface of a lake. Every
+    /// basin is a watershed draining into one lake or stretch of coast, good for
+    /// naming them or deciding which lake a river feeds. Labels start at 0,
+    /// numbered in row-major order of the first cell of each pit. Does nothing
+    /// without an elevation field.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     Generator::new()
+    ///         .with_size(40, 20)
+    ///         .spawn_faults(100)
+    ///         .drainage_basins()
+    ///         .show_layers(&["basins"]);
+    /// }
+    /// ```
+    pub fn drainage_basins(self) -> Self {
+        self.stage(Stage::Basins, |mut generator| {
+            let (width, height) = (generator.width, generator.height);
+            let elevation = match generator.field("elevation") {
+                Some(elevation) => elevation,
+                None => return generator,
+            };
+            let mut downstream = downstream(elevation, width, height);
+            route_flats(elevation, &mut downstream, width, height);
+            // cells left without a way down are pits, connected ones forming one basin
+            let mut basins = vec![usize::MAX; elevation.len()];
+            let mut count = 0;
+            for pos in 0..basins.len() {
+                if downstream[pos].is_some() || basins[pos] != usize::MAX {
+                    continue;
+                }
+                basins[pos] = count;
+                let mut pit = vec![pos];
+                while let Some(cell) = pit.pop() {
+                    for next in same_height_neighbors(elevation, width, height, cell) {
+                        if basins[next] == usize::MAX {
+                            basins[next] = count;
+                            pit.push(next);
+                        }
+                    }
+                }
+                count += 1;
+            }
+            for pos in 0..basins.len() {
+                let mut path = vec![];
+                let mut cell = pos;
+                while basins[cell] == usize::MAX {
+                    path.push(cell);
+                    cell = downstream[cell].unwrap();
+                }
+                for on_path in path {
+                    basins[on_path] = basins[cell];
+                }
+            }
+            generator.set_layer("basins", basins);
+            generator
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn carve_rivers() {
+        // a slope down to the left with two valleys merging halfway
+        let (width, height) = (20, 9);
+        let elevation: Vec<f64> = (0..width * height)
+            .map(|pos| {
+                let (x, y) = ((pos % width) as f64, (pos / width) as f64);
+                let valley = if x < 10. { (y - 4.).abs() } else { (y - 1.).abs().min((y - 7.).abs()) };
+                x * 0.05 + valley * 0.03
+            })
+            .collect();
+        let slope = || {
+            let mut generator = Generator::new().with_size(width, height);
+            generator.set_field("elevation", elevation.clone());
+            generator
+        };
+        let options = RiverOptions {
+            sea_level: 0.,
+            min_flow: 5,
+            wide_flow: usize::MAX,
+            ..RiverOptions::default()
+        };
+        let generator = slope().carve_rivers(options.clone());
+        // both valleys carry a river, which join into one
+        assert_eq!(generator.get(15, 1), 2);
+        assert_eq!(generator.get(15, 7), 2);
+        assert_eq!(generator.get(5, 4), 2);
+        assert_eq!(generator.get(5, 1), 0);
+
+        // the same river fans out as it nears the sea, which it doesn't flood
+        let generator = slope().carve_rivers(RiverOptions {
+            sea_level: 0.1,
+            delta_height: 0.15,
+            ..options
+        });
+        assert_eq!((0..height).filter(|&y| generator.get(1, y) == 2).count(), 0);
+        assert!((0..height).filter(|&y| generator.get(2, y) == 2).count() > 1);
+    }
+
+    #[test]
+    fn place_bridges() {
+        // a road cut by a river and a shallow stream, and one ending at the river
+        let generator = Generator::new()
+            .with_size(8, 5)
+            .fill_rect(0, 1, 8, 1, 4)
+            .fill_rect(0, 3, 3, 1, 4)
+            .fill_rect(3, 0, 2, 5, 2)
+            .fill_rect(6, 0, 1, 5, 7)
+            .place_bridges(BridgeOptions {
+                shallow: Some(7),
+                ..BridgeOptions::default()
+            });
+        let bridges = &generator.artifacts().bridges;
+        assert_eq!(bridges[0], Bridge { cells: vec![(3, 1), (4, 1)], ford: false });
+        assert_eq!(bridges[1], Bridge { cells: vec![(6, 1)], ford: true });
+        assert_eq!(bridges.len(), 2);
+        assert_eq!(generator.get_2d_map()[1], vec![4, 4, 4, 5, 5, 4, 6, 4]);
+        assert_eq!(generator.get(3, 3), 2);
+    }
+
+    #[test]
+    fn drainage_basins() {
+        // two valleys split by a ridge at x = 4
+        let elevation = vec![0., 1., 2., 3., 4., 3., 2., 1., 0.];
+        let mut generator = Generator::new().with_size(9, 1);
+        generator.set_field("elevation", elevation);
+        let generator = generator.drainage_basins();
+        assert_eq!(generator.layer("basins").unwrap(), &[0, 0, 0, 0, 0, 1, 1, 1, 1]);
+        assert_eq!(Generator::new().with_size(3, 1).drainage_basins().layer("basins"), None);
+
+        // a plateau drains off its lower edge as a whole, and a flat pit is one basin
+        let basins = |elevation: Vec<f64>, width| {
+            let mut generator = Generator::new().with_size(width, elevation.len() / width);
+            generator.set_field("elevation", elevation);
+            generator.drainage_basins().layer("basins").unwrap().to_vec()
+        };
+        assert_eq!(basins(vec![0., 1., 1., 1., 1., 2.], 6), vec![0; 6]);
+        assert_eq!(basins(vec![2., 0., 0., 0., 2., 3., 1.], 7), vec![0, 0, 0, 0, 0, 1, 1]);
+
+        // the middle drains into the steeper pit on the left, not the deeper one
+        // diagonally below
+        let elevation = vec![9., 9., 9., 3., 5., 9., 9., 9., 2.5];
+        assert_eq!(basins(elevation, 3), vec![0, 0, 0, 0, 0, 1, 0, 1, 1]);
+    }
+}
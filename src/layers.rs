@@ -0,0 +1,175 @@
+//! Named layers of per-cell values kept next to the map, such as depth tiers,
+//! and fields of fractional values such as elevation.
+
+use crate::{Generator, Stage};
+
+impl Generator {
+    /// Returns the layer called `name`, laid out like `map`, if a stage made one.
+    pub fn layer(&self, name: &str) -> Option<&[usize]> {
+        self.layers.get(name).map(Vec::as_slice)
+    }
+    /// Names of every layer, in alphabetical order.
+    pub fn layer_names(&self) -> impl Iterator<Item = &str> {
+        self.layers.keys().map(String::as_str)
+    }
+    /// Stores `values` as the layer called `name`, replacing any layer with that
+    /// name. Panics if `values` doesn't have a value for every cell. Like the map,
+    /// layers are cleared by `with_size`.
+    pub fn set_layer(&mut self, name: &str, values: Vec<usize>) {
+        assert_eq!(values.len(), self.map.len(), "layer `{}` must have a value for every cell", name);
+        self.layers.insert(name.to_string(), values);
+    }
+    /// Returns the field called `name`, a layer of fractional values such as
+    /// elevation, laid out like `map`.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let options = NoiseOptions { slope: true, ..NoiseOptions::default() };
+    ///     let generator = Generator::new()
+    ///         .with_size(40, 20)
+    ///         .with_options(options)
+    ///         .spawn_perlin_bands(&[(0.5, 1)]);
+    ///     // cliffs where the slope is steep
+    ///     let slope = generator.field("slope").unwrap();
+    ///     let cliffs = slope.iter().filter(|&&slope| slope > 0.05).count();
+    ///     println!("{} cliffs", cliffs);
+    /// }
+    /// ```
+    pub fn field(&self, name: &str) -> Option<&[f64]> {
+        self.fields.get(name).map(Vec::as_slice)
+    }
+    /// Names of every field, in alphabetical order.
+    pub fn field_names(&self) -> impl Iterator<Item = &str> {
+        self.fields.keys().map(String::as_str)
+    }
+    /// Stores `values` as the field called `name`, replacing any field with that
+    /// name. Panics if `values` doesn't have a value for every cell. Fields are
+    /// cleared by `with_size`.
+    pub fn set_field(&mut self, name: &str, values: Vec<f64>) {
+        assert_eq!(values.len(), self.map.len(), "field `{}` must have a value for every cell", name);
+        self.fields.insert(name.to_string(), values);
+    }
+    /// Removes the field called `name` and returns it, if there was one.
+    pub fn remove_field(&mut self, name: &str) -> Option<Vec<f64>> {
+        self.fields.remove(name)
+    }
+    /// Returns the field called `name`, computing it with `f` first if no stage
+    /// published it yet. Stages that need the same intermediate data, such as
+    /// the distance to water, share it this way instead of each recomputing it.
+    /// Fields aren't updated when the map changes, so remove a field once the map
+    /// no longer matches it. Panics if `f` doesn't return a value for every cell.
+    pub fn field_or_compute<F: FnOnce(&Generator) -> Vec<f64>>(&mut self, name: &str, f: F) -> &[f64] {
+        if !self.fields.contains_key(name) {
+            let values = f(self);
+            self.set_field(name, values);
+        }
+        &self.fields[name]
+    }
+    /// Publishes `f(generator)` as the field called `name` for later stages to
+    /// read with `field()` and for inspecting with `show_field()`, replacing any
+    /// field with that name. Panics if `f` doesn't return a value for every cell.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let generator = Generator::new()
+    ///         .with_size(40, 20)
+    ///         .spawn_perlin_bands(&[(0.5, 1)])
+    ///         .publish_field("distance_to_water", |generator| {
+    ///             let water = generator.positions_of(0);
+    ///             let distances = generator.dijkstra_map(&water, |_| Some(1));
+    ///             distances.into_iter().map(f64::from).collect()
+    ///         });
+    ///     generator.show_field("distance_to_water");
+    /// }
+    /// ```
+    pub fn publish_field<F: FnOnce(&Generator) -> Vec<f64>>(self, name: &str, f: F) -> Self {
+        self.stage(Stage::Custom { name: "field".into() }, |mut generator| {
+            let values = f(&generator);
+            generator.set_field(name, values);
+            generator
+        })
+    }
+    /// Buckets every cell reachable from `entrance` through `floor` into `tiers`
+    /// depth tiers by walking distance, and stores them as the `"depth"` layer. Item
+    /// rarity, enemy levels or music can then key off the tier of a cell directly.
+    /// Tiers go from 1 near the entrance to `tiers` at the far end, unreachable cells
+    /// get 0.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let generator = Generator::new()
+    ///         .with_size(40, 20)
+    ///         .spawn_perlin_bands(&[(0.4, 1)]);
+    ///     let entrance = generator.random_position_where(|_, _, value| value == 1).unwrap_or((0, 0));
+    ///     let generator = generator.classify_depth(entrance, 1, 3);
+    ///     let depth = generator.layer("depth").unwrap();
+    ///     println!("{} cells in the deepest tier", depth.iter().filter(|&&tier| tier == 3).count());
+    /// }
+    /// ```
+    pub fn classify_depth(self, entrance: (usize, usize), floor: usize, tiers: usize) -> Self {
+        let stage = Stage::DepthTiers { entrance, floor, tiers };
+        self.stage(stage, |mut generator| {
+            let distances = generator.dijkstra_map(&[entrance], |value| if value == floor { Some(1) } else { None });
+            let deepest = distances.iter().filter(|&&distance| distance != u32::MAX).max().copied();
+            let depth = distances
+                .iter()
+                .map(|&distance| match deepest {
+                    Some(deepest) if distance != u32::MAX => 1 + distance as usize * tiers / (deepest as usize + 1),
+                    _ => 0,
+                })
+                .collect();
+            generator.set_layer("depth", depth);
+            generator
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NoiseOptions;
+
+    #[test]
+    fn classify_depth() {
+        let generator = Generator::new()
+            .with_size(7, 1)
+            .draw_line((0, 0), (5, 0), 1)
+            .classify_depth((0, 0), 1, 3);
+        assert_eq!(generator.layer("depth").unwrap(), &[1, 1, 2, 2, 3, 3, 0]);
+        assert_eq!(generator.layer_names().collect::<Vec<_>>(), vec!["depth"]);
+        assert_eq!(generator.with_size(7, 1).layer("depth"), None);
+    }
+    #[test]
+    fn noise_slope() {
+        let options = NoiseOptions {
+            slope: true,
+            ..NoiseOptions::default()
+        };
+        let generator = Generator::new()
+            .with_size(20, 10)
+            .with_options(options)
+            .spawn_perlin_bands(&[(0.5, 1)]);
+        let elevation = generator.field("elevation").unwrap();
+        assert!(elevation.iter().zip(&generator.map).all(|(&elevation, &value)| (elevation > 0.5) == (value == 1)));
+        assert_eq!(generator.field("slope").unwrap().len(), 200);
+        assert_eq!(generator.field_names().collect::<Vec<_>>(), vec!["elevation", "slope"]);
+    }
+    #[test]
+    fn shared_fields() {
+        let mut generator = Generator::new()
+            .with_size(3, 1)
+            .publish_field("x", |generator| generator.iter_cells().map(|(x, _, _)| x as f64).collect());
+        assert_eq!(generator.field("x").unwrap(), &[0., 1., 2.]);
+        // published fields aren't computed again
+        assert_eq!(generator.field_or_compute("x", |_| unreachable!()), &[0., 1., 2.]);
+        assert_eq!(generator.field_or_compute("y", |_| vec![1.; 3]), &[1.; 3]);
+        assert_eq!(generator.remove_field("x"), Some(vec![0., 1., 2.]));
+        assert_eq!(generator.field_names().collect::<Vec<_>>(), vec!["y"]);
+    }
+}
@@ -36,15 +36,126 @@
 //! 0 0 0 0 0 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 2 2 2 2 2 2 2 2 2 2 2 2 1 1 1
 //! ```
 
-use owo_colors::OwoColorize;
 use rand::prelude::*;
 use noise::{Perlin, NoiseFn, Seedable};
 use smart_default::*;
 use rayon::prelude::*;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use control::Progress;
+use render::Window;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// Amount of cells noise is sampled in per work item.
+const CHUNK_SIZE: usize = 1024;
+
+/// Offsets of the orthogonal neighbors of a cell.
+const NEIGHBORS_4: [(isize, isize); 4] = [(0, -1), (-1, 0), (1, 0), (0, 1)];
+
+/// Offsets of the orthogonal and diagonal neighbors of a cell.
+const NEIGHBORS_8: [(isize, isize); 8] = [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)];
+
+mod artifacts;
+#[cfg(feature = "config")]
+mod config;
+mod control;
+mod decorate;
+mod draw;
+mod error;
+mod fairness;
+mod flow;
+mod fov;
+mod heightmap;
+mod hydrology;
+mod layers;
+mod maze;
+mod mission;
+mod palette;
+mod path;
+mod pipeline;
+mod placement;
+mod prefab;
+mod quota;
+mod render;
+mod regions;
+mod registry;
+mod rules;
+mod scatter;
+mod simulate;
+mod stats;
+mod synthesis;
+mod territory;
+mod theme;
+mod terrain;
+mod view;
+mod wfc;
+mod zoom;
+pub use artifacts::*;
+#[cfg(feature = "config")]
+pub use config::*;
+pub use control::CancelToken;
+pub use decorate::*;
+pub use error::*;
+pub use fairness::*;
+pub use fov::*;
+pub use heightmap::*;
+pub use hydrology::*;
+pub use mission::*;
+pub use palette::*;
+pub use pipeline::*;
+pub use placement::*;
+pub use prefab::*;
+pub use quota::*;
+pub use regions::*;
+pub use registry::*;
+pub use rules::*;
+pub use stats::*;
+pub use theme::*;
+pub use view::*;
+pub use wfc::*;
+
+/// How noise is mapped to the 0 to 1 range handed to classification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Normalization {
+    /// `(value.powf(redistribution) + 1.) / 2.`, the mapping used before there
+    /// was a choice, kept so old seeds produce the same maps. Sums of several
+    /// octaves go past 1, and negative noise with a fractional redistribution
+    /// turns into NaN.
+    #[default]
+    Legacy,
+    /// Noise is divided by the summed amplitudes of the octaves, mapped to 0 to
+    /// 1, and only then raised to the power of the redistribution. Values always
+    /// lie between 0 and 1, so thresholds mean the same for any options.
+    Normalized,
+}
+
+/// Where on the noise plane the cells of the map are sampled.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Sampling {
+    /// Both coordinates are divided by the width of the map, the original
+    /// sampling. Cells are square on the noise plane, so a map twice as tall
+    /// shows twice as much noise vertically.
+    #[default]
+    Width,
+    /// x is divided by the width and y by the height, so the same amount of
+    /// noise fills the map whatever its shape, stretched along the longer side.
+    PerAxis,
+    /// Every cell is this many units of the noise plane wide and tall, whatever
+    /// the size of the map, so features keep their size when the map grows.
+    CellSize(f64),
+}
 
 /// Different options for defining how noise should behave. 
-#[derive(Debug, SmartDefault)]
+#[derive(Debug, Clone, PartialEq, SmartDefault)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct NoiseOptions {
     /// Higher frequency adds a zooming effect to the noise. Default is 1.0.
     #[default = 1.0]
@@ -55,6 +166,41 @@ pub struct NoiseOptions {
     /// More octaves increases variety. Default is 1.
     #[default = 1]
     pub octaves: usize,
+    /// How noise is mapped to 0 to 1. Default is `Normalization::Legacy`.
+    pub normalization: Normalization,
+    /// How cells map to the noise plane. Default is `Sampling::Width`.
+    pub sampling: Sampling,
+    /// Divides the sum of the octaves by their summed amplitudes, so it stays
+    /// between -1 and 1 however many octaves there are. `Normalization::Normalized`
+    /// always does this. Default is false.
+    pub normalize_octaves: bool,
+    /// Also stores the noise as the `"elevation"` field and how steeply it
+    /// changes per cell as the `"slope"` field, see `field()`. Default is false.
+    pub slope: bool,
+    /// How far a second noise of the same frequency pushes every sample around,
+    /// bending features into swirls and veins. Around 1 moves samples as far as
+    /// the features are wide, 0 turns it off. Default is 0.
+    pub warp: f64,
+}
+
+/// Tuned noise options for common looks, see
+/// [NoiseOptions::preset](struct.NoiseOptions.html#method.preset). Every preset
+/// uses `Normalization::Normalized`, so thresholds mean the same whichever
+/// preset they're used with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Preset {
+    /// A few large landmasses with ragged coasts.
+    Continents,
+    /// Many small islands, most of the map lies below 0.5.
+    Islands,
+    /// Winding blobs that make cave systems when thresholded.
+    Caves,
+    /// Soft, evenly spread puffs.
+    Clouds,
+    /// Veins bent by heavy warping.
+    Marble,
 }
 
 impl NoiseOptions {
@@ -82,6 +228,49 @@ impl NoiseOptions {
     pub fn new() -> Self {
         Self::default()
     }
+    /// Creates `NoiseOptions` tuned for the look of `preset`, a good place to
+    /// start before adjusting each option.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     Generator::new()
+    ///         .with_size(60, 20)
+    ///         .with_options(NoiseOptions::preset(Preset::Islands))
+    ///         .spawn_perlin_bands(&[(0.6, 2), (0.45, 1)])
+    ///         .show();
+    /// }
+    /// ```
+    pub fn preset(preset: Preset) -> Self {
+        let (frequency, octaves, redistribution, warp) = match preset {
+            Preset::Continents => (1.5, 6, 1., 0.3),
+            Preset::Islands => (4., 4, 1.5, 0.),
+            Preset::Caves => (6., 2, 1., 0.4),
+            Preset::Clouds => (3., 6, 1., 0.),
+            Preset::Marble => (2., 3, 1., 2.),
+        };
+        NoiseOptions {
+            frequency,
+            octaves,
+            redistribution,
+            warp,
+            normalization: Normalization::Normalized,
+            ..NoiseOptions::default()
+        }
+    }
+}
+
+// pushes the point `(x, y)` of the noise plane, after frequency, around by `warp`
+// times the noise at two far away points
+fn warp(perlin: &Perlin, warp: f64, x: f64, y: f64) -> (f64, f64) {
+    if warp == 0. {
+        return (x, y);
+    }
+    (
+        x + warp * perlin.get([x + 31.7, y + 47.3]),
+        y + warp * perlin.get([x + 83.1, y + 12.9]),
+    )
 }
 
 /// The foundation of this crate
@@ -92,24 +281,58 @@ pub struct Generator {
     pub height: usize,
     pub noise_options: NoiseOptions,
     rooms: Vec<Room>,
-    seed: u32,
+    artifacts: Artifacts,
+    layers: BTreeMap<String, Vec<usize>>,
+    fields: BTreeMap<String, Vec<f64>>,
+    palette: Palette,
+    rulers: bool,
+    animation: Option<Duration>,
+    seed: u64,
+    stage_counts: HashMap<String, u64>,
+    rng: Option<UserRng>,
+    mask: Option<Mask>,
+    stages: Vec<Stage>,
+    in_stage: bool,
+    current_stage: String,
+    progress: Option<Progress>,
+    cancel: Option<CancelToken>,
+    deadline: Option<Instant>,
+}
+
+/// Predicate set by `with_mask`, boxed so `Generator` can hold it.
+struct Mask(Box<dyn Fn(usize, usize, usize) -> bool + Send + Sync>);
+
+impl fmt::Debug for Mask {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Mask")
+    }
+}
+
+/// Random number generator set by `with_rng`.
+struct UserRng(Box<dyn RngCore + Send + Sync>);
+
+impl fmt::Debug for UserRng {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "UserRng")
+    }
 }
 
 impl Generator {
     /// Create generator.
     pub fn new() -> Self {
-        let seed: u32 = rand::thread_rng().gen();
+        let seed: u64 = rand::thread_rng().gen();
         Self {
             seed,
             ..Self::default()
         }
     }
-    fn spawn_room(&mut self, number: usize, size: &Size, rng: &mut StdRng) -> &mut Self {
+    fn spawn_room(&mut self, number: usize, size: &Size, rng: &mut dyn RngCore) -> &mut Self {
         let mut x = rng.gen_range(0, self.width);
         let mut y = rng.gen_range(0, self.height);
 
-        let width = rng.gen_range(size.min_size.0, size.max_size.0);
-        let height = rng.gen_range(size.min_size.1, size.max_size.1);
+        // rooms larger than the map are cut down to fit
+        let width = rng.gen_range(size.min_size.0, size.max_size.0).min(self.width);
+        let height = rng.gen_range(size.min_size.1, size.max_size.1).min(self.height);
 
         // shift room back on if it's off
         if x + width > self.width {
@@ -125,7 +348,7 @@ impl Generator {
         let room = Room::new(x, y, width, height);
 
         for other_room in &self.rooms {
-            if room.intersects(&other_room) {
+            if room.intersects(other_room) {
                 collides = true;
                 break;
             }
@@ -143,10 +366,138 @@ impl Generator {
         self
     }
     /// Set seed for noise generation. Useful for reproducing results. Random otherwise.
-    pub fn with_seed(mut self, seed: u32) -> Self {
+    /// Seeds that fit in 32 bits produce the same maps as they did back when seeds
+    /// were `u32`, so old seeds keep working with `with_seed(old_seed.into())`.
+    pub fn with_seed(mut self, seed: u64) -> Self {
         self.seed = seed;
         self
     }
+    /// Returns the seed, which is useful for displaying or saving the random seed
+    /// picked by `Generator::new()`.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+    /// Makes every stage draw its randomness from `rng` instead of from seeds
+    /// derived with `with_seed`, for projects that standardize on one PRNG for
+    /// cross-platform determinism. Read-only queries such as `random_position_where`
+    /// keep using the seed.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    /// use rand::{rngs::StdRng, SeedableRng};
+    ///
+    /// fn main() {
+    ///     let size = Size::new((4, 4), (10, 10));
+    ///     Generator::new()
+    ///         .with_size(30, 20)
+    ///         .with_rng(StdRng::seed_from_u64(7))
+    ///         .spawn_rooms(1, 5, &size)
+    ///         .show();
+    /// }
+    /// ```
+    pub fn with_rng<R: RngCore + Send + Sync + 'static>(mut self, rng: R) -> Self {
+        self.rng = Some(UserRng(Box::new(rng)));
+        self
+    }
+    /// Runs `f` with the random number generator for the next run of the stage
+    /// called `name`: the one set by `with_rng`, or one seeded by `stage_seed`.
+    fn with_stage_rng<T, F: FnOnce(&mut Self, &mut dyn RngCore) -> T>(&mut self, name: &str, f: F) -> T {
+        match self.rng.take() {
+            Some(mut rng) => {
+                let result = f(self, &mut *rng.0);
+                self.rng = Some(rng);
+                result
+            }
+            None => {
+                let mut rng: StdRng = SeedableRng::seed_from_u64(self.stage_seed(name));
+                f(self, &mut rng)
+            }
+        }
+    }
+    /// Derives the seed for the next run of the stage called `name`. Every stage
+    /// gets its own stream of seeds, so adding, removing or reordering stages doesn't
+    /// change what the others generate, and running a stage twice doesn't repeat it.
+    /// Custom [Spawner](trait.Spawner.html)s should seed their randomness with this.
+    pub fn stage_seed(&mut self, name: &str) -> u64 {
+        let count = self.stage_counts.entry(name.to_string()).or_insert(0);
+        let occurrence = *count;
+        *count += 1;
+        // maps generated before stages had their own seeds stay reproducible
+        if occurrence == 0 && LEGACY_STAGES.contains(&name) {
+            return self.seed;
+        }
+        derive_seed(self.seed, name, occurrence)
+    }
+    /// Restricts the next stage, such as `spawn_perlin` or `draw_line`, to cells
+    /// where `f(x, y, value)` returns true. `value` is what the cell held before the
+    /// stage ran, every other cell is left untouched. The mask is cleared once the
+    /// stage has run. Masks are closures, so maps built with them can't be replayed,
+    /// which includes `generate_until` and `explore_seeds`.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let size = Size::new((4, 4), (10, 10));
+    ///     Generator::new()
+    ///         .with_size(30, 20)
+    ///         .spawn_rooms(1, 5, &size)
+    ///         // scatter rubble, but only inside rooms
+    ///         .with_mask(|_, _, value| value == 1)
+    ///         .spawn_perlin(|value| if value > 0.6 { 2 } else { 1 })
+    ///         .show();
+    /// }
+    /// ```
+    pub fn with_mask<F: Fn(usize, usize, usize) -> bool + Send + Sync + 'static>(mut self, f: F) -> Self {
+        self.mask = Some(Mask(Box::new(f)));
+        self
+    }
+    // runs a stage that writes to the map, recording it and honoring then clearing the
+    // mask. stages run from inside another stage, such as a custom spawner drawing
+    // shapes, are part of the outer stage and neither recorded nor masked on their own
+    fn stage<F: FnOnce(Self) -> Self>(mut self, stage: Stage, f: F) -> Self {
+        if self.is_cancelled() {
+            self.mask = None;
+            return self;
+        }
+        if self.in_stage {
+            return f(self);
+        }
+        let mask = self.mask.take();
+        self.current_stage = stage.name().to_string();
+        self.stages.push(match mask {
+            Some(_) => Stage::Masked { inner: Box::new(stage) },
+            None => stage,
+        });
+        let before = mask.as_ref().map(|_| self.map.clone());
+
+        self.report(0.);
+        self.in_stage = true;
+        let mut generator = f(self);
+        generator.in_stage = false;
+
+        if let (Some(mask), Some(before)) = (mask, before) {
+            let width = generator.width;
+            generator
+                .map
+                .par_iter_mut()
+                .zip(before.par_iter())
+                .enumerate()
+                .for_each(|(pos, (value, &old))| {
+                    if !(mask.0)(pos % width, pos / width, old) {
+                        *value = old;
+                    }
+                });
+        }
+        generator.report(1.);
+        generator
+    }
+    /// Same as `with_seed(...)`, except the seed is hashed from a string, so players
+    /// can share readable world codes like `"dragon-caves-42"`. The hash is stable
+    /// across platforms and releases.
+    pub fn with_seed_str(self, seed: &str) -> Self {
+        self.with_seed(fnv1a(seed))
+    }
     /// Changes how noise is generated. Different values make for much more interesting noise
     pub fn with_options(mut self, options: NoiseOptions) -> Self {
         self.noise_options = options;
@@ -156,9 +507,34 @@ impl Generator {
     pub fn show(&self) {
         println!("{}", self);
     }
-    /// Sets size of map. This clears the map as well.
+    /// Prints column indices above and row indices left of the map when `rulers`
+    /// is true, for finding cells on large maps. Column indices are written
+    /// vertically, one digit per line.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     Generator::new()
+    ///         .with_size(40, 10)
+    ///         .with_rulers(true)
+    ///         .spawn_perlin_bands(&[(0.66, 2), (0.33, 1)])
+    ///         .show();
+    /// }
+    /// ```
+    pub fn with_rulers(mut self, rulers: bool) -> Self {
+        self.rulers = rulers;
+        self
+    }
+    /// Sets size of map. This clears the map and the recorded stages as well.
     pub fn with_size(mut self, width: usize, height: usize) -> Self {
         self.map = vec![0; width * height];
+        self.rooms.clear();
+        self.artifacts = Artifacts::default();
+        self.layers.clear();
+        self.fields.clear();
+        self.stages.clear();
+        self.stage_counts.clear();
         self.width = width;
         self.height = height;
         self
@@ -188,32 +564,165 @@ impl Generator {
     ///         .show();
     /// }
     /// ```
-    pub fn spawn_perlin<F: Fn(f64) -> usize + Sync>(mut self, f: F) -> Self {
-        let perlin = Perlin::new().set_seed(self.seed);
+    pub fn spawn_perlin<F: Fn(f64) -> usize + Sync>(self, f: F) -> Self {
+        self.stage(Stage::Custom { name: "perlin".into() }, |generator| generator.perlin(f))
+    }
+    /// Same as `spawn_perlin(...)`, except noise is classified with a list of
+    /// `(threshold, value)` bands instead of a closure: every cell gets the value of
+    /// the highest threshold its noise lies above, or 0 if it lies below all of them.
+    /// Unlike closures, bands can be recorded in a [Recipe](struct.Recipe.html).
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     Generator::new()
+    ///         .with_size(40, 20)
+    ///         .spawn_perlin_bands(&[(0.66, 2), (0.33, 1)])
+    ///         .show();
+    /// }
+    /// ```
+    pub fn spawn_perlin_bands(self, bands: &[(f64, usize)]) -> Self {
+        let classify = classifier(bands);
+        let stage = Stage::PerlinBands {
+            options: self.noise_options.clone(),
+            bands: bands.to_vec(),
+        };
+        self.stage(stage, |generator| generator.perlin(classify))
+    }
+    fn perlin<F: Fn(f64) -> usize + Sync>(mut self, f: F) -> Self {
+        if self.map.is_empty() {
+            return self;
+        }
+        let seed = match self.rng.as_mut() {
+            Some(rng) => rng.0.next_u64(),
+            None => self.stage_seed("perlin"),
+        };
+        let perlin = Perlin::new().set_seed(fold_seed(seed));
         let redistribution = self.noise_options.redistribution;
+        let normalization = self.noise_options.normalization;
         let freq = self.noise_options.frequency;
-        let octaves = self.noise_options.octaves;
+        let warp_by = self.noise_options.warp;
         let width = self.width;
+        let (scale_x, scale_y) = self.noise_scale();
 
-        self.map.par_iter_mut().enumerate().for_each(|(pos, index)| {
-            let x = pos % width;
-            let y = pos / width;
+        // power and amplitude of each octave, computed once instead of per cell
+        let octaves: Vec<(f64, f64)> = (0..self.noise_options.octaves)
+            .map(|n| {
+                let power = 2.0f64.powf(n as f64);
+                (power, 1. / power)
+            })
+            .collect();
+        let amplitude: f64 = octaves.iter().map(|&(_, modifier)| modifier).sum();
+        let amplitude = if self.noise_options.normalize_octaves || normalization == Normalization::Normalized {
+            amplitude
+        } else {
+            1.
+        };
 
-            let nx = x as f64 / width as f64;
-            let ny = y as f64 / width as f64;
+        let slope = self.noise_options.slope;
+        let mut elevation = if slope { vec![0.; self.map.len()] } else { vec![] };
+        let chunks = self.map.len().div_ceil(CHUNK_SIZE);
+        let chunks_done = AtomicUsize::new(0);
+        let mut map = std::mem::take(&mut self.map);
+        let generator = &self;
 
-            let value = (0..octaves).fold(0., |acc, n| {
-                let power = 2.0f64.powf(n as f64);
-                let modifier = 1. / power;
-                acc + modifier * perlin.get([nx * freq * power, ny * freq * power])
-            });
+        // the map is split into runs of cells in row-major order, so the amount of
+        // work items grows with the amount of cells whatever the shape of the map.
+        // square tiles are no faster, sampling reads nothing but the small perlin
+        // permutation table, so there is no locality to win
+        let sample = |chunk: usize, cells: &mut [usize], elevation: &mut [f64]| {
+            if generator.is_cancelled() {
+                return;
+            }
+            for (i, cell) in cells.iter_mut().enumerate() {
+                let pos = chunk * CHUNK_SIZE + i;
+                let (x, y) = (pos % width, pos / width);
+                let (nx, ny) = warp(&perlin, warp_by, x as f64 * scale_x * freq, y as f64 * scale_y * freq);
 
-            // add redistribution, map range from -1, 1 to 0, 1 then parse
-            // biome and set it
-            *index = f((value.powf(redistribution) + 1.) / 2.);
-        });
+                let value = octaves.iter().fold(0., |acc, &(power, modifier)| {
+                    acc + modifier * perlin.get([nx * power, ny * power])
+                });
+
+                // add redistribution, map range from -1, 1 to 0, 1 then parse
+                // biome and set it
+                let value = match normalization {
+                    Normalization::Legacy => ((value / amplitude).powf(redistribution) + 1.) / 2.,
+                    Normalization::Normalized => ((value / amplitude + 1.) / 2.).clamp(0., 1.).powf(redistribution),
+                };
+                if slope {
+                    elevation[i] = value;
+                }
+                *cell = f(value);
+            }
+            let done = chunks_done.fetch_add(1, Ordering::Relaxed) + 1;
+            generator.report(done as f32 / chunks as f32);
+        };
+        if slope {
+            map.par_chunks_mut(CHUNK_SIZE)
+                .zip(elevation.par_chunks_mut(CHUNK_SIZE))
+                .enumerate()
+                .for_each(|(chunk, (cells, elevation))| sample(chunk, cells, elevation));
+        } else {
+            map.par_chunks_mut(CHUNK_SIZE)
+                .enumerate()
+                .for_each(|(chunk, cells)| sample(chunk, cells, &mut []));
+        }
+        if slope && !self.is_cancelled() {
+            let slope = terrain::slope(&elevation, width, self.height);
+            self.fields.insert("elevation".into(), elevation);
+            self.fields.insert("slope".into(), slope);
+        }
+        self.map = map;
         self
     }
+    // units of the noise plane per cell along x and y
+    fn noise_scale(&self) -> (f64, f64) {
+        match self.noise_options.sampling {
+            Sampling::Width => (1. / self.width as f64, 1. / self.width as f64),
+            Sampling::PerAxis => (1. / self.width as f64, 1. / self.height as f64),
+            Sampling::CellSize(size) => (size, size),
+        }
+    }
+    // fractal noise of every cell with the frequency, octaves and sampling of the
+    // noise options, divided by the summed amplitudes so it lies between -1 and 1,
+    // seeded like a stage called `name`
+    pub(crate) fn noise_field(&mut self, name: &str) -> Vec<f64> {
+        let seed = match self.rng.as_mut() {
+            Some(rng) => rng.0.next_u64(),
+            None => self.stage_seed(name),
+        };
+        let perlin = Perlin::new().set_seed(fold_seed(seed));
+        let (scale_x, scale_y) = self.noise_scale();
+        let frequency = self.noise_options.frequency;
+        let octaves = self.noise_options.octaves.max(1);
+        let amplitude: f64 = (0..octaves).map(|n| 0.5f64.powi(n as i32)).sum();
+        let warp_by = self.noise_options.warp;
+        let width = self.width;
+        (0..self.map.len())
+            .into_par_iter()
+            .map(|pos| {
+                let (x, y) = ((pos % width) as f64 * scale_x, (pos / width) as f64 * scale_y);
+                let (nx, ny) = warp(&perlin, warp_by, x * frequency, y * frequency);
+                let value: f64 = (0..octaves)
+                    .map(|n| {
+                        let power = 2.0f64.powi(n as i32);
+                        perlin.get([nx * power, ny * power]) / power
+                    })
+                    .sum();
+                value / amplitude
+            })
+            .collect()
+    }
+    /// Same as `spawn_perlin(...)`, except it fails with
+    /// [GenerationError::EmptyMap](enum.GenerationError.html) instead of silently
+    /// doing nothing when the map has no size.
+    pub fn try_spawn_perlin<F: Fn(f64) -> usize + Sync>(self, f: F) -> Result<Self, GenerationError> {
+        if self.map.is_empty() {
+            return Err(GenerationError::EmptyMap);
+        }
+        Ok(self.spawn_perlin(f))
+    }
     /// Spawns rooms of varying sizes based on input `size`. `number` sets
     /// what number the rooms are represented with in the map, `rooms` is amount of rooms
     /// to generate and `size` specifies the minimum and maximum boundaries for each room.
@@ -230,13 +739,141 @@ impl Generator {
     ///         .show();
     /// }
     /// ```
-    pub fn spawn_rooms(mut self, number: usize, rooms: usize, size: &Size) -> Self {
-        let mut rng = SeedableRng::seed_from_u64(self.seed as u64);
-        // let mut rng = rand::thread_rng();
-        for _ in 0..rooms {
-            self.spawn_room(number, size, &mut rng);
+    pub fn spawn_rooms(self, number: usize, rooms: usize, size: &Size) -> Self {
+        let stage = Stage::Rooms {
+            number,
+            rooms,
+            min_size: size.min_size,
+            max_size: size.max_size,
+        };
+        self.stage(stage, |mut generator| {
+            generator.with_stage_rng("rooms", |generator, rng| {
+                for _ in 0..rooms {
+                    generator.spawn_room(number, size, rng);
+                }
+            });
+            generator
+        })
+    }
+    /// Same as `spawn_rooms(...)`, except it reports an empty map or a `size`
+    /// that can't produce rooms instead of panicking.
+    pub fn try_spawn_rooms(self, number: usize, rooms: usize, size: &Size) -> Result<Self, GenerationError> {
+        if self.map.is_empty() {
+            return Err(GenerationError::EmptyMap);
         }
-        self
+        if size.min_size.0 >= size.max_size.0 || size.min_size.1 >= size.max_size.1 {
+            return Err(GenerationError::InvalidRoomSize);
+        }
+        if size.min_size.0 > self.width || size.min_size.1 > self.height {
+            return Err(GenerationError::RoomTooLarge);
+        }
+        Ok(self.spawn_rooms(number, rooms, size))
+    }
+    /// Rewrites every cell in parallel with `f(x, y, value)`, handy for small
+    /// tweaks after the other stages have run.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     Generator::new()
+    ///         .with_size(40, 10)
+    ///         .spawn_perlin(|value| if value > 0.5 { 1 } else { 0 })
+    ///         // wall off the border
+    ///         .apply(|x, y, value| if x == 0 || y == 0 || x == 39 || y == 9 { 2 } else { value })
+    ///         .show();
+    /// }
+    /// ```
+    pub fn apply<F: Fn(usize, usize, usize) -> usize + Sync>(self, f: F) -> Self {
+        self.stage(Stage::Custom { name: "apply".into() }, |mut generator| {
+            generator.par_iter_cells_mut().for_each(|(x, y, value)| *value = f(x, y, *value));
+            generator
+        })
+    }
+    /// Merges `other` into this map cell by cell, every cell becomes `f(a, b)` where
+    /// `a` is the value in this map and `b` the value in `other`. Panics if the maps
+    /// differ in size, see `try_combine(...)`.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let size = Size::new((4, 4), (10, 10));
+    ///     let structures = Generator::new()
+    ///         .with_size(40, 10)
+    ///         .spawn_rooms(3, 4, &size);
+    ///     Generator::new()
+    ///         .with_size(40, 10)
+    ///         .spawn_perlin(|value| if value > 0.5 { 1 } else { 0 })
+    ///         // structures are drawn on top of the terrain
+    ///         .combine(&structures, |terrain, structure| if structure > 0 { structure } else { terrain })
+    ///         .show();
+    /// }
+    /// ```
+    pub fn combine<F: Fn(usize, usize) -> usize + Sync>(self, other: &Generator, f: F) -> Self {
+        match self.try_combine(other, f) {
+            Ok(generator) => generator,
+            Err(error) => panic!("{}", error),
+        }
+    }
+    /// Same as `combine(...)`, except it returns an error when the maps differ in size.
+    pub fn try_combine<F: Fn(usize, usize) -> usize + Sync>(self, other: &Generator, f: F) -> Result<Self, GenerationError> {
+        if (self.width, self.height) != (other.width, other.height) {
+            return Err(GenerationError::SizeMismatch {
+                expected: (self.width, self.height),
+                found: (other.width, other.height),
+            });
+        }
+        Ok(self.stage(Stage::Custom { name: "combine".into() }, |mut generator| {
+            generator
+                .map
+                .par_iter_mut()
+                .zip(other.map.par_iter())
+                .for_each(|(a, &b)| *a = f(*a, b));
+            generator
+        }))
+    }
+    /// Lists every cell that differs from `other` as `(x, y, from, to)`, where `from`
+    /// is the value in this map and `to` the value in `other`, in row-major order.
+    /// Handy for checking that generation is deterministic, or for seeing what a new
+    /// stage actually changed. Panics if the maps differ in size.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let before = Generator::new()
+    ///         .with_size(40, 10)
+    ///         .spawn_perlin_bands(&[(0.5, 1)]);
+    ///     let after = Generator::from_recipe(&before.recipe()).unwrap().replace_weighted(1, &[(2, 0.1)]);
+    ///     for (x, y, from, to) in before.diff(&after) {
+    ///         println!("({}, {}): {} -> {}", x, y, from, to);
+    ///     }
+    /// }
+    /// ```
+    pub fn diff(&self, other: &Generator) -> Vec<(usize, usize, usize, usize)> {
+        match self.try_diff(other) {
+            Ok(diff) => diff,
+            Err(error) => panic!("{}", error),
+        }
+    }
+    /// Same as `diff(...)`, except it returns an error when the maps differ in size.
+    pub fn try_diff(&self, other: &Generator) -> Result<Vec<(usize, usize, usize, usize)>, GenerationError> {
+        if (self.width, self.height) != (other.width, other.height) {
+            return Err(GenerationError::SizeMismatch {
+                expected: (self.width, self.height),
+                found: (other.width, other.height),
+            });
+        }
+        let width = self.width;
+        Ok(self
+            .map
+            .par_iter()
+            .zip(other.map.par_iter())
+            .enumerate()
+            .filter(|(_, (from, to))| from != to)
+            .map(|(pos, (&from, &to))| (pos % width, pos / width, from, to))
+            .collect())
     }
     /// Returns value at (x, y) coordinate, useful since map is in 1d form
     /// but treated as 2d.
@@ -247,6 +884,132 @@ impl Generator {
     pub fn set(&mut self, x: usize, y: usize, value: usize) {
         self.map[x + y * self.width] = value;
     }
+    /// Same as `get(...)`, except it returns `None` when (x, y) is outside the map.
+    pub fn get_checked(&self, x: usize, y: usize) -> Option<usize> {
+        if x < self.width && y < self.height {
+            Some(self.get(x, y))
+        } else {
+            None
+        }
+    }
+    /// Same as `get(...)`, except coordinates wrap around the edges, so (-1, 0)
+    /// is the last cell of the first row. Useful for maps that tile seamlessly.
+    pub fn get_wrapped(&self, x: isize, y: isize) -> usize {
+        let x = x.rem_euclid(self.width as isize) as usize;
+        let y = y.rem_euclid(self.height as isize) as usize;
+        self.get(x, y)
+    }
+    /// Same as `get(...)`, except it returns an error instead of panicking when
+    /// (x, y) is outside the map.
+    pub fn try_get(&self, x: usize, y: usize) -> Result<usize, GenerationError> {
+        if x >= self.width || y >= self.height {
+            return Err(GenerationError::OutOfBounds { x, y });
+        }
+        Ok(self.get(x, y))
+    }
+    /// Same as `set(...)`, except it returns an error instead of panicking when
+    /// (x, y) is outside the map.
+    pub fn try_set(&mut self, x: usize, y: usize, value: usize) -> Result<(), GenerationError> {
+        if x >= self.width || y >= self.height {
+            return Err(GenerationError::OutOfBounds { x, y });
+        }
+        self.set(x, y, value);
+        Ok(())
+    }
+    /// Returns the orthogonal neighbors of (x, y) that lie inside the map, as
+    /// `(x, y, value)`. Cells at the edges simply have fewer neighbors.
+    pub fn neighbors4(&self, x: usize, y: usize) -> impl Iterator<Item = (usize, usize, usize)> + '_ {
+        self.neighbors(x, y, &NEIGHBORS_4)
+    }
+    /// Same as `neighbors4(...)`, except diagonal neighbors are included as well.
+    pub fn neighbors8(&self, x: usize, y: usize) -> impl Iterator<Item = (usize, usize, usize)> + '_ {
+        self.neighbors(x, y, &NEIGHBORS_8)
+    }
+    fn neighbors(
+        &self,
+        x: usize,
+        y: usize,
+        offsets: &'static [(isize, isize)],
+    ) -> impl Iterator<Item = (usize, usize, usize)> + '_ {
+        offsets.iter().filter_map(move |&(dx, dy)| {
+            let nx = x as isize + dx;
+            let ny = y as isize + dy;
+            if nx < 0 || ny < 0 {
+                return None;
+            }
+            let (nx, ny) = (nx as usize, ny as usize);
+            self.get_checked(nx, ny).map(|value| (nx, ny, value))
+        })
+    }
+    /// Iterates over every cell as `(x, y, value)` in row-major order.
+    pub fn iter_cells(&self) -> impl Iterator<Item = (usize, usize, usize)> + '_ {
+        let width = self.width;
+        self.map
+            .iter()
+            .enumerate()
+            .map(move |(pos, &value)| (pos % width, pos / width, value))
+    }
+    /// Same as `iter_cells()`, except values can be modified.
+    pub fn iter_cells_mut(&mut self) -> impl Iterator<Item = (usize, usize, &mut usize)> + '_ {
+        let width = self.width;
+        self.map
+            .iter_mut()
+            .enumerate()
+            .map(move |(pos, value)| (pos % width, pos / width, value))
+    }
+    /// Parallel version of `iter_cells()`.
+    pub fn par_iter_cells(&self) -> impl IndexedParallelIterator<Item = (usize, usize, usize)> + '_ {
+        let width = self.width;
+        self.map
+            .par_iter()
+            .enumerate()
+            .map(move |(pos, &value)| (pos % width, pos / width, value))
+    }
+    /// Parallel version of `iter_cells_mut()`.
+    pub fn par_iter_cells_mut(&mut self) -> impl IndexedParallelIterator<Item = (usize, usize, &mut usize)> + '_ {
+        let width = self.width;
+        self.map
+            .par_iter_mut()
+            .enumerate()
+            .map(move |(pos, value)| (pos % width, pos / width, value))
+    }
+    /// Returns the coordinates of every cell holding `value`, in row-major order.
+    pub fn positions_of(&self, value: usize) -> Vec<(usize, usize)> {
+        self.iter_cells()
+            .filter(|&(_, _, other)| other == value)
+            .map(|(x, y, _)| (x, y))
+            .collect()
+    }
+    /// Picks a random cell for which `f(x, y, value)` returns true, or `None` if
+    /// there is no such cell. The choice is driven by the generator's seed, so the
+    /// same map and seed always pick the same cell.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let size = Size::new((4, 4), (10, 10));
+    ///     let spawn = Generator::new()
+    ///         .with_size(30, 20)
+    ///         .spawn_rooms(1, 3, &size)
+    ///         .random_position_where(|_, _, value| value == 1);
+    ///     println!("{:?}", spawn);
+    /// }
+    /// ```
+    pub fn random_position_where<F: Fn(usize, usize, usize) -> bool>(&self, f: F) -> Option<(usize, usize)> {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(derive_seed(self.seed, "random_position_where", 0));
+        self.iter_cells()
+            .filter(|&(x, y, value)| f(x, y, value))
+            .map(|(x, y, _)| (x, y))
+            .choose(&mut rng)
+    }
+    /// Counts how many cells hold each value.
+    pub fn histogram(&self) -> HashMap<usize, usize> {
+        self.map.iter().fold(HashMap::new(), |mut histogram, &value| {
+            *histogram.entry(value).or_insert(0) += 1;
+            histogram
+        })
+    }
     /// This is not recommended unless it's convenient or necessary,
     /// as 2d vectors are slow.
     pub fn get_2d_map(&self) -> Vec<Vec<usize>> {
@@ -257,27 +1020,46 @@ impl Generator {
     }
 }
 
+/// Stages that used the generator seed directly before every stage got its own seed.
+const LEGACY_STAGES: [&str; 2] = ["perlin", "rooms"];
+
+/// Mixes the generator seed with a stage name and how many times that stage ran before.
+fn derive_seed(seed: u64, name: &str, occurrence: u64) -> u64 {
+    // splitmix64 finalizer to spread the bits
+    let mut z = (seed ^ fnv1a(name)).wrapping_add(occurrence.wrapping_add(1).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+// value of the highest threshold in `bands` that a number lies above, 0 if none
+fn classifier(bands: &[(f64, usize)]) -> impl Fn(f64) -> usize + Sync {
+    let mut sorted = bands.to_vec();
+    sorted.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+    move |value| {
+        sorted
+            .iter()
+            .find(|&&(threshold, _)| value > threshold)
+            .map_or(0, |&(_, band)| band)
+    }
+}
+
+/// 64-bit FNV-1a hash, which unlike `DefaultHasher` is stable across releases.
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// The noise crate only takes 32-bit seeds, so the upper half is folded into the
+/// lower one. Seeds that fit in 32 bits map to themselves.
+fn fold_seed(seed: u64) -> u32 {
+    (seed ^ (seed >> 32)) as u32
+}
+
 impl fmt::Display for Generator {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for y in 0..self.height {
-            for x in 0..self.width {
-                let value = self.get(x, y);
-                let remainder = value % 7;
-                match remainder {
-                    1 => write!(f, "{:?} ", value.red())?,
-                    2 => write!(f, "{:?} ", value.green())?,
-                    3 => write!(f, "{:?} ", value.cyan())?,
-                    4 => write!(f, "{:?} ", value.magenta())?,
-                    5 => write!(f, "{:?} ", value.white())?,
-                    6 => write!(f, "{:?} ", value.yellow())?,
-                    _ => write!(f, "{:?} ", value.blue())?,
-                }
-            }
-            if y < self.height - 1 {
-                write!(f, "\n")?
-            }
-        }
-        Ok(())
+        Window::new(self, 0, 0, self.width, self.height).fmt(f)
     }
 }
 
@@ -301,8 +1083,6 @@ struct Room {
     y: usize,
     x2: usize,
     y2: usize,
-    width: usize,
-    height: usize,
 }
 
 impl Room {
@@ -312,8 +1092,6 @@ impl Room {
             y,
             x2: x + width,
             y2: y + height,
-            width,
-            height,
         }
     }
     fn intersects(&self, other: &Self) -> bool {
@@ -354,6 +1132,92 @@ mod tests {
         assert_eq!(generator.map, output);
     }
     #[test]
+    fn normalization() {
+        use super::*;
+        use std::sync::Mutex;
+        let sample = |normalization| {
+            let values = Mutex::new(vec![]);
+            let options = NoiseOptions {
+                redistribution: 1.5,
+                octaves: 6,
+                normalization,
+                ..NoiseOptions::default()
+            };
+            Generator::new().with_size(64, 64).with_seed(3).with_options(options).spawn_perlin(|value| {
+                values.lock().unwrap().push(value);
+                0
+            });
+            values.into_inner().unwrap()
+        };
+        assert!(sample(Normalization::Legacy).iter().any(|value| value.is_nan()));
+        assert!(sample(Normalization::Normalized).iter().all(|value| (0. ..=1.).contains(value)));
+    }
+    #[test]
+    fn normalize_octaves() {
+        use super::*;
+        let spawn = |octaves, normalize_octaves| {
+            let options = NoiseOptions {
+                octaves,
+                normalize_octaves,
+                ..NoiseOptions::default()
+            };
+            Generator::new()
+                .with_size(64, 64)
+                .with_seed(1)
+                .with_options(options)
+                .spawn_perlin_bands(&[(0.7, 1)])
+        };
+        // one octave has nothing to normalize
+        assert_eq!(spawn(1, true).map, spawn(1, false).map);
+        // summed octaves reach higher without it
+        let peaks = |generator: Generator| generator.positions_of(1).len();
+        assert!(peaks(spawn(8, true)) < peaks(spawn(8, false)));
+    }
+    #[test]
+    fn noise_presets() {
+        use super::*;
+        let spawn = |options: NoiseOptions| {
+            Generator::new()
+                .with_size(64, 64)
+                .with_seed(1)
+                .with_options(options)
+                .spawn_perlin_bands(&[(0.5, 1)])
+        };
+        let marble = NoiseOptions::preset(Preset::Marble);
+        assert_eq!(marble.normalization, Normalization::Normalized);
+        // warping moves the features around
+        let unwarped = spawn(NoiseOptions { warp: 0., ..marble.clone() });
+        assert_ne!(spawn(marble).map, unwarped.map);
+        let land = |preset| spawn(NoiseOptions::preset(preset)).positions_of(1).len();
+        assert!(land(Preset::Islands) < land(Preset::Caves));
+    }
+    #[test]
+    fn sampling() {
+        use super::*;
+        let spawn = |width, height, sampling| {
+            let options = NoiseOptions {
+                frequency: 4.,
+                sampling,
+                ..NoiseOptions::default()
+            };
+            Generator::new()
+                .with_size(width, height)
+                .with_seed(5)
+                .with_options(options)
+                .spawn_perlin_bands(&[(0.5, 1)])
+        };
+        // with a fixed cell size, a wider map only adds columns
+        let narrow = spawn(16, 8, Sampling::CellSize(1. / 16.));
+        let wide = spawn(32, 8, Sampling::CellSize(1. / 16.));
+        assert!(narrow.iter_cells().all(|(x, y, value)| wide.get(x, y) == value));
+        // the original sampling is the same as a cell size of one over the width
+        assert_eq!(spawn(16, 8, Sampling::Width).map, narrow.map);
+        // per axis, a taller map stretches the same noise over more rows
+        let short = spawn(16, 8, Sampling::PerAxis);
+        let tall = spawn(16, 16, Sampling::PerAxis);
+        assert!(short.iter_cells().all(|(x, y, value)| tall.get(x, y * 2) == value));
+    }
+    #[test]
     fn rooms() {
         use super::*;
         let size = Size::new((4, 4), (10, 10));
@@ -375,4 +1239,130 @@ mod tests {
         ];
         assert_eq!(generator.map, output);
     }
+    #[test]
+    fn errors() {
+        use super::*;
+        let size = Size::new((4, 4), (10, 10));
+        assert_eq!(Generator::new().try_spawn_perlin(|_| 1).unwrap_err(), GenerationError::EmptyMap);
+        assert_eq!(
+            Generator::new().with_size(3, 3).try_spawn_rooms(1, 1, &size).unwrap_err(),
+            GenerationError::RoomTooLarge
+        );
+        assert_eq!(
+            Generator::new().with_size(3, 3).try_get(3, 0),
+            Err(GenerationError::OutOfBounds { x: 3, y: 0 })
+        );
+    }
+    #[test]
+    fn accessors() {
+        use super::*;
+        let mut generator = Generator::new().with_size(3, 2);
+        generator.set(2, 1, 5);
+        assert_eq!(generator.get_checked(2, 1), Some(5));
+        assert_eq!(generator.get_checked(3, 1), None);
+        assert_eq!(generator.get_wrapped(-1, -1), 5);
+        assert_eq!(generator.get_wrapped(5, 3), 5);
+    }
+    #[test]
+    fn diff() {
+        use super::*;
+        let a = Generator::new().with_size(3, 2);
+        let b = Generator::new().with_size(3, 2).draw_line((1, 1), (2, 1), 4);
+        assert_eq!(a.diff(&b), vec![(1, 1, 0, 4), (2, 1, 0, 4)]);
+        assert!(b.diff(&b).is_empty());
+        assert_eq!(
+            a.try_diff(&Generator::new().with_size(2, 2)).unwrap_err(),
+            GenerationError::SizeMismatch {
+                expected: (3, 2),
+                found: (2, 2)
+            }
+        );
+    }
+    #[test]
+    fn rulers() {
+        use super::*;
+        let generator = Generator::new()
+            .with_size(12, 2)
+            .with_palette(Palette::new().with_glyph(0, '.'))
+            .with_rulers(true);
+        let lines: Vec<String> = generator.to_string().lines().map(str::to_string).collect();
+        assert_eq!(lines[0], format!("  0 {}1   ", " ".repeat(18)));
+        assert_eq!(lines[1], "  0 1 2 3 4 5 6 7 8 9 0 1 ");
+        assert_eq!(lines[3], format!("1 {}", ". ".repeat(12)));
+    }
+    #[test]
+    fn cell_iterators() {
+        use super::*;
+        let mut generator = Generator::new().with_size(3, 2);
+        generator.iter_cells_mut().for_each(|(x, y, value)| *value = x + y * 10);
+        assert_eq!(generator.iter_cells().nth(4), Some((1, 1, 11)));
+        let sum: usize = generator.par_iter_cells().map(|(_, _, value)| value).sum();
+        assert_eq!(sum, 36);
+    }
+    #[test]
+    fn neighbors() {
+        use super::*;
+        let generator = Generator::new().with_size(3, 3);
+        assert_eq!(generator.neighbors4(0, 0).count(), 2);
+        assert_eq!(generator.neighbors8(0, 0).count(), 3);
+        assert_eq!(generator.neighbors8(1, 1).count(), 8);
+        assert_eq!(generator.neighbors4(2, 1).collect::<Vec<_>>(), vec![(2, 0, 0), (1, 1, 0), (2, 2, 0)]);
+    }
+    #[test]
+    fn find_and_count() {
+        use super::*;
+        let mut generator = Generator::new().with_size(3, 2);
+        generator.set(1, 0, 2);
+        generator.set(2, 1, 2);
+        assert_eq!(generator.positions_of(2), vec![(1, 0), (2, 1)]);
+        assert_eq!(generator.histogram()[&0], 4);
+        assert_eq!(generator.histogram()[&2], 2);
+    }
+    #[test]
+    fn mask() {
+        use super::*;
+        let generator = Generator::new()
+            .with_size(4, 1)
+            .draw_line((0, 0), (1, 0), 1)
+            .with_mask(|_, _, value| value == 1)
+            .spawn_perlin(|_| 2)
+            .draw_line((3, 0), (3, 0), 3);
+        assert_eq!(generator.map, vec![2, 2, 0, 3]);
+        assert_eq!(generator.stages().len(), 3);
+        assert_eq!(generator.stages()[1].to_string(), "masked(inner: perlin())");
+        let error = generator.generate_until(|_| false, 2).unwrap_err();
+        assert_eq!(error, GenerationError::UnreplayableStage("mask".into()));
+
+        // a mask set when generation is cancelled doesn't outlive the skipped stage
+        let token = CancelToken::new();
+        token.cancel();
+        let generator = Generator::new()
+            .with_size(2, 1)
+            .with_cancel_token(token)
+            .with_mask(|_, _, _| false)
+            .fill_rect(0, 0, 2, 1, 1);
+        assert!(generator.mask.is_none());
+    }
+    #[test]
+    fn seed_from_string() {
+        use super::*;
+        let a = Generator::new().with_seed_str("dragon-caves-42");
+        let b = Generator::new().with_seed_str("dragon-caves-42");
+        let c = Generator::new().with_seed_str("dragon-caves-43");
+        assert_eq!(a.seed(), b.seed());
+        assert_ne!(a.seed(), c.seed());
+    }
+    #[test]
+    fn stage_seeds() {
+        use super::*;
+        let size = Size::new((2, 2), (4, 4));
+        let once = Generator::new().with_size(20, 20).with_seed(0).spawn_rooms(1, 4, &size);
+        let twice = Generator::new().with_size(20, 20).with_seed(0).spawn_rooms(1, 4, &size).spawn_rooms(2, 4, &size);
+        // the second run places different rooms instead of painting over the first
+        assert!(twice.map.contains(&1));
+        assert!(twice.map.contains(&2));
+        // and the first run is unaffected by later ones or other stages
+        let after_perlin = Generator::new().with_size(20, 20).with_seed(0).spawn_perlin(|_| 0).spawn_rooms(1, 4, &size);
+        assert_eq!(once.map, after_perlin.map);
+    }
 }
@@ -0,0 +1,97 @@
+//! Reworking mazes and corridor networks.
+
+use crate::{Generator, Stage, NEIGHBORS_4};
+use rand::prelude::*;
+
+impl Generator {
+    /// Turns a `factor` fraction of the dead ends in the `floor` into loops, by
+    /// knocking through a wall with more floor right behind it. A factor of 0 keeps
+    /// a perfect maze, with one way between any two cells, and 1 removes every dead
+    /// end it can. Walls behind which lies another dead end are knocked through
+    /// first, so one hole removes two dead ends. Dead ends with no floor two cells
+    /// away are left alone.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     // a comb of corridors, every tooth is a dead end
+    ///     let mut generator = Generator::new().with_size(21, 9).fill_rect(1, 1, 19, 1, 1);
+    ///     for x in (1..20).step_by(2) {
+    ///         generator = generator.fill_rect(x, 1, 1, 7, 1);
+    ///     }
+    ///     generator.braid(1, 0.5).show();
+    /// }
+    /// ```
+    pub fn braid(self, floor: usize, factor: f64) -> Self {
+        self.stage(Stage::Braid { floor, factor }, |mut generator| {
+            let (width, height) = (generator.width as isize, generator.height as isize);
+            let at = |x: isize, y: isize| {
+                if x < 0 || y < 0 || x >= width || y >= height {
+                    None
+                } else {
+                    Some((x + y * width) as usize)
+                }
+            };
+            let is_dead_end = |map: &[usize], pos: usize| {
+                let (x, y) = (pos as isize % width, pos as isize / width);
+                map[pos] == floor
+                    && NEIGHBORS_4
+                        .iter()
+                        .filter(|&&(dx, dy)| at(x + dx, y + dy).is_some_and(|next| map[next] == floor))
+                        .count()
+                        == 1
+            };
+            let mut dead_ends: Vec<usize> = (0..generator.map.len())
+                .filter(|&pos| is_dead_end(&generator.map, pos))
+                .collect();
+            generator.with_stage_rng("braid", |generator, rng| {
+                dead_ends.shuffle(rng);
+                for pos in dead_ends {
+                    // an earlier hole may have opened this one up already
+                    if !is_dead_end(&generator.map, pos) || rng.gen::<f64>() >= factor {
+                        continue;
+                    }
+                    let (x, y) = (pos as isize % width, pos as isize / width);
+                    let mut holes: Vec<(usize, bool)> = NEIGHBORS_4
+                        .iter()
+                        .filter_map(|&(dx, dy)| {
+                            let wall = at(x + dx, y + dy)?;
+                            let behind = at(x + dx * 2, y + dy * 2)?;
+                            if generator.map[wall] == floor || generator.map[behind] != floor {
+                                return None;
+                            }
+                            Some((wall, is_dead_end(&generator.map, behind)))
+                        })
+                        .collect();
+                    holes.shuffle(rng);
+                    if let Some(&(wall, _)) = holes.iter().find(|&&(_, dead_end)| dead_end).or_else(|| holes.first()) {
+                        generator.map[wall] = floor;
+                    }
+                }
+            });
+            generator
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn braid() {
+        // two corridors joined on the left, ending next to each other on the right
+        let maze = || {
+            Generator::new()
+                .with_size(7, 5)
+                .fill_rect(1, 1, 5, 1, 1)
+                .fill_rect(1, 3, 5, 1, 1)
+                .fill_rect(1, 2, 1, 1, 1)
+        };
+        assert_eq!(maze().braid(1, 0.).map, maze().map);
+        let braided = maze().braid(1, 1.);
+        assert_eq!(braided.get(5, 2), 1);
+        assert_eq!(braided.positions_of(1).len(), maze().positions_of(1).len() + 1);
+    }
+}
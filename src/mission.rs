@@ -0,0 +1,516 @@
+//! Dungeons laid out from a mission graph of rooms and the connections between
+//! them, so the shape of the level is designed rather than left to chance.
+
+use crate::{GenerationError, Generator, Stage, NEIGHBORS_8};
+use rand::prelude::*;
+use smart_default::*;
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// What a room in a [MissionGraph](struct.MissionGraph.html) is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum RoomKind {
+    /// Where the player enters.
+    Start,
+    /// A room several paths branch off from.
+    Hub,
+    /// Any other room.
+    Normal,
+    /// A dead end holding a reward.
+    Treasure,
+    /// The goal of the level.
+    Boss,
+}
+
+/// How two rooms in a [MissionGraph](struct.MissionGraph.html) are connected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ConnectionKind {
+    /// A plain corridor.
+    Open,
+    /// A corridor with a locked door, marked with `MissionOptions::locked`.
+    Locked,
+    /// A corridor behind a secret door, marked with `MissionOptions::secret`.
+    Secret,
+}
+
+/// Rooms and the connections between them, laid out on the map by
+/// [Generator::embed_mission](struct.Generator.html#method.embed_mission).
+/// Rooms are referred to by their index.
+///
+/// ```rust
+/// use procedural_generation::*;
+///
+/// fn main() {
+///     // a hub with a loop on one side and a hidden treasure room on the other
+///     let graph = MissionGraph::new()
+///         .with_room(RoomKind::Start)
+///         .with_room(RoomKind::Hub)
+///         .with_room(RoomKind::Normal)
+///         .with_room(RoomKind::Normal)
+///         .with_room(RoomKind::Treasure)
+///         .with_room(RoomKind::Boss)
+///         .with_connection(0, 1, ConnectionKind::Open)
+///         .with_connection(1, 2, ConnectionKind::Open)
+///         .with_connection(2, 3, ConnectionKind::Open)
+///         .with_connection(3, 1, ConnectionKind::Open)
+///         .with_connection(1, 4, ConnectionKind::Secret)
+///         .with_connection(3, 5, ConnectionKind::Locked);
+///     Generator::new()
+///         .with_size(45, 27)
+///         .embed_mission(&graph, MissionOptions::default())
+///         .show();
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MissionGraph {
+    /// Kind of every room.
+    pub rooms: Vec<RoomKind>,
+    /// Pairs of connected rooms and how they're connected.
+    pub connections: Vec<(usize, usize, ConnectionKind)>,
+}
+
+impl MissionGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Adds a room of `kind`, whose index is the number of rooms before it.
+    pub fn with_room(mut self, kind: RoomKind) -> Self {
+        self.rooms.push(kind);
+        self
+    }
+    /// Connects rooms `a` and `b`.
+    pub fn with_connection(mut self, a: usize, b: usize, kind: ConnectionKind) -> Self {
+        self.connections.push((a, b, kind));
+        self
+    }
+    /// Grows a graph of `rooms` rooms with a small graph grammar. It starts from a
+    /// start room behind which lies a locked boss room, and then either lengthens a
+    /// connection with a room in the middle or branches a treasure room off a room
+    /// on the way, at random. `loops` times a connection on the way, but not the
+    /// one into the boss room, gets a second path of two rooms next to it, which
+    /// count towards `rooms`. Rooms with three or more connections become hubs. The
+    /// same seed gives the same graph.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let graph = MissionGraph::generate(12, 1, 7);
+    ///     Generator::new()
+    ///         .with_size(60, 40)
+    ///         .embed_mission(&graph, MissionOptions::default())
+    ///         .show();
+    /// }
+    /// ```
+    pub fn generate(rooms: usize, loops: usize, seed: u64) -> Self {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(seed);
+        if rooms < 2 {
+            return (0..rooms).fold(Self::new(), |graph, _| graph.with_room(RoomKind::Start));
+        }
+        let mut graph = Self::new()
+            .with_room(RoomKind::Start)
+            .with_room(RoomKind::Boss)
+            .with_connection(0, 1, ConnectionKind::Locked);
+        let loops = loops.min((rooms - 2) / 2);
+        while graph.rooms.len() < rooms - loops * 2 {
+            let branchable: Vec<usize> = (0..graph.rooms.len())
+                .filter(|&room| graph.rooms[room] != RoomKind::Boss && graph.rooms[room] != RoomKind::Treasure)
+                .filter(|&room| graph.degree(room) < 3)
+                .collect();
+            let new = graph.rooms.len();
+            match branchable.choose(&mut rng) {
+                Some(&room) if rng.gen_bool(0.4) => {
+                    let kind = if rng.gen_bool(0.3) { ConnectionKind::Secret } else { ConnectionKind::Open };
+                    graph = graph.with_room(RoomKind::Treasure).with_connection(room, new, kind);
+                }
+                _ => {
+                    // the new room goes in the middle, the kind stays with the far end
+                    // so the boss room stays locked
+                    let i = rng.gen_range(0, graph.connections.len());
+                    let (a, b, kind) = graph.connections[i];
+                    graph.connections[i] = (a, new, ConnectionKind::Open);
+                    graph = graph.with_room(RoomKind::Normal).with_connection(new, b, kind);
+                }
+            }
+        }
+        for _ in 0..loops {
+            let candidates: Vec<(usize, usize)> = graph
+                .connections
+                .iter()
+                .filter(|&&(a, b, _)| {
+                    [a, b]
+                        .iter()
+                        .all(|&room| graph.rooms[room] == RoomKind::Start || graph.rooms[room] == RoomKind::Normal)
+                })
+                .filter(|&&(a, b, _)| graph.degree(a) < 4 && graph.degree(b) < 4)
+                .map(|&(a, b, _)| (a, b))
+                .collect();
+            let (a, b) = match candidates.choose(&mut rng) {
+                Some(&pair) => pair,
+                None => break,
+            };
+            let new = graph.rooms.len();
+            graph = graph
+                .with_room(RoomKind::Normal)
+                .with_room(RoomKind::Normal)
+                .with_connection(a, new, ConnectionKind::Open)
+                .with_connection(new, new + 1, ConnectionKind::Open)
+                .with_connection(new + 1, b, ConnectionKind::Open);
+        }
+        for room in 0..graph.rooms.len() {
+            if graph.rooms[room] == RoomKind::Normal && graph.degree(room) >= 3 {
+                graph.rooms[room] = RoomKind::Hub;
+            }
+        }
+        graph
+    }
+    fn degree(&self, room: usize) -> usize {
+        self.connections.iter().filter(|&&(a, b, _)| a == room || b == room).count()
+    }
+    // rooms in breadth first order from the first room, fails on broken
+    // connections and on rooms that can't be reached
+    fn order(&self) -> Result<Vec<usize>, GenerationError> {
+        let mut neighbors = vec![vec![]; self.rooms.len()];
+        for (i, &(a, b, _)) in self.connections.iter().enumerate() {
+            if a >= self.rooms.len() || b >= self.rooms.len() {
+                let reason = format!("connection {} refers to a room that doesn't exist", i);
+                return Err(GenerationError::InvalidMission(reason));
+            }
+            if a == b {
+                let reason = format!("connection {} connects room {} to itself", i, a);
+                return Err(GenerationError::InvalidMission(reason));
+            }
+            neighbors[a].push(b);
+            neighbors[b].push(a);
+        }
+        let mut order = vec![];
+        let mut seen = vec![false; self.rooms.len()];
+        let mut queue = VecDeque::new();
+        if !self.rooms.is_empty() {
+            seen[0] = true;
+            queue.push_back(0);
+        }
+        while let Some(room) = queue.pop_front() {
+            order.push(room);
+            for &next in &neighbors[room] {
+                if !seen[next] {
+                    seen[next] = true;
+                    queue.push_back(next);
+                }
+            }
+        }
+        match seen.iter().position(|&seen| !seen) {
+            Some(room) => Err(GenerationError::InvalidMission(format!("room {} can't be reached from room 0", room))),
+            None => Ok(order),
+        }
+    }
+}
+
+/// How [Generator::embed_mission](struct.Generator.html#method.embed_mission)
+/// draws the rooms and connections.
+#[derive(Debug, Clone, PartialEq, SmartDefault)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MissionOptions {
+    /// Width and height of the square cell every room is drawn in, rooms are at
+    /// most 2 smaller so walls stay between them. Default is 9.
+    #[default = 9]
+    pub cell_size: usize,
+    /// Smallest width and height of a room. Default is 3.
+    #[default = 3]
+    pub min_room_size: usize,
+    /// Value of rooms and corridors. Default is 1.
+    #[default = 1]
+    pub floor: usize,
+    /// Value of locked doors. Default is 2.
+    #[default = 2]
+    pub locked: usize,
+    /// Value of secret doors. Default is 3.
+    #[default = 3]
+    pub secret: usize,
+}
+
+/// A room drawn by [Generator::embed_mission](struct.Generator.html#method.embed_mission).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MissionRoom {
+    pub kind: RoomKind,
+    /// Top left corner.
+    pub position: (usize, usize),
+    /// Width and height.
+    pub size: (usize, usize),
+}
+
+// placement of the rooms on a grid of cells, connected rooms sit side by side or
+// diagonally, where a free cell in the corner takes the corridor
+#[derive(Clone)]
+struct Layout {
+    columns: usize,
+    rows: usize,
+    taken: Vec<bool>,
+    positions: Vec<Option<(usize, usize)>>,
+    corners: Vec<Option<(usize, usize)>>,
+}
+
+impl Layout {
+    // places the rooms from `order[index]` onwards, backtracking when a room has
+    // nowhere to go, gives up once `budget` placements have been tried
+    fn search(
+        self,
+        graph: &MissionGraph,
+        order: &[usize],
+        index: usize,
+        rng: &mut dyn RngCore,
+        budget: &mut usize,
+    ) -> Option<Layout> {
+        let room = match order.get(index) {
+            Some(&room) => room,
+            None => return Some(self),
+        };
+        let mut candidates: Vec<(usize, usize)> = match index {
+            0 => (0..self.columns * self.rows).map(|i| (i % self.columns, i / self.columns)).collect(),
+            // breadth first, so one of the neighbors is already placed
+            _ => {
+                let placed = graph
+                    .connections
+                    .iter()
+                    .filter_map(|&(a, b, _)| match (a == room, b == room) {
+                        (true, _) => self.positions[b],
+                        (_, true) => self.positions[a],
+                        _ => None,
+                    })
+                    .next()?;
+                self.around(placed).collect()
+            }
+        };
+        candidates.shuffle(rng);
+        for cell in candidates {
+            if *budget == 0 {
+                return None;
+            }
+            *budget -= 1;
+            if let Some(layout) = self.place(graph, room, cell, rng) {
+                if let Some(layout) = layout.search(graph, order, index + 1, rng, budget) {
+                    return Some(layout);
+                }
+            }
+        }
+        None
+    }
+    // puts `room` in `cell` if it's free and next to every placed room it's
+    // connected to
+    fn place(&self, graph: &MissionGraph, room: usize, cell: (usize, usize), rng: &mut dyn RngCore) -> Option<Layout> {
+        if self.taken[cell.0 + cell.1 * self.columns] {
+            return None;
+        }
+        let mut layout = self.clone();
+        layout.taken[cell.0 + cell.1 * self.columns] = true;
+        layout.positions[room] = Some(cell);
+        for (i, &(a, b, _)) in graph.connections.iter().enumerate() {
+            let other = match (a == room, b == room) {
+                (true, _) => b,
+                (_, true) => a,
+                _ => continue,
+            };
+            let other = match layout.positions[other] {
+                Some(other) if other != cell => other,
+                _ => continue,
+            };
+            let (dx, dy) = (cell.0.max(other.0) - cell.0.min(other.0), cell.1.max(other.1) - cell.1.min(other.1));
+            match (dx, dy) {
+                (0, 1) | (1, 0) => {}
+                (1, 1) => {
+                    let mut corners = [(cell.0, other.1), (other.0, cell.1)];
+                    corners.shuffle(rng);
+                    let corner = corners.iter().find(|&&(x, y)| !layout.taken[x + y * self.columns])?;
+                    layout.taken[corner.0 + corner.1 * self.columns] = true;
+                    layout.corners[i] = Some(*corner);
+                }
+                _ => return None,
+            }
+        }
+        Some(layout)
+    }
+    fn around(&self, (x, y): (usize, usize)) -> impl Iterator<Item = (usize, usize)> + '_ {
+        NEIGHBORS_8.iter().filter_map(move |&(dx, dy)| {
+            let (nx, ny) = (x as isize + dx, y as isize + dy);
+            if nx < 0 || ny < 0 || nx >= self.columns as isize || ny >= self.rows as isize {
+                None
+            } else {
+                Some((nx as usize, ny as usize))
+            }
+        })
+    }
+}
+
+impl Generator {
+    /// Lays out the rooms of `graph` on the map and carves them, along with a
+    /// corridor for every connection and nothing more, so hubs, loops and dead end
+    /// treasure rooms come out exactly as designed. Rooms go in a grid of square
+    /// cells, with connected rooms side by side or diagonally, and the first room
+    /// is placed first. Locked and secret connections get a door next to their
+    /// second room. The rooms are listed in `artifacts().mission`, in the same
+    /// order as in the graph. Panics if the graph can't be laid out, see
+    /// `try_embed_mission(...)`.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let graph = MissionGraph::new()
+    ///         .with_room(RoomKind::Start)
+    ///         .with_room(RoomKind::Treasure)
+    ///         .with_room(RoomKind::Boss)
+    ///         .with_connection(0, 1, ConnectionKind::Open)
+    ///         .with_connection(0, 2, ConnectionKind::Locked);
+    ///     let generator = Generator::new()
+    ///         .with_size(40, 20)
+    ///         .embed_mission(&graph, MissionOptions::default());
+    ///     generator.show();
+    ///     println!("{:?}", generator.artifacts().mission);
+    /// }
+    /// ```
+    pub fn embed_mission(self, graph: &MissionGraph, options: MissionOptions) -> Self {
+        match self.try_embed_mission(graph, options) {
+            Ok(generator) => generator,
+            Err(error) => panic!("{}", error),
+        }
+    }
+    /// Same as `embed_mission(...)`, except it fails with
+    /// [GenerationError::InvalidMission](enum.GenerationError.html) when a
+    /// connection is broken, a room can't be reached from the first room, or
+    /// no layout fits on the map. Every room can have up to 8 connections, but
+    /// the more connections, the harder a layout is to find.
+    pub fn try_embed_mission(self, graph: &MissionGraph, options: MissionOptions) -> Result<Self, GenerationError> {
+        let order = graph.order()?;
+        let size = options.cell_size;
+        if size < 3 {
+            return Err(GenerationError::InvalidMission("cells must be at least 3 wide".into()));
+        }
+        let (columns, rows) = (self.width / size, self.height / size);
+        if graph.rooms.len() > columns * rows {
+            let reason = format!("{} rooms don't fit in {} cells", graph.rooms.len(), columns * rows);
+            return Err(GenerationError::InvalidMission(reason));
+        }
+        let stage = Stage::Mission {
+            graph: graph.clone(),
+            options: options.clone(),
+        };
+        let mut found = true;
+        let generator = self.stage(stage, |mut generator| {
+            let layout = Layout {
+                columns,
+                rows,
+                taken: vec![false; columns * rows],
+                positions: vec![None; graph.rooms.len()],
+                corners: vec![None; graph.connections.len()],
+            };
+            generator.with_stage_rng("mission", |generator, rng| {
+                // deep backtracking rarely recovers from a bad early choice, so
+                // it gives up quickly and starts over instead
+                let layout = (0..100).find_map(|_| layout.clone().search(graph, &order, 0, rng, &mut 2000));
+                let layout = match layout {
+                    Some(layout) => layout,
+                    None => {
+                        found = false;
+                        return;
+                    }
+                };
+                let width = generator.width;
+                let center = |(column, row): (usize, usize)| (column * size + size / 2, row * size + size / 2);
+                let mut in_room = vec![false; generator.map.len()];
+                let largest = size - 2;
+                let smallest = options.min_room_size.clamp(1, largest);
+                for (room, &kind) in graph.rooms.iter().enumerate() {
+                    let (x, y) = center(layout.positions[room].unwrap());
+                    let (w, h) = (rng.gen_range(smallest, largest + 1), rng.gen_range(smallest, largest + 1));
+                    let position = (x - w / 2, y - h / 2);
+                    for y in position.1..position.1 + h {
+                        for x in position.0..position.0 + w {
+                            generator.map[x + y * width] = options.floor;
+                            in_room[x + y * width] = true;
+                        }
+                    }
+                    generator.artifacts.mission.push(MissionRoom {
+                        kind,
+                        position,
+                        size: (w, h),
+                    });
+                }
+                for (i, &(a, b, kind)) in graph.connections.iter().enumerate() {
+                    let mut points = vec![center(layout.positions[a].unwrap())];
+                    points.extend(layout.corners[i].map(center));
+                    points.push(center(layout.positions[b].unwrap()));
+                    // from `a` towards `b`, so the last cell outside a room is the
+                    // one next to `b`
+                    let toward = |from: usize, to: usize| match from.cmp(&to) {
+                        Ordering::Less => from + 1,
+                        Ordering::Greater => from - 1,
+                        Ordering::Equal => from,
+                    };
+                    let mut corridor = vec![];
+                    for pair in points.windows(2) {
+                        let ((mut x, mut y), (x2, y2)) = (pair[0], pair[1]);
+                        corridor.push(x + y * width);
+                        while (x, y) != (x2, y2) {
+                            x = toward(x, x2);
+                            y = toward(y, y2);
+                            corridor.push(x + y * width);
+                        }
+                    }
+                    for &pos in &corridor {
+                        generator.map[pos] = options.floor;
+                    }
+                    let door = match kind {
+                        ConnectionKind::Open => continue,
+                        ConnectionKind::Locked => options.locked,
+                        ConnectionKind::Secret => options.secret,
+                    };
+                    if let Some(&pos) = corridor.iter().rev().find(|&&pos| !in_room[pos]) {
+                        generator.map[pos] = door;
+                    }
+                }
+            });
+            generator
+        });
+        if found {
+            Ok(generator)
+        } else {
+            Err(GenerationError::InvalidMission("no layout fits on the map".into()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn embed_mission() {
+        let graph = MissionGraph::generate(10, 1, 3);
+        assert_eq!(graph.rooms.len(), 10);
+        assert_eq!(graph.rooms.iter().filter(|&&kind| kind == RoomKind::Boss).count(), 1);
+
+        let options = MissionOptions::default();
+        let generator = Generator::new().with_size(54, 45).embed_mission(&graph, options.clone());
+        let rooms = &generator.artifacts().mission;
+        assert_eq!(rooms.len(), 10);
+        // with the doors open every room can be reached from the start
+        let open = |value: usize| value != 0;
+        let start = rooms[0].position;
+        assert!(rooms.iter().all(|room| generator.is_reachable(start, room.position, open)));
+        // the only way into the boss room is through the locked door
+        let boss = rooms.iter().find(|room| room.kind == RoomKind::Boss).unwrap().position;
+        assert!(!generator.is_reachable(start, boss, |value| value == options.floor));
+
+        let broken = MissionGraph::new().with_room(RoomKind::Start).with_room(RoomKind::Boss);
+        let error = Generator::new().with_size(20, 20).try_embed_mission(&broken, options);
+        assert_eq!(error.unwrap_err(), GenerationError::InvalidMission("room 1 can't be reached from room 0".into()));
+    }
+}
@@ -0,0 +1,307 @@
+//! Colors used when printing the map.
+
+use crate::Generator;
+use owo_colors::OwoColorize;
+use std::collections::BTreeMap;
+use std::fmt;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// truecolor versions of the default colors, indexed by value % 7
+const DEFAULT_RGB: [(u8, u8, u8); 7] = [
+    (0, 0, 238),
+    (205, 0, 0),
+    (0, 205, 0),
+    (0, 205, 205),
+    (205, 0, 205),
+    (229, 229, 229),
+    (205, 205, 0),
+];
+
+// red, green and blue
+type Rgb = (u8, u8, u8);
+
+// colors of the presets, for values 0 and up
+const PRESETS: [(&str, [Rgb; 8]); 4] = [
+    (
+        "viridis",
+        [
+            (68, 1, 84),
+            (70, 50, 126),
+            (54, 92, 141),
+            (39, 127, 142),
+            (31, 161, 135),
+            (74, 193, 109),
+            (160, 218, 57),
+            (253, 231, 37),
+        ],
+    ),
+    (
+        "okabe-ito",
+        [
+            (0, 0, 0),
+            (230, 159, 0),
+            (86, 180, 233),
+            (0, 158, 115),
+            (240, 228, 66),
+            (0, 114, 178),
+            (213, 94, 0),
+            (204, 121, 167),
+        ],
+    ),
+    (
+        "high-contrast",
+        [
+            (0, 0, 0),
+            (255, 255, 255),
+            (255, 255, 0),
+            (0, 255, 255),
+            (255, 0, 255),
+            (255, 0, 0),
+            (0, 255, 0),
+            (0, 0, 255),
+        ],
+    ),
+    (
+        "grayscale",
+        [
+            (0, 0, 0),
+            (36, 36, 36),
+            (73, 73, 73),
+            (109, 109, 109),
+            (146, 146, 146),
+            (182, 182, 182),
+            (219, 219, 219),
+            (255, 255, 255),
+        ],
+    ),
+];
+
+/// How a single value is printed, see [Palette](struct.Palette.html).
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Swatch {
+    /// Color of the text as red, green and blue.
+    pub fg: Option<(u8, u8, u8)>,
+    /// Color behind the text as red, green and blue.
+    pub bg: Option<(u8, u8, u8)>,
+    /// Printed instead of the value.
+    pub glyph: Option<char>,
+    /// What the value stands for, shown in the legend.
+    pub name: Option<String>,
+}
+
+impl Swatch {
+    // prints `value`, or the glyph, followed by a space in the colors of the swatch
+    fn write(&self, f: &mut fmt::Formatter, value: usize) -> fmt::Result {
+        let text = match self.glyph {
+            Some(glyph) => glyph.to_string(),
+            None => value.to_string(),
+        };
+        match (self.fg, self.bg) {
+            (Some((r, g, b)), Some((br, bg, bb))) => write!(f, "{} ", text.truecolor(r, g, b).on_truecolor(br, bg, bb)),
+            (Some((r, g, b)), None) => write!(f, "{} ", text.truecolor(r, g, b)),
+            (None, Some((r, g, b))) => write!(f, "{} ", text.on_truecolor(r, g, b)),
+            (None, None) => write!(f, "{} ", text),
+        }
+    }
+}
+
+/// Colors for printing values, values without a swatch keep the default colors
+/// which repeat every 7 values.
+///
+/// ```rust
+/// use procedural_generation::*;
+///
+/// fn main() {
+///     let palette = Palette::new()
+///         .with_color(0, (30, 60, 200))
+///         .with_color(1, (240, 220, 130))
+///         .with_background(2, (20, 120, 30));
+///     Generator::new()
+///         .with_size(40, 10)
+///         .with_palette(palette)
+///         .spawn_perlin_bands(&[(0.66, 2), (0.33, 1)])
+///         .show();
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Palette {
+    swatches: BTreeMap<usize, Swatch>,
+}
+
+impl Palette {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Returns the built-in palette called `name`, which colors the values 0 to 7:
+    ///
+    /// * `"viridis"`, dark purple to yellow, readable with every common form of
+    ///   color blindness and in grayscale, suits ordered values such as elevation
+    /// * `"okabe-ito"`, eight colors that stay apart with color blindness, suits
+    ///   unrelated values such as biomes
+    /// * `"high-contrast"`, saturated primaries on black and white
+    /// * `"grayscale"`, black to white
+    ///
+    /// Returns `None` for any other name. Like any palette, presets can be changed
+    /// further with `with_color` and friends.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     Generator::new()
+    ///         .with_size(40, 10)
+    ///         .with_palette(Palette::preset("viridis").unwrap())
+    ///         .spawn_perlin_bands(&[(0.75, 3), (0.5, 2), (0.25, 1)])
+    ///         .show();
+    /// }
+    /// ```
+    pub fn preset(name: &str) -> Option<Self> {
+        let (_, colors) = PRESETS.iter().find(|(preset, _)| *preset == name)?;
+        let palette = colors
+            .iter()
+            .enumerate()
+            .fold(Self::new(), |palette, (value, &color)| palette.with_color(value, color));
+        Some(palette)
+    }
+    /// Names of the built-in palettes, see `preset()`.
+    pub fn preset_names() -> impl Iterator<Item = &'static str> {
+        PRESETS.iter().map(|(name, _)| *name)
+    }
+    /// Prints `value` in the `fg` text color.
+    pub fn with_color(mut self, value: usize, fg: (u8, u8, u8)) -> Self {
+        self.swatches.entry(value).or_default().fg = Some(fg);
+        self
+    }
+    /// Prints `value` on top of the `bg` background color.
+    pub fn with_background(mut self, value: usize, bg: (u8, u8, u8)) -> Self {
+        self.swatches.entry(value).or_default().bg = Some(bg);
+        self
+    }
+    /// Prints `value` as `glyph` instead of as a number.
+    pub fn with_glyph(mut self, value: usize, glyph: char) -> Self {
+        self.swatches.entry(value).or_default().glyph = Some(glyph);
+        self
+    }
+    /// Names what `value` stands for in the legend, see `show_with_legend()`.
+    pub fn with_name(mut self, value: usize, name: &str) -> Self {
+        self.swatches.entry(value).or_default().name = Some(name.to_string());
+        self
+    }
+    /// Returns the swatch for `value`, if the palette has one.
+    pub fn get(&self, value: usize) -> Option<&Swatch> {
+        self.swatches.get(&value)
+    }
+    /// Single color for `value`, for output where a cell is a block of color, such
+    /// as images. That's the background of its swatch, or else the text color, or
+    /// else the default color.
+    pub fn rgb(&self, value: usize) -> (u8, u8, u8) {
+        self.get(value)
+            .and_then(|swatch| swatch.bg.or(swatch.fg))
+            .unwrap_or(DEFAULT_RGB[value % 7])
+    }
+    // prints `value` followed by a space, with its swatch or the default colors
+    pub(crate) fn write(&self, f: &mut fmt::Formatter, value: usize) -> fmt::Result {
+        if let Some(swatch) = self.get(value) {
+            return swatch.write(f, value);
+        }
+        match value % 7 {
+            1 => write!(f, "{:?} ", value.red()),
+            2 => write!(f, "{:?} ", value.green()),
+            3 => write!(f, "{:?} ", value.cyan()),
+            4 => write!(f, "{:?} ", value.magenta()),
+            5 => write!(f, "{:?} ", value.white()),
+            6 => write!(f, "{:?} ", value.yellow()),
+            _ => write!(f, "{:?} ", value.blue()),
+        }
+    }
+}
+
+// one line per value in the map: how it's printed, the value and its name
+struct Legend<'a>(&'a Generator);
+
+impl fmt::Display for Legend<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Legend(generator) = self;
+        let mut values: Vec<usize> = generator.histogram().into_keys().collect();
+        values.sort_unstable();
+        for (i, &value) in values.iter().enumerate() {
+            generator.palette.write(f, value)?;
+            write!(f, "{}", value)?;
+            if let Some(name) = generator.palette.get(value).and_then(|swatch| swatch.name.as_ref()) {
+                write!(f, " {}", name)?;
+            }
+            if i + 1 < values.len() {
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Generator {
+    /// Prints the map with `palette` from now on, for maps with more values than
+    /// the 7 default colors can tell apart.
+    pub fn with_palette(mut self, palette: Palette) -> Self {
+        self.palette = palette;
+        self
+    }
+    /// Same as `show()`, except a legend of every value in the map is printed
+    /// below it, with the names given by the palette, so screenshots and logs
+    /// explain themselves.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let palette = Palette::new()
+    ///         .with_name(0, "water")
+    ///         .with_name(1, "grass")
+    ///         .with_glyph(2, '^')
+    ///         .with_name(2, "mountain");
+    ///     Generator::new()
+    ///         .with_size(40, 10)
+    ///         .with_palette(palette)
+    ///         .spawn_perlin_bands(&[(0.66, 2), (0.33, 1)])
+    ///         .show_with_legend();
+    /// }
+    /// ```
+    pub fn show_with_legend(&self) {
+        println!("{}\n\n{}", self, Legend(self));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn palette() {
+        let generator = Generator::new()
+            .with_size(2, 1)
+            .draw_line((1, 0), (1, 0), 1)
+            .with_palette(Palette::new().with_color(1, (1, 2, 3)).with_background(1, (4, 5, 6)));
+        let printed = generator.to_string();
+        // values without a swatch keep the default colors
+        assert!(printed.starts_with("\u{1b}[34m0\u{1b}[0m "));
+        assert!(printed.contains("\u{1b}[38;2;1;2;3m"));
+        assert!(printed.contains("\u{1b}[48;2;4;5;6m"));
+
+        let generator = generator.with_palette(Palette::new().with_glyph(1, '#').with_name(1, "wall"));
+        assert!(generator.to_string().ends_with("# "));
+        assert!(Legend(&generator).to_string().ends_with("# 1 wall"));
+    }
+    #[test]
+    fn presets() {
+        for name in Palette::preset_names() {
+            let palette = Palette::preset(name).unwrap();
+            assert!(palette.get(7).is_some());
+            assert!(palette.get(8).is_none());
+        }
+        assert_eq!(Palette::preset("viridis").unwrap().rgb(7), (253, 231, 37));
+        assert_eq!(Palette::new().rgb(8), DEFAULT_RGB[1]);
+        assert!(Palette::preset("rainbow").is_none());
+    }
+}
@@ -0,0 +1,219 @@
+//! Pathfinding over the map.
+
+use crate::Generator;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+
+impl Generator {
+    /// Finds the cheapest path from `start` to `goal` with A*, moving between
+    /// orthogonal neighbors. `cost(value)` returns what it costs to step onto a cell
+    /// holding `value`, or `None` if the cell can't be entered. Costs below 1 are
+    /// treated as 1. The returned path includes both `start` and `goal`, and is `None`
+    /// when the goal can't be reached.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let generator = Generator::new()
+    ///         .with_size(40, 10)
+    ///         .spawn_perlin(|value| if value > 0.6 { 1 } else { 0 });
+    ///     // walls can't be crossed, everything else costs the same
+    ///     let path = generator.path((0, 0), (39, 9), |value| if value == 1 { None } else { Some(1) });
+    ///     println!("{:?}", path);
+    /// }
+    /// ```
+    pub fn path<F: Fn(usize) -> Option<u32>>(
+        &self,
+        start: (usize, usize),
+        goal: (usize, usize),
+        cost: F,
+    ) -> Option<Vec<(usize, usize)>> {
+        cost(self.get_checked(goal.0, goal.1)?)?;
+        self.path_by(start, goal, |_, next| cost(self.map[next]))
+    }
+    // same as `path(...)`, except `cost(from, to)` prices a step between two cells
+    // given as indices into the map, for costs that depend on more than the value
+    pub(crate) fn path_by<F: Fn(usize, usize) -> Option<u32>>(
+        &self,
+        start: (usize, usize),
+        goal: (usize, usize),
+        cost: F,
+    ) -> Option<Vec<(usize, usize)>> {
+        self.get_checked(start.0, start.1)?;
+        self.get_checked(goal.0, goal.1)?;
+
+        let width = self.width;
+        let start = start.0 + start.1 * width;
+        let goal = goal.0 + goal.1 * width;
+        let heuristic = |pos: usize| {
+            let (x, y) = (pos % width, pos / width);
+            let (gx, gy) = (goal % width, goal / width);
+            (x.max(gx) - x.min(gx) + y.max(gy) - y.min(gy)) as u32
+        };
+
+        let mut costs = vec![u32::MAX; self.map.len()];
+        let mut came_from = vec![usize::MAX; self.map.len()];
+        let mut open = BinaryHeap::new();
+        costs[start] = 0;
+        open.push(Reverse((heuristic(start), start)));
+
+        while let Some(Reverse((_, pos))) = open.pop() {
+            if pos == goal {
+                let mut path = vec![(pos % width, pos / width)];
+                let mut pos = pos;
+                while pos != start {
+                    pos = came_from[pos];
+                    path.push((pos % width, pos / width));
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for (x, y, _) in self.neighbors4(pos % width, pos / width) {
+                let next = x + y * width;
+                let step = match cost(pos, next) {
+                    Some(step) => step.max(1),
+                    None => continue,
+                };
+                let next_cost = costs[pos].saturating_add(step);
+                if next_cost < costs[next] {
+                    costs[next] = next_cost;
+                    came_from[next] = pos;
+                    open.push(Reverse((next_cost.saturating_add(heuristic(next)), next)));
+                }
+            }
+        }
+        None
+    }
+    /// Returns true if `goal` can be reached from `start` through cells whose value
+    /// is `passable`, moving between orthogonal neighbors. Cheaper than `path(...)`
+    /// when only the answer matters, such as when validating a level.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let generator = Generator::new()
+    ///         .with_size(40, 10)
+    ///         .spawn_perlin(|value| if value > 0.6 { 1 } else { 0 });
+    ///     if !generator.is_reachable((0, 0), (39, 9), |value| value == 0) {
+    ///         println!("unwinnable, try another seed");
+    ///     }
+    /// }
+    /// ```
+    pub fn is_reachable<F: Fn(usize) -> bool>(&self, start: (usize, usize), goal: (usize, usize), passable: F) -> bool {
+        self.steps_between(start, goal, passable).is_some()
+    }
+    /// Same as `is_reachable(...)`, except it returns the amount of steps on the
+    /// shortest path, or `None` when the goal can't be reached.
+    pub fn steps_between<F: Fn(usize) -> bool>(
+        &self,
+        start: (usize, usize),
+        goal: (usize, usize),
+        passable: F,
+    ) -> Option<usize> {
+        self.get_checked(start.0, start.1)?;
+        if !passable(self.get_checked(goal.0, goal.1)?) {
+            return None;
+        }
+        let width = self.width;
+        let goal = goal.0 + goal.1 * width;
+        let mut steps = vec![usize::MAX; self.map.len()];
+        let mut open = VecDeque::new();
+        steps[start.0 + start.1 * width] = 0;
+        open.push_back(start.0 + start.1 * width);
+
+        while let Some(pos) = open.pop_front() {
+            if pos == goal {
+                return Some(steps[pos]);
+            }
+            for (x, y, value) in self.neighbors4(pos % width, pos / width) {
+                let next = x + y * width;
+                if steps[next] == usize::MAX && passable(value) {
+                    steps[next] = steps[pos] + 1;
+                    open.push_back(next);
+                }
+            }
+        }
+        None
+    }
+    /// Builds a Dijkstra map: the cost of the cheapest path from the nearest of
+    /// `sources` to every cell, using the same `cost` closure as `path(...)`. Sources
+    /// have a distance of 0, and cells that can't be reached hold `u32::MAX`.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let generator = Generator::new().with_size(10, 10);
+    ///     let distances = generator.dijkstra_map(&[(0, 0)], |_| Some(1));
+    ///     assert_eq!(distances[9 + 9 * 10], 18);
+    /// }
+    /// ```
+    pub fn dijkstra_map<F: Fn(usize) -> Option<u32>>(&self, sources: &[(usize, usize)], cost: F) -> Vec<u32> {
+        let width = self.width;
+        let mut distances = vec![u32::MAX; self.map.len()];
+        let mut open = BinaryHeap::new();
+        for &(x, y) in sources {
+            if self.get_checked(x, y).is_some() {
+                distances[x + y * width] = 0;
+                open.push(Reverse((0, x + y * width)));
+            }
+        }
+
+        while let Some(Reverse((distance, pos))) = open.pop() {
+            if distance > distances[pos] {
+                continue;
+            }
+            for (x, y, value) in self.neighbors4(pos % width, pos / width) {
+                let step = match cost(value) {
+                    Some(step) => step.max(1),
+                    None => continue,
+                };
+                let next = x + y * width;
+                let next_distance = distance.saturating_add(step);
+                if next_distance < distances[next] {
+                    distances[next] = next_distance;
+                    open.push(Reverse((next_distance, next)));
+                }
+            }
+        }
+        distances
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_around_wall() {
+        let mut generator = Generator::new().with_size(5, 3);
+        generator.map = vec![
+            0,0,1,0,0,
+            0,0,1,0,0,
+            0,0,0,0,0,
+        ];
+        let walls = |value| if value == 1 { None } else { Some(1) };
+        let path = generator.path((0, 0), (4, 0), walls).unwrap();
+        assert_eq!(path.len(), 9);
+        assert_eq!(path[0], (0, 0));
+        assert_eq!(path[8], (4, 0));
+        assert!(path.iter().all(|&(x, y)| generator.get(x, y) == 0));
+
+        assert_eq!(generator.steps_between((0, 0), (4, 0), |value| value == 0), Some(8));
+
+        generator.set(2, 2, 1);
+        assert_eq!(generator.path((0, 0), (4, 0), walls), None);
+        assert!(!generator.is_reachable((0, 0), (4, 0), |value| value == 0));
+        assert!(generator.is_reachable((0, 0), (1, 2), |value| value == 0));
+    }
+
+    #[test]
+    fn dijkstra_map_from_two_sources() {
+        let mut generator = Generator::new().with_size(5, 1);
+        generator.map = vec![0, 0, 0, 1, 0];
+        let distances = generator.dijkstra_map(&[(0, 0), (2, 0)], |value| if value == 1 { None } else { Some(1) });
+        assert_eq!(distances, vec![0, 1, 0, u32::MAX, u32::MAX]);
+    }
+}
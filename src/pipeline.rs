@@ -0,0 +1,834 @@
+//! Recording and replaying the stages that produced a map.
+
+use crate::{derive_seed, BridgeOptions, Decoration, DepositionOptions, ExitConstraints, GenerationError, Generator, LightOptions, MissionGraph, MissionOptions, NoiseOptions, QuotaStrategy, Registry, RiverOptions, Rule, Size, Tileset, TrafficOptions, TrapOptions, Variant, VillageOptions};
+use rayon::prelude::*;
+use std::fmt;
+use std::ops::RangeInclusive;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// `(value, weight)` pairs of a weighted pick
+type Choices = Vec<(usize, f64)>;
+
+/// One recorded stage along with its parameters.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "stage", rename_all = "snake_case"))]
+pub enum Stage {
+    /// `spawn_perlin_bands(...)`.
+    PerlinBands {
+        #[cfg_attr(feature = "serde", serde(default))]
+        options: NoiseOptions,
+        bands: Vec<(f64, usize)>,
+    },
+    /// `spawn_rooms(...)`.
+    Rooms {
+        number: usize,
+        rooms: usize,
+        min_size: (usize, usize),
+        max_size: (usize, usize),
+    },
+    /// `draw_line(...)`.
+    Line {
+        from: (usize, usize),
+        to: (usize, usize),
+        value: usize,
+    },
+    /// `draw_rect(...)` or `fill_rect(...)`.
+    Rect {
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        value: usize,
+        filled: bool,
+    },
+    /// `draw_circle(...)` or `fill_circle(...)`.
+    Circle {
+        center: (usize, usize),
+        radius: usize,
+        value: usize,
+        filled: bool,
+    },
+    /// `replace_weighted(...)`.
+    ReplaceWeighted { from: usize, choices: Vec<(usize, f64)> },
+    /// `fill_weighted(...)`.
+    FillWeighted { choices: Vec<(usize, f64)> },
+    /// `rewrite(...)`.
+    Rewrite { rules: Vec<Rule>, steps: usize },
+    /// `ensure_quota(...)`.
+    Quota {
+        value: usize,
+        min_fraction: f64,
+        strategy: QuotaStrategy,
+    },
+    /// `scatter_loot(...)`.
+    Loot {
+        start: (usize, usize),
+        floor: usize,
+        loot: usize,
+        count: usize,
+    },
+    /// `place_exit(...)`.
+    Exit {
+        start: (usize, usize),
+        constraints: ExitConstraints,
+    },
+    /// `place_traps(...)`.
+    Traps {
+        start: (usize, usize),
+        exit: (usize, usize),
+        options: TrapOptions,
+    },
+    /// `spawn_villages(...)`.
+    Villages { count: usize, options: VillageOptions },
+    /// `spawn_clusters(...)`.
+    Clusters {
+        value: usize,
+        clusters: usize,
+        cluster_size: usize,
+        spread: f64,
+    },
+    /// `generate_patrols(...)`.
+    Patrols { count: usize, length: usize, floor: usize },
+    /// `classify_depth(...)`.
+    DepthTiers {
+        entrance: (usize, usize),
+        floor: usize,
+        tiers: usize,
+    },
+    /// `spawn_flow_field(...)`.
+    FlowField { options: NoiseOptions },
+    /// `spawn_faults(...)`.
+    Faults { iterations: usize },
+    /// `spawn_deposition(...)`.
+    Deposition { particles: usize, options: DepositionOptions },
+    /// `settle(...)`.
+    Settle {
+        material: usize,
+        empty: usize,
+        liquid: bool,
+        steps: usize,
+    },
+    /// `flow_water(...)`.
+    FlowWater { water: usize, ground: usize, steps: usize },
+    /// `classify(...)`.
+    Classify { field: String, bands: Vec<(f64, usize)> },
+    /// `synthesize(...)`, with the example as rows.
+    Synthesize { example: Vec<Vec<usize>> },
+    /// `embed_mission(...)`.
+    Mission { graph: MissionGraph, options: MissionOptions },
+    /// `collapse_tiles(...)`.
+    CollapseTiles { tileset: Tileset },
+    /// `classify_banded(...)`.
+    ClassifyBanded {
+        field: String,
+        bands: Vec<(RangeInclusive<f64>, Choices)>,
+    },
+    /// `braid(...)`.
+    Braid { floor: usize, factor: f64 },
+    /// `carve_rivers(...)`.
+    Rivers { options: RiverOptions },
+    /// `place_bridges(...)`.
+    Bridges { options: BridgeOptions },
+    /// `variant(...)`.
+    Variant { variant: Variant },
+    /// `grade_roads(...)`.
+    Traffic { options: TrafficOptions },
+    /// `subdivide(...)`.
+    Subdivide { times: usize, roughness: f64 },
+    /// `decorate(...)`.
+    Decorate { decorations: Vec<Decoration> },
+    /// `hillshade(...)`.
+    Hillshade { azimuth: f64, altitude: f64 },
+    /// `drainage_basins()`.
+    Basins,
+    /// `light(...)`.
+    Light { options: LightOptions },
+    /// `spawn_if(...)`, and whether its predicate held. The predicate is a closure,
+    /// so replays stop here instead of running the stages after it regardless of
+    /// the map.
+    SpawnIf { held: bool },
+    /// A stage restricted by `with_mask(...)`. The mask is a closure, so replays
+    /// stop here instead of running the stage over the whole map.
+    Masked { inner: Box<Stage> },
+    /// A stage driven by a closure, such as `spawn_perlin(...)` or `apply(...)`.
+    /// Closures can't be recorded, so only the name of the stage is kept.
+    Custom { name: String },
+}
+
+impl Stage {
+    /// Name of the stage, matching the method that ran it without its prefix.
+    pub fn name(&self) -> &str {
+        match self {
+            Stage::PerlinBands { .. } => "perlin_bands",
+            Stage::Rooms { .. } => "rooms",
+            Stage::Line { .. } => "line",
+            Stage::Rect { filled: false, .. } => "rect",
+            Stage::Rect { filled: true, .. } => "filled_rect",
+            Stage::Circle { filled: false, .. } => "circle",
+            Stage::Circle { filled: true, .. } => "filled_circle",
+            Stage::ReplaceWeighted { .. } => "replace_weighted",
+            Stage::FillWeighted { .. } => "fill_weighted",
+            Stage::Rewrite { .. } => "rewrite",
+            Stage::Quota { .. } => "quota",
+            Stage::Loot { .. } => "loot",
+            Stage::Exit { .. } => "exit",
+            Stage::Traps { .. } => "traps",
+            Stage::Villages { .. } => "villages",
+            Stage::Clusters { .. } => "clusters",
+            Stage::Patrols { .. } => "patrols",
+            Stage::DepthTiers { .. } => "depth_tiers",
+            Stage::FlowField { .. } => "flow_field",
+            Stage::Faults { .. } => "faults",
+            Stage::Deposition { .. } => "deposition",
+            Stage::Settle { .. } => "settle",
+            Stage::FlowWater { .. } => "flow_water",
+            Stage::Classify { .. } => "classify",
+            Stage::Synthesize { .. } => "synthesize",
+            Stage::Mission { .. } => "mission",
+            Stage::CollapseTiles { .. } => "collapse_tiles",
+            Stage::ClassifyBanded { .. } => "classify_banded",
+            Stage::Braid { .. } => "braid",
+            Stage::Rivers { .. } => "rivers",
+            Stage::Bridges { .. } => "bridges",
+            Stage::Variant { .. } => "variant",
+            Stage::Traffic { .. } => "traffic",
+            Stage::Subdivide { .. } => "subdivide",
+            Stage::Decorate { .. } => "decorate",
+            Stage::Hillshade { .. } => "hillshade",
+            Stage::Basins => "basins",
+            Stage::Light { .. } => "light",
+            Stage::SpawnIf { .. } => "spawn_if",
+            Stage::Masked { .. } => "masked",
+            Stage::Custom { name } => name,
+        }
+    }
+    /// Parameters of the stage as `(name, value)` pairs, for displaying them in tools.
+    pub fn parameters(&self) -> Vec<(&'static str, String)> {
+        match self {
+            Stage::PerlinBands { options, bands } => {
+                let mut parameters = noise_parameters(options);
+                parameters.push(("bands", format!("{:?}", bands)));
+                parameters
+            }
+            Stage::Rooms {
+                number,
+                rooms,
+                min_size,
+                max_size,
+            } => vec![
+                ("number", number.to_string()),
+                ("rooms", rooms.to_string()),
+                ("min_size", format!("{:?}", min_size)),
+                ("max_size", format!("{:?}", max_size)),
+            ],
+            Stage::Line { from, to, value } => vec![
+                ("from", format!("{:?}", from)),
+                ("to", format!("{:?}", to)),
+                ("value", value.to_string()),
+            ],
+            Stage::Rect {
+                x,
+                y,
+                width,
+                height,
+                value,
+                ..
+            } => vec![
+                ("x", x.to_string()),
+                ("y", y.to_string()),
+                ("width", width.to_string()),
+                ("height", height.to_string()),
+                ("value", value.to_string()),
+            ],
+            Stage::Circle {
+                center, radius, value, ..
+            } => vec![
+                ("center", format!("{:?}", center)),
+                ("radius", radius.to_string()),
+                ("value", value.to_string()),
+            ],
+            Stage::ReplaceWeighted { from, choices } => vec![
+                ("from", from.to_string()),
+                ("choices", format!("{:?}", choices)),
+            ],
+            Stage::FillWeighted { choices } => vec![("choices", format!("{:?}", choices))],
+            Stage::Rewrite { rules, steps } => vec![
+                ("rules", rules.len().to_string()),
+                ("steps", steps.to_string()),
+            ],
+            Stage::Quota {
+                value,
+                min_fraction,
+                strategy,
+            } => vec![
+                ("value", value.to_string()),
+                ("min_fraction", min_fraction.to_string()),
+                ("strategy", format!("{:?}", strategy)),
+            ],
+            Stage::Loot {
+                start,
+                floor,
+                loot,
+                count,
+            } => vec![
+                ("start", format!("{:?}", start)),
+                ("floor", floor.to_string()),
+                ("loot", loot.to_string()),
+                ("count", count.to_string()),
+            ],
+            Stage::Exit { start, constraints } => vec![
+                ("start", format!("{:?}", start)),
+                ("floor", constraints.floor.to_string()),
+                ("exit", constraints.exit.to_string()),
+                ("min_steps", constraints.min_steps.to_string()),
+            ],
+            Stage::Traps { start, exit, options } => vec![
+                ("start", format!("{:?}", start)),
+                ("exit", format!("{:?}", exit)),
+                ("floor", options.floor.to_string()),
+                ("trap", options.trap.to_string()),
+                ("chance", options.chance.to_string()),
+                ("on_path_chance", options.on_path_chance.to_string()),
+            ],
+            Stage::Villages { count, options } => vec![
+                ("count", count.to_string()),
+                ("ground", options.ground.to_string()),
+                ("building", options.building.to_string()),
+                ("well", options.well.to_string()),
+                ("road", options.road.to_string()),
+                ("buildings", options.buildings.to_string()),
+                ("radius", options.radius.to_string()),
+            ],
+            Stage::Clusters {
+                value,
+                clusters,
+                cluster_size,
+                spread,
+            } => vec![
+                ("value", value.to_string()),
+                ("clusters", clusters.to_string()),
+                ("cluster_size", cluster_size.to_string()),
+                ("spread", spread.to_string()),
+            ],
+            Stage::Patrols { count, length, floor } => vec![
+                ("count", count.to_string()),
+                ("length", length.to_string()),
+                ("floor", floor.to_string()),
+            ],
+            Stage::DepthTiers { entrance, floor, tiers } => vec![
+                ("entrance", format!("{:?}", entrance)),
+                ("floor", floor.to_string()),
+                ("tiers", tiers.to_string()),
+            ],
+            Stage::FlowField { options } => noise_parameters(options),
+            Stage::Faults { iterations } => vec![("iterations", iterations.to_string())],
+            Stage::Deposition { particles, options } => vec![
+                ("particles", particles.to_string()),
+                ("vents", options.vents.to_string()),
+                ("spread", options.spread.to_string()),
+                ("roughness", options.roughness.to_string()),
+            ],
+            Stage::Settle {
+                material,
+                empty,
+                liquid,
+                steps,
+            } => vec![
+                ("material", material.to_string()),
+                ("empty", empty.to_string()),
+                ("liquid", liquid.to_string()),
+                ("steps", steps.to_string()),
+            ],
+            Stage::FlowWater { water, ground, steps } => vec![
+                ("water", water.to_string()),
+                ("ground", ground.to_string()),
+                ("steps", steps.to_string()),
+            ],
+            Stage::Classify { field, bands } => vec![("field", field.clone()), ("bands", format!("{:?}", bands))],
+            Stage::Synthesize { example } => vec![("example", format!("{:?}", example))],
+            Stage::Mission { graph, options } => vec![
+                ("graph", format!("{:?}", graph)),
+                ("options", format!("{:?}", options)),
+            ],
+            Stage::CollapseTiles { tileset } => vec![("tileset", format!("{:?}", tileset))],
+            Stage::ClassifyBanded { field, bands } => {
+                vec![("field", field.clone()), ("bands", format!("{:?}", bands))]
+            }
+            Stage::Braid { floor, factor } => vec![("floor", floor.to_string()), ("factor", factor.to_string())],
+            Stage::Rivers { options } => vec![("options", format!("{:?}", options))],
+            Stage::Bridges { options } => vec![("options", format!("{:?}", options))],
+            Stage::Variant { variant } => vec![("variant", format!("{:?}", variant))],
+            Stage::Traffic { options } => vec![("options", format!("{:?}", options))],
+            Stage::Subdivide { times, roughness } => {
+                vec![("times", times.to_string()), ("roughness", roughness.to_string())]
+            }
+            Stage::Decorate { decorations } => vec![("decorations", format!("{:?}", decorations))],
+            Stage::Hillshade { azimuth, altitude } => {
+                vec![("azimuth", azimuth.to_string()), ("altitude", altitude.to_string())]
+            }
+            Stage::Light { options } => vec![("options", format!("{:?}", options))],
+            Stage::SpawnIf { held } => vec![("held", held.to_string())],
+            Stage::Masked { inner } => vec![("inner", inner.to_string())],
+            Stage::Basins | Stage::Custom { .. } => vec![],
+        }
+    }
+}
+
+// every noise option as `(name, value)` pairs
+fn noise_parameters(options: &NoiseOptions) -> Vec<(&'static str, String)> {
+    vec![
+        ("frequency", options.frequency.to_string()),
+        ("redistribution", options.redistribution.to_string()),
+        ("octaves", options.octaves.to_string()),
+        ("normalization", format!("{:?}", options.normalization)),
+        ("sampling", format!("{:?}", options.sampling)),
+        ("normalize_octaves", options.normalize_octaves.to_string()),
+        ("slope", options.slope.to_string()),
+        ("warp", options.warp.to_string()),
+    ]
+}
+
+/// Formats the stage as `name(parameter: value, ...)`.
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let parameters: Vec<String> = self
+            .parameters()
+            .iter()
+            .map(|(name, value)| format!("{}: {}", name, value))
+            .collect();
+        write!(f, "{}({})", self.name(), parameters.join(", "))
+    }
+}
+
+/// A custom generation stage, which composes with the built-in ones through
+/// [Generator::spawn_with](struct.Generator.html#method.spawn_with).
+///
+/// ```rust
+/// use procedural_generation::*;
+/// use rand::{rngs::StdRng, Rng, SeedableRng};
+///
+/// struct Volcano {
+///     lava: usize,
+/// }
+///
+/// impl Spawner for Volcano {
+///     fn spawn(&self, generator: &mut Generator) {
+///         let mut rng = StdRng::seed_from_u64(generator.stage_seed(self.name()));
+///         let x = rng.gen_range(0, generator.width);
+///         let y = rng.gen_range(0, generator.height);
+///         generator.set(x, y, self.lava);
+///     }
+///     fn name(&self) -> &str {
+///         "volcano"
+///     }
+/// }
+///
+/// fn main() {
+///     Generator::new()
+///         .with_size(40, 10)
+///         .spawn_with(&Volcano { lava: 3 })
+///         .show();
+/// }
+/// ```
+pub trait Spawner {
+    /// Modifies the map.
+    fn spawn(&self, generator: &mut Generator);
+    /// Name the stage is recorded under.
+    fn name(&self) -> &str {
+        "custom"
+    }
+}
+
+/// Everything needed to generate a map again: its size, seed and every stage
+/// that ran, in order. See [Generator::recipe](struct.Generator.html#method.recipe).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Recipe {
+    /// Width the map had when stages started, before any `subdivide(...)`.
+    pub width: usize,
+    pub height: usize,
+    pub seed: u64,
+    pub stages: Vec<Stage>,
+}
+
+impl Generator {
+    /// Returns the recipe for the current map. Stages are recorded from the last
+    /// call to `with_size`, set the seed before running any stages.
+    ///
+    /// With the `serde` feature enabled recipes can be serialized, so an exact map
+    /// can be stored in a tiny file and rebuilt with `Generator::from_recipe(...)`.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let size = Size::new((4, 4), (10, 10));
+    ///     let generator = Generator::new()
+    ///         .with_size(30, 20)
+    ///         .spawn_perlin_bands(&[(0.66, 2), (0.33, 1)])
+    ///         .spawn_rooms(3, 5, &size);
+    ///     let copy = Generator::from_recipe(&generator.recipe()).unwrap();
+    ///     assert_eq!(generator.map, copy.map);
+    /// }
+    /// ```
+    pub fn recipe(&self) -> Recipe {
+        // the size the map had before `subdivide(...)` grew it
+        let doublings: usize = self
+            .stages
+            .iter()
+            .map(|stage| match stage {
+                Stage::Subdivide { times, .. } => *times,
+                _ => 0,
+            })
+            .sum();
+        Recipe {
+            width: self.width >> doublings,
+            height: self.height >> doublings,
+            seed: self.seed,
+            stages: self.stages.clone(),
+        }
+    }
+    /// Returns the stages that produced the current map, in the order they ran.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let generator = Generator::new()
+    ///         .with_size(40, 10)
+    ///         .spawn_perlin_bands(&[(0.5, 1)])
+    ///         .draw_line((0, 0), (39, 9), 2);
+    ///     for stage in generator.stages() {
+    ///         println!("{}", stage);
+    ///     }
+    /// }
+    /// ```
+    pub fn stages(&self) -> &[Stage] {
+        &self.stages
+    }
+    /// Generates the map described by `recipe`. Fails with
+    /// [GenerationError::UnreplayableStage](enum.GenerationError.html) if the recipe
+    /// holds a stage driven by a closure, see [Registry::replay](struct.Registry.html#method.replay)
+    /// for replaying custom stages.
+    pub fn from_recipe(recipe: &Recipe) -> Result<Self, GenerationError> {
+        Generator::new()
+            .with_size(recipe.width, recipe.height)
+            .with_seed(recipe.seed)
+            .replay_stages(recipe, None)
+    }
+    // runs the stages of `recipe` on this generator, looking up custom stages in `registry`
+    pub(crate) fn replay_stages(self, recipe: &Recipe, registry: Option<&Registry>) -> Result<Self, GenerationError> {
+        recipe
+            .stages
+            .iter()
+            .try_fold(self, |generator, stage| generator.replay(stage, registry))
+    }
+    /// Keeps regenerating the map until `accept` returns true for it, for levels that
+    /// must pass checks like "start and exit are far apart". The current map is checked
+    /// first, then its stages are replayed with fresh seeds derived from the original
+    /// one, for up to `max_attempts` maps in total. The accepted map is returned, and
+    /// `seed()` gives the seed that produced it.
+    ///
+    /// Fails with [GenerationError::AttemptsExhausted](enum.GenerationError.html) if
+    /// no map is accepted, and with `UnreplayableStage` if a stage is driven by a closure.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let generator = Generator::new()
+    ///         .with_size(30, 20)
+    ///         .spawn_perlin_bands(&[(0.5, 1)])
+    ///         .generate_until(|generator| generator.positions_of(1).len() > 200, 20);
+    ///     match generator {
+    ///         Ok(generator) => println!("accepted seed {}", generator.seed()),
+    ///         Err(error) => println!("{}", error),
+    ///     }
+    /// }
+    /// ```
+    pub fn generate_until<F: Fn(&Self) -> bool>(self, accept: F, max_attempts: usize) -> Result<Self, GenerationError> {
+        let recipe = self.recipe();
+        let mut generator = self;
+        for attempt in 0..max_attempts {
+            if attempt > 0 {
+                if generator.is_out_of_time() {
+                    return Err(GenerationError::TimedOut);
+                }
+                let seed = derive_seed(recipe.seed, "generate_until", attempt as u64);
+                generator = generator
+                    .with_size(recipe.width, recipe.height)
+                    .with_seed(seed)
+                    .replay_stages(&recipe, None)?;
+            }
+            generator = generator.try_finish()?;
+            if accept(&generator) {
+                return Ok(generator);
+            }
+        }
+        Err(GenerationError::AttemptsExhausted(max_attempts))
+    }
+    /// Generates `n` candidate maps in parallel and returns the one `score` rates
+    /// highest, for picking good maps automatically. The current map is the first
+    /// candidate, the others replay its stages with seeds derived from the original
+    /// one. `seed()` on the winner gives the seed that produced it.
+    ///
+    /// Candidates are built by fresh generators, so a progress callback, cancel
+    /// token or rng set on this generator only applies to the current map. Fails
+    /// with [GenerationError::UnreplayableStage](enum.GenerationError.html) if a
+    /// stage is driven by a closure.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     // the map with the most land out of 16
+    ///     let best = Generator::new()
+    ///         .with_size(30, 20)
+    ///         .spawn_perlin_bands(&[(0.5, 1)])
+    ///         .explore_seeds(16, |generator| generator.positions_of(1).len() as f64)
+    ///         .unwrap();
+    ///     println!("seed {}", best.seed());
+    /// }
+    /// ```
+    pub fn explore_seeds<F: Fn(&Self) -> f64 + Sync>(self, n: usize, score: F) -> Result<Self, GenerationError> {
+        let recipe = self.recipe();
+        let options = self.noise_options.clone();
+        let candidates = (1..n)
+            .into_par_iter()
+            .map(|candidate| {
+                let generator = Generator::new()
+                    .with_size(recipe.width, recipe.height)
+                    .with_options(options.clone())
+                    .with_seed(derive_seed(recipe.seed, "explore_seeds", candidate as u64))
+                    .replay_stages(&recipe, None)?;
+                Ok((score(&generator), generator))
+            })
+            .collect::<Result<Vec<_>, GenerationError>>()?;
+        let current = (score(&self), self);
+        let (_, best) = candidates
+            .into_iter()
+            .fold(current, |best, candidate| if candidate.0 > best.0 { candidate } else { best });
+        Ok(best)
+    }
+    /// Runs a custom [Spawner](trait.Spawner.html) as a stage. Like the built-in
+    /// stages it honors `with_mask`, and it is recorded under `spawner.name()`.
+    pub fn spawn_with<S: Spawner + ?Sized>(self, spawner: &S) -> Self {
+        let stage = Stage::Custom {
+            name: spawner.name().to_string(),
+        };
+        self.stage(stage, |mut generator| {
+            spawner.spawn(&mut generator);
+            generator
+        })
+    }
+    /// Runs `stage` only when `predicate` holds for the current map, otherwise the
+    /// generator is returned untouched. The predicate can't be recorded, so maps
+    /// built with `spawn_if` can't be replayed, which includes `generate_until` and
+    /// `explore_seeds`.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     Generator::new()
+    ///         .with_size(40, 10)
+    ///         .spawn_perlin_bands(&[(0.4, 1)])
+    ///         // only build a harbor if at least 20% of the map is water
+    ///         .spawn_if(
+    ///             |generator| generator.positions_of(0).len() * 5 >= generator.map.len(),
+    ///             |generator| generator.fill_rect(18, 3, 4, 4, 2),
+    ///         )
+    ///         .show();
+    /// }
+    /// ```
+    pub fn spawn_if<P: FnOnce(&Self) -> bool, F: FnOnce(Self) -> Self>(mut self, predicate: P, stage: F) -> Self {
+        let held = predicate(&self);
+        if !self.in_stage && !self.is_cancelled() {
+            self.stages.push(Stage::SpawnIf { held });
+        }
+        if held {
+            stage(self)
+        } else {
+            self
+        }
+    }
+    fn replay(self, stage: &Stage, registry: Option<&Registry>) -> Result<Self, GenerationError> {
+        Ok(match stage.clone() {
+            Stage::PerlinBands { options, bands } => self.with_options(options).spawn_perlin_bands(&bands),
+            Stage::FlowField { options } => self.with_options(options).spawn_flow_field(),
+            Stage::Faults { iterations } => self.spawn_faults(iterations),
+            Stage::Deposition { particles, options } => self.spawn_deposition(particles, options),
+            Stage::Settle {
+                material,
+                empty,
+                liquid,
+                steps,
+            } => self.settle(material, empty, liquid, steps),
+            Stage::FlowWater { water, ground, steps } => self.flow_water(water, ground, steps),
+            Stage::Classify { field, bands } => self.classify(&field, &bands),
+            Stage::Synthesize { example } => self.synthesize_rows(example),
+            Stage::Mission { graph, options } => self.try_embed_mission(&graph, options)?,
+            Stage::CollapseTiles { tileset } => self.try_collapse_tiles(&tileset)?,
+            Stage::Braid { floor, factor } => self.braid(floor, factor),
+            Stage::Rivers { options } => self.carve_rivers(options),
+            Stage::Bridges { options } => self.place_bridges(options),
+            Stage::Variant { variant } => self.variant(&variant),
+            Stage::Traffic { options } => self.grade_roads(options),
+            Stage::Subdivide { times, roughness } => self.subdivide(times, roughness),
+            Stage::Decorate { decorations } => self.decorate(&decorations),
+            Stage::Hillshade { azimuth, altitude } => self.hillshade(azimuth, altitude),
+            Stage::Basins => self.drainage_basins(),
+            Stage::Light { options } => self.light(options),
+            Stage::ClassifyBanded { field, bands } => {
+                let bands: Vec<_> = bands.iter().map(|(range, choices)| (range.clone(), &choices[..])).collect();
+                self.classify_banded(&field, &bands)
+            }
+            Stage::Rooms {
+                number,
+                rooms,
+                min_size,
+                max_size,
+            } => self.spawn_rooms(number, rooms, &Size::new(min_size, max_size)),
+            Stage::Line { from, to, value } => self.draw_line(from, to, value),
+            Stage::Rect {
+                x,
+                y,
+                width,
+                height,
+                value,
+                filled,
+            } => {
+                if filled {
+                    self.fill_rect(x, y, width, height, value)
+                } else {
+                    self.draw_rect(x, y, width, height, value)
+                }
+            }
+            Stage::Circle {
+                center,
+                radius,
+                value,
+                filled,
+            } => {
+                if filled {
+                    self.fill_circle(center, radius, value)
+                } else {
+                    self.draw_circle(center, radius, value)
+                }
+            }
+            Stage::ReplaceWeighted { from, choices } => self.replace_weighted(from, &choices),
+            Stage::FillWeighted { choices } => self.fill_weighted(&choices),
+            Stage::Rewrite { rules, steps } => self.rewrite(&rules, steps),
+            Stage::Quota {
+                value,
+                min_fraction,
+                strategy,
+            } => self.try_ensure_quota(value, min_fraction, strategy)?,
+            Stage::Loot {
+                start,
+                floor,
+                loot,
+                count,
+            } => self.scatter_loot(start, floor, loot, count),
+            Stage::Exit { start, constraints } => self.try_place_exit(start, constraints)?,
+            Stage::Traps { start, exit, options } => self.place_traps(start, exit, options),
+            Stage::Villages { count, options } => self.spawn_villages(count, options),
+            Stage::Clusters {
+                value,
+                clusters,
+                cluster_size,
+                spread,
+            } => self.spawn_clusters(value, clusters, cluster_size, spread),
+            Stage::Patrols { count, length, floor } => self.generate_patrols(count, length, floor),
+            Stage::DepthTiers { entrance, floor, tiers } => self.classify_depth(entrance, floor, tiers),
+            Stage::SpawnIf { .. } => return Err(GenerationError::UnreplayableStage("spawn_if".into())),
+            Stage::Masked { .. } => return Err(GenerationError::UnreplayableStage("mask".into())),
+            Stage::Custom { name } => match registry {
+                Some(registry) if registry.names().any(|registered| registered == name) => {
+                    return self.run(registry, &name)
+                }
+                _ => return Err(GenerationError::UnreplayableStage(name)),
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay_recipe() {
+        let size = Size::new((2, 2), (6, 6));
+        let generator = Generator::new()
+            .with_size(20, 20)
+            .spawn_perlin_bands(&[(0.5, 1)])
+            .spawn_rooms(2, 4, &size)
+            .spawn_rooms(3, 4, &size)
+            .fill_circle((10, 10), 3, 4)
+            .draw_rect(0, 0, 20, 20, 5);
+        let recipe = generator.recipe();
+        assert_eq!(recipe.stages.len(), 5);
+        assert_eq!(generator.stages()[3].to_string(), "filled_circle(center: (10, 10), radius: 3, value: 4)");
+        assert_eq!(
+            generator.stages()[0].to_string(),
+            "perlin_bands(frequency: 1, redistribution: 1, octaves: 1, normalization: Legacy, sampling: Width, \
+             normalize_octaves: false, slope: false, warp: 0, bands: [(0.5, 1)])"
+        );
+        assert_eq!(Generator::from_recipe(&recipe).unwrap().map, generator.map);
+
+        let recipe = generator.apply(|_, _, value| value).recipe();
+        assert_eq!(
+            Generator::from_recipe(&recipe).unwrap_err(),
+            GenerationError::UnreplayableStage("apply".into())
+        );
+    }
+
+    #[test]
+    fn generate_until() {
+        let generator = Generator::new()
+            .with_size(20, 20)
+            .with_seed(3)
+            .spawn_perlin_bands(&[(0.5, 1)]);
+        let first = generator.map.clone();
+        let accepted = generator
+            .generate_until(|generator| generator.map != first, 5)
+            .unwrap();
+        assert_ne!(accepted.seed(), 3);
+        let copy = Generator::from_recipe(&accepted.recipe()).unwrap();
+        assert_eq!(copy.map, accepted.map);
+
+        let error = Generator::new().with_size(5, 5).generate_until(|_| false, 3);
+        assert_eq!(error.unwrap_err(), GenerationError::AttemptsExhausted(3));
+    }
+
+    #[test]
+    fn spawn_if() {
+        let harbor = |generator: Generator| {
+            generator.spawn_if(
+                |generator| generator.positions_of(0).is_empty(),
+                |generator| generator.fill_rect(0, 0, 2, 2, 2),
+            )
+        };
+        let generator = harbor(Generator::new().with_size(5, 5).fill_rect(0, 0, 5, 5, 1));
+        assert_eq!(generator.positions_of(2).len(), 4);
+        assert_eq!(generator.stages()[1], Stage::SpawnIf { held: true });
+        let error = generator.generate_until(|_| false, 3).unwrap_err();
+        assert_eq!(error, GenerationError::UnreplayableStage("spawn_if".into()));
+        assert_eq!(harbor(Generator::new().with_size(5, 5)).stages(), &[Stage::SpawnIf { held: false }]);
+    }
+
+    #[test]
+    fn explore_seeds() {
+        let spawn = |seed| Generator::new().with_size(20, 20).with_seed(seed).spawn_perlin_bands(&[(0.5, 1)]);
+        let score = |generator: &Generator| generator.positions_of(1).len() as f64;
+        let best = spawn(7).explore_seeds(8, score).unwrap();
+        let highest = (1..8)
+            .map(|candidate| score(&spawn(derive_seed(7, "explore_seeds", candidate))))
+            .fold(score(&spawn(7)), f64::max);
+        assert_eq!(score(&best), highest);
+        assert_eq!(Generator::from_recipe(&best.recipe()).unwrap().map, best.map);
+    }
+}
@@ -0,0 +1,863 @@
+//! Placing points of interest such as spawns, loot and landmarks.
+
+use crate::{derive_seed, Encounter, GenerationError, Generator, Stage, Village};
+use rand::prelude::*;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use smart_default::*;
+
+/// Where [Generator::place_exit](struct.Generator.html#method.place_exit) may put the exit.
+#[derive(Debug, Clone, PartialEq, SmartDefault)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ExitConstraints {
+    /// Value of cells that can be walked on. Default is 1.
+    #[default = 1]
+    pub floor: usize,
+    /// Value the exit is marked with. Default is 2.
+    #[default = 2]
+    pub exit: usize,
+    /// Least amount of steps between the start and the exit. Default is 0.
+    pub min_steps: u32,
+}
+
+/// How [Generator::place_traps](struct.Generator.html#method.place_traps) spreads traps.
+#[derive(Debug, Clone, PartialEq, SmartDefault)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TrapOptions {
+    /// Value of cells that can be walked on. Default is 1.
+    #[default = 1]
+    pub floor: usize,
+    /// Value traps are marked with. Default is 3.
+    #[default = 3]
+    pub trap: usize,
+    /// Chance of a floor cell off the critical path becoming a trap. Default is 0.05.
+    #[default = 0.05]
+    pub chance: f64,
+    /// Chance of a floor cell on the critical path becoming a trap. Default is 0.
+    pub on_path_chance: f64,
+}
+
+/// What [Generator::spawn_villages](struct.Generator.html#method.spawn_villages) builds.
+#[derive(Debug, Clone, PartialEq, SmartDefault)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct VillageOptions {
+    /// Value of the terrain villages can be built on. Default is 1.
+    #[default = 1]
+    pub ground: usize,
+    /// Value of building cells. Default is 2.
+    #[default = 2]
+    pub building: usize,
+    /// Value of the well at the center of each village. Default is 3.
+    #[default = 3]
+    pub well: usize,
+    /// Value of the roads between villages. Default is 4.
+    #[default = 4]
+    pub road: usize,
+    /// Buildings per village, fewer are built when there isn't enough ground. Default is 6.
+    #[default = 6]
+    pub buildings: usize,
+    /// How far buildings may be from the well. Default is 5.
+    #[default = 5]
+    pub radius: usize,
+    /// Extra cost of a road step per unit of `"elevation"` it climbs or descends,
+    /// so roads follow the contours around hills instead of going straight over
+    /// them. With elevation between 0 and 1, around 100 makes roads go well out of
+    /// their way. Ignored without an elevation field. Default is 0.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub slope_cost: f64,
+    /// Steepest change in `"elevation"` a road step may make, if any. Roads that
+    /// have to climb steeper slopes zigzag up them in switchbacks. Villages that
+    /// can't be reached without a steeper step are joined ignoring the limit.
+    /// Default is None.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub max_grade: Option<f64>,
+}
+
+/// How [Generator::grade_roads](struct.Generator.html#method.grade_roads) sorts
+/// roads into highways and side roads.
+#[derive(Debug, Clone, PartialEq, SmartDefault)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TrafficOptions {
+    /// Value of the roads. Default is 4.
+    #[default = 4]
+    pub road: usize,
+    /// Value of the wells at the center of villages. Default is 3.
+    #[default = 3]
+    pub well: usize,
+    /// Value busy roads are upgraded to. Default is 7.
+    #[default = 7]
+    pub highway: usize,
+    /// Share of the traffic of the busiest road cell a road cell needs to become a
+    /// highway. Default is 0.5.
+    #[default = 0.5]
+    pub share: f64,
+}
+
+impl Generator {
+    /// Picks `n` cells for which `valid(x, y, value)` returns true, at least
+    /// `min_distance` cells apart from each other, for player or enemy spawns. The
+    /// first cell is random, every following one is the valid cell farthest from
+    /// the ones picked so far. Returns `None` when `n` cells that far apart can't
+    /// be found. The result only depends on the seed and the map.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let generator = Generator::new()
+    ///         .with_size(40, 20)
+    ///         .spawn_perlin_bands(&[(0.5, 1)]);
+    ///     // four players on land, at least 10 cells apart
+    ///     let spawns = generator.place_spawn_points(4, 10., |_, _, value| value == 1);
+    ///     println!("{:?}", spawns);
+    /// }
+    /// ```
+    pub fn place_spawn_points<F: Fn(usize, usize, usize) -> bool>(
+        &self,
+        n: usize,
+        min_distance: f64,
+        valid: F,
+    ) -> Option<Vec<(usize, usize)>> {
+        let candidates: Vec<(usize, usize)> = self
+            .iter_cells()
+            .filter(|&(x, y, value)| valid(x, y, value))
+            .map(|(x, y, _)| (x, y))
+            .collect();
+        let mut rng: StdRng = SeedableRng::seed_from_u64(derive_seed(self.seed, "spawn_points", 0));
+        let points = maximin(&candidates, n, min_distance, &mut rng);
+        if points.len() == n {
+            Some(points)
+        } else {
+            None
+        }
+    }
+    /// Lays out `count` patrol routes over cells holding `floor`, for AI guards to
+    /// follow out of the box. Each route is a closed loop of about `length` steps
+    /// through three waypoints: a start, spread across the map like landmarks, and
+    /// two cells about a third of the loop away from it and from each other. Routes
+    /// are listed in `artifacts().patrols`, starting and ending at the same cell.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let size = Size::new((6, 6), (12, 12));
+    ///     let generator = Generator::new()
+    ///         .with_size(60, 30)
+    ///         .spawn_rooms(1, 4, &size)
+    ///         .generate_patrols(2, 20, 1);
+    ///     for patrol in &generator.artifacts().patrols {
+    ///         println!("{:?}", patrol);
+    ///     }
+    /// }
+    /// ```
+    pub fn generate_patrols(self, count: usize, length: usize, floor: usize) -> Self {
+        let stage = Stage::Patrols { count, length, floor };
+        self.stage(stage, |mut generator| {
+            let width = generator.width;
+            let walk = |value| if value == floor { Some(1) } else { None };
+            let starts = generator.place_landmarks(count, |value| value == floor);
+            let leg = (length / 3).max(1) as i64;
+            // reachable cell whose distances are closest to a leg, random among ties
+            let pick = |distances: &[&[u32]], rng: &mut dyn RngCore| {
+                let score = |pos: usize| -> Option<i64> {
+                    distances.iter().try_fold(0, |score, distances| match distances[pos] {
+                        u32::MAX | 0 => None,
+                        distance => Some(score + (distance as i64 - leg).abs()),
+                    })
+                };
+                let best = (0..distances[0].len()).filter_map(score).min()?;
+                let ties: Vec<usize> = (0..distances[0].len()).filter(|&pos| score(pos) == Some(best)).collect();
+                ties.choose(rng).copied()
+            };
+            let patrols = generator.with_stage_rng("patrols", |generator, rng| {
+                let mut patrols = vec![];
+                for start in starts {
+                    let from_start = generator.dijkstra_map(&[start], walk);
+                    let first = match pick(&[&from_start], rng) {
+                        Some(first) => first,
+                        None => continue,
+                    };
+                    let from_first = generator.dijkstra_map(&[(first % width, first / width)], walk);
+                    let second = match pick(&[&from_start, &from_first], rng) {
+                        Some(second) => second,
+                        None => continue,
+                    };
+                    let waypoints = [start, (first % width, first / width), (second % width, second / width), start];
+                    let mut patrol = vec![start];
+                    for pair in waypoints.windows(2) {
+                        let leg = generator.path(pair[0], pair[1], walk).unwrap_or_default();
+                        patrol.extend(leg.into_iter().skip(1));
+                    }
+                    patrols.push(patrol);
+                }
+                patrols
+            });
+            generator.artifacts.patrols.extend(patrols);
+            generator
+        })
+    }
+    /// Picks up to `n` cells holding a `walkable` value, spread across the whole map
+    /// instead of clumped together like uniformly random picks often are, for quest
+    /// objectives and other landmarks. Uses the same farthest point selection as
+    /// `place_spawn_points(...)` without a minimum distance, so fewer cells are only
+    /// returned when fewer are walkable.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let generator = Generator::new()
+    ///         .with_size(60, 30)
+    ///         .spawn_perlin_bands(&[(0.4, 1)]);
+    ///     for (x, y) in generator.place_landmarks(5, |value| value == 1) {
+    ///         println!("quest objective at ({}, {})", x, y);
+    ///     }
+    /// }
+    /// ```
+    pub fn place_landmarks<F: Fn(usize) -> bool>(&self, n: usize, walkable: F) -> Vec<(usize, usize)> {
+        let candidates: Vec<(usize, usize)> = self
+            .iter_cells()
+            .filter(|&(_, _, value)| walkable(value))
+            .map(|(x, y, _)| (x, y))
+            .collect();
+        let mut rng: StdRng = SeedableRng::seed_from_u64(derive_seed(self.seed, "landmarks", 0));
+        maximin(&candidates, n, 0., &mut rng)
+    }
+    /// Turns `count` floor cells reachable from `start` into `loot`, picking cells
+    /// farther away more often, so risk and reward grow with depth. The chance of a
+    /// cell is proportional to its walking distance from `start` through cells holding
+    /// `floor`. Fewer cells are turned when not enough floor is reachable.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let size = Size::new((4, 4), (10, 10));
+    ///     let generator = Generator::new()
+    ///         .with_size(40, 20)
+    ///         .spawn_rooms(1, 5, &size);
+    ///     if let Some(start) = generator.random_position_where(|_, _, value| value == 1) {
+    ///         generator.scatter_loot(start, 1, 2, 5).show();
+    ///     }
+    /// }
+    /// ```
+    pub fn scatter_loot(self, start: (usize, usize), floor: usize, loot: usize, count: usize) -> Self {
+        let stage = Stage::Loot {
+            start,
+            floor,
+            loot,
+            count,
+        };
+        self.stage(stage, |mut generator| {
+            let distances = generator.dijkstra_map(&[start], |value| if value == floor { Some(1) } else { None });
+            generator.with_stage_rng("loot", |generator, rng| {
+                // weighted sampling without replacement: the cells with the largest
+                // random^(1 / weight) win
+                let mut keys: Vec<(f64, usize)> = distances
+                    .iter()
+                    .enumerate()
+                    .filter(|&(pos, &distance)| distance != u32::MAX && distance > 0 && generator.map[pos] == floor)
+                    .map(|(pos, &distance)| (rng.gen::<f64>().powf(1. / distance as f64), pos))
+                    .collect();
+                keys.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+                for &(_, pos) in keys.iter().take(count) {
+                    generator.map[pos] = loot;
+                }
+            });
+            generator
+        })
+    }
+    /// Places enemy encounters in every room reachable from `entrance`, where a room
+    /// is a region of connected `floor` cells once narrow passages are taken out, see
+    /// `chokepoints(...)`. `budget(distance, size)` receives the walking
+    /// distance from the entrance to the region and the amount of cells in it, and
+    /// returns the difficulty tier of each encounter to place there, so deeper and
+    /// larger rooms can get more and harder fights. Encounters end up on distinct
+    /// random cells of their region, and are listed in `artifacts().encounters`.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let size = Size::new((4, 4), (10, 10));
+    ///     let generator = Generator::new()
+    ///         .with_size(40, 20)
+    ///         .spawn_rooms(1, 5, &size);
+    ///     let entrance = generator.random_position_where(|_, _, value| value == 1).unwrap_or((0, 0));
+    ///     // one encounter per 20 cells, a tier harder every 10 steps
+    ///     let generator = generator.place_encounters(entrance, 1, |distance, size| {
+    ///         vec![distance as usize / 10; size / 20]
+    ///     });
+    ///     for encounter in &generator.artifacts().encounters {
+    ///         println!("tier {} at {:?}", encounter.tier, encounter.position);
+    ///     }
+    /// }
+    /// ```
+    pub fn place_encounters<F: Fn(u32, usize) -> Vec<usize>>(self, entrance: (usize, usize), floor: usize, budget: F) -> Self {
+        self.stage(Stage::Custom { name: "encounters".into() }, |mut generator| {
+            let distances = generator.dijkstra_map(&[entrance], |value| if value == floor { Some(1) } else { None });
+            // rooms are what is left of the floor once corridors and doorways are removed
+            let mut rooms = Generator::new().with_size(generator.width, generator.height);
+            rooms.map = generator.map.iter().map(|&value| (value == floor) as usize).collect();
+            for (x, y) in generator.chokepoints(|value| value == floor) {
+                rooms.set(x, y, 0);
+            }
+            let regions = rooms.label_regions();
+            // cells and distance to the closest cell of every reachable room
+            let mut cells = vec![vec![]; regions.count()];
+            let mut region_distance = vec![u32::MAX; regions.count()];
+            for (pos, &label) in regions.labels.iter().enumerate() {
+                if regions.values[label] == 1 && distances[pos] != u32::MAX {
+                    cells[label].push((pos % generator.width, pos / generator.width));
+                    region_distance[label] = region_distance[label].min(distances[pos]);
+                }
+            }
+            generator.with_stage_rng("encounters", |generator, rng| {
+                for (cells, &distance) in cells.iter().zip(&region_distance) {
+                    if cells.is_empty() {
+                        continue;
+                    }
+                    let tiers = budget(distance, cells.len());
+                    let positions = cells.choose_multiple(rng, tiers.len());
+                    for (&position, tier) in positions.zip(tiers) {
+                        generator.artifacts.encounters.push(Encounter { position, tier });
+                    }
+                }
+            });
+            generator
+        })
+    }
+    /// Marks an exit that can always be reached from `start`, at least
+    /// `constraints.min_steps` steps away, so generated levels are never unwinnable.
+    /// The exit goes on a random floor cell that is reachable and far enough away.
+    /// If there is none, a corridor of floor is dug from the start to a cell far
+    /// enough away, going through existing floor where it can. The exit is listed in
+    /// `artifacts().exit`. Panics if no cell on the map is far enough away, see
+    /// `try_place_exit(...)`.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let size = Size::new((4, 4), (10, 10));
+    ///     let constraints = ExitConstraints { min_steps: 20, ..ExitConstraints::default() };
+    ///     let generator = Generator::new()
+    ///         .with_size(40, 20)
+    ///         .spawn_rooms(1, 5, &size)
+    ///         .fill_rect(1, 1, 2, 2, 1)
+    ///         .place_exit((1, 1), constraints);
+    ///     generator.show();
+    ///     println!("exit at {:?}", generator.artifacts().exit);
+    /// }
+    /// ```
+    pub fn place_exit(self, start: (usize, usize), constraints: ExitConstraints) -> Self {
+        match self.try_place_exit(start, constraints) {
+            Ok(generator) => generator,
+            Err(error) => panic!("{}", error),
+        }
+    }
+    /// Same as `place_exit(...)`, except it fails with
+    /// [GenerationError::OutOfBounds](enum.GenerationError.html) when `start` lies
+    /// outside the map, and with `ExitTooFar` when no cell is far enough away.
+    pub fn try_place_exit(self, start: (usize, usize), constraints: ExitConstraints) -> Result<Self, GenerationError> {
+        self.try_get(start.0, start.1)?;
+        let ExitConstraints { floor, exit, min_steps } = constraints;
+        let width = self.width;
+        let manhattan = |pos: usize| {
+            let (x, y) = (pos % width, pos / width);
+            (x.max(start.0) - x.min(start.0) + y.max(start.1) - y.min(start.1)) as u32
+        };
+        // no path can be shorter than the manhattan distance, so no cell beyond it
+        // means no exit is possible
+        if (0..self.map.len()).all(|pos| manhattan(pos) < min_steps.max(1)) {
+            return Err(GenerationError::ExitTooFar { min_steps });
+        }
+        let stage = Stage::Exit { start, constraints };
+        Ok(self.stage(stage, |mut generator| {
+            let distances = generator.dijkstra_map(&[start], |value| if value == floor { Some(1) } else { None });
+            let reachable: Vec<usize> = (0..generator.map.len())
+                .filter(|&pos| {
+                    generator.map[pos] == floor && distances[pos] != u32::MAX && distances[pos] >= min_steps.max(1)
+                })
+                .collect();
+            let position = generator.with_stage_rng("exit", |generator, rng| match reachable.choose(rng) {
+                Some(&pos) => pos,
+                None => {
+                    // dig towards a cell far enough away, floor cells cost less to go
+                    // through than cells that have to be dug out
+                    let targets: Vec<usize> = (0..generator.map.len())
+                        .filter(|&pos| manhattan(pos) >= min_steps.max(1))
+                        .collect();
+                    let target = *targets.choose(rng).unwrap();
+                    let corridor = generator
+                        .path(start, (target % width, target / width), |value| {
+                            Some(if value == floor { 1 } else { 4 })
+                        })
+                        .unwrap();
+                    for (x, y) in corridor {
+                        generator.set(x, y, floor);
+                    }
+                    target
+                }
+            });
+            generator.map[position] = exit;
+            generator.artifacts.exit = Some((position % width, position / width));
+            generator
+        }))
+    }
+    /// Turns floor cells into traps, but keeps them off the critical path: the
+    /// shortest walk from `start` to `exit`. Traps add flavor to side rooms and dead
+    /// ends without making damage unavoidable, unless `options.on_path_chance` asks
+    /// for some on the path as well. The start and exit cells never become traps,
+    /// and when the exit can't be reached every floor cell counts as off the path.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let constraints = ExitConstraints { min_steps: 20, ..ExitConstraints::default() };
+    ///     let generator = Generator::new()
+    ///         .with_size(40, 20)
+    ///         .fill_rect(1, 1, 38, 18, 1)
+    ///         .place_exit((1, 1), constraints);
+    ///     let exit = generator.artifacts().exit.unwrap();
+    ///     generator.place_traps((1, 1), exit, TrapOptions::default()).show();
+    /// }
+    /// ```
+    pub fn place_traps(self, start: (usize, usize), exit: (usize, usize), options: TrapOptions) -> Self {
+        let stage = Stage::Traps {
+            start,
+            exit,
+            options: options.clone(),
+        };
+        self.stage(stage, |mut generator| {
+            let TrapOptions {
+                floor,
+                trap,
+                chance,
+                on_path_chance,
+            } = options;
+            // the exit is usually marked with a value of its own
+            let exit_value = generator.get_checked(exit.0, exit.1);
+            let path = generator
+                .path(start, exit, |value| {
+                    if value == floor || Some(value) == exit_value {
+                        Some(1)
+                    } else {
+                        None
+                    }
+                })
+                .unwrap_or_default();
+            let mut on_path = vec![false; generator.map.len()];
+            for &(x, y) in &path {
+                on_path[x + y * generator.width] = true;
+            }
+            let width = generator.width;
+            generator.with_stage_rng("traps", |generator, rng| {
+                for (pos, value) in generator.map.iter_mut().enumerate() {
+                    let position = (pos % width, pos / width);
+                    if *value != floor || position == start || position == exit {
+                        continue;
+                    }
+                    let chance = if on_path[pos] { on_path_chance } else { chance };
+                    if rng.gen::<f64>() < chance {
+                        *value = trap;
+                    }
+                }
+            });
+            generator
+        })
+    }
+    /// Builds `count` villages on ground, each a well surrounded by 2x2 buildings,
+    /// and connects them with roads. Villages are spread out as far from each other
+    /// as the ground allows, roads follow the cheapest route and prefer going around
+    /// other terrain, which they cross only when they have to. Given an elevation
+    /// field, `slope_cost` and `max_grade` make roads follow contours and wind up
+    /// steep slopes. Every village is listed in `artifacts().villages`.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     Generator::new()
+    ///         .with_size(60, 30)
+    ///         .spawn_perlin_bands(&[(0.4, 1)])
+    ///         .spawn_villages(3, VillageOptions::default())
+    ///         .show();
+    /// }
+    /// ```
+    pub fn spawn_villages(self, count: usize, options: VillageOptions) -> Self {
+        let stage = Stage::Villages {
+            count,
+            options: options.clone(),
+        };
+        self.stage(stage, |mut generator| {
+            let VillageOptions {
+                ground,
+                building,
+                well,
+                road,
+                buildings,
+                radius,
+                slope_cost,
+                max_grade,
+            } = options;
+            let (width, height) = (generator.width, generator.height);
+            // wells need room around them for buildings
+            let candidates: Vec<(usize, usize)> = generator
+                .iter_cells()
+                .filter(|&(x, y, value)| {
+                    value == ground && x >= radius && y >= radius && x + radius < width && y + radius < height
+                })
+                .map(|(x, y, _)| (x, y))
+                .collect();
+            let villages = generator.with_stage_rng("villages", |generator, rng| {
+                let centers = maximin(&candidates, count, (radius * 2 + 2) as f64, rng);
+                let mut villages = vec![];
+                for center in centers {
+                    generator.set(center.0, center.1, well);
+                    let mut lots: Vec<(usize, usize)> = (center.1 - radius..center.1 + radius)
+                        .flat_map(|y| (center.0 - radius..center.0 + radius).map(move |x| (x, y)))
+                        .collect();
+                    lots.shuffle(rng);
+                    let mut placed = vec![];
+                    for (x, y) in lots {
+                        if placed.len() == buildings {
+                            break;
+                        }
+                        // keep a free cell between buildings and around the well
+                        let free = (x.saturating_sub(1)..=x + 2).all(|x| {
+                            (y.saturating_sub(1)..=y + 2).all(|y| {
+                                let value = generator.get_checked(x, y);
+                                value != Some(building) && value != Some(well)
+                            })
+                        });
+                        let footprint = [(x, y), (x + 1, y), (x, y + 1), (x + 1, y + 1)];
+                        let clear = footprint.iter().all(|&(x, y)| generator.get(x, y) == ground);
+                        if free && clear {
+                            for &(x, y) in footprint.iter() {
+                                generator.set(x, y, building);
+                            }
+                            placed.push((x, y));
+                        }
+                    }
+                    villages.push(Village {
+                        center,
+                        buildings: placed,
+                    });
+                }
+                villages
+            });
+            // join the villages with a minimum spanning tree of roads
+            let mut connected = vec![false; villages.len()];
+            if !villages.is_empty() {
+                connected[0] = true;
+            }
+            for _ in 1..villages.len() {
+                let (from, to) = (0..villages.len())
+                    .filter(|&from| connected[from])
+                    .flat_map(|from| (0..villages.len()).filter(|&to| !connected[to]).map(move |to| (from, to)))
+                    .min_by(|&(a, b), &(c, d)| {
+                        let first = euclidean(villages[a].center, villages[b].center);
+                        let second = euclidean(villages[c].center, villages[d].center);
+                        first.partial_cmp(&second).unwrap_or(std::cmp::Ordering::Equal)
+                    })
+                    .unwrap();
+                connected[to] = true;
+                let elevation = generator.field("elevation");
+                let route = |grade: Option<f64>| {
+                    generator.path_by(villages[from].center, villages[to].center, |pos, next| {
+                        let base = match generator.map[next] {
+                            value if value == building => return None,
+                            value if value == ground || value == road || value == well => 1,
+                            _ => 5,
+                        };
+                        let climb = elevation.map_or(0., |elevation| (elevation[next] - elevation[pos]).abs());
+                        if grade.is_some_and(|grade| climb > grade) {
+                            return None;
+                        }
+                        Some(base + (climb * slope_cost).round() as u32)
+                    })
+                };
+                let route = route(max_grade).or_else(|| route(None));
+                for (x, y) in route.unwrap_or_default() {
+                    if generator.get(x, y) != well {
+                        generator.set(x, y, road);
+                    }
+                }
+            }
+            generator.artifacts.villages.extend(villages);
+            generator
+        })
+    }
+    /// Sends traffic between every pair of villages in `artifacts().villages` along
+    /// the shortest route over the roads, and upgrades the road cells most of it
+    /// passes through to highways, so the roads sort themselves into main roads and
+    /// side roads. Villages with more buildings send more traffic. Traffic leaves the
+    /// roads only where they don't connect, such as over bridges. The traffic of
+    /// every cell is stored as the `"traffic"` field, between 0 and 1.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     Generator::new()
+    ///         .with_size(60, 30)
+    ///         .spawn_perlin_bands(&[(0.4, 1)])
+    ///         .spawn_villages(5, VillageOptions::default())
+    ///         .grade_roads(TrafficOptions::default())
+    ///         .show();
+    /// }
+    /// ```
+    pub fn grade_roads(self, options: TrafficOptions) -> Self {
+        self.stage(Stage::Traffic { options: options.clone() }, |mut generator| {
+            let TrafficOptions {
+                road,
+                well,
+                highway,
+                share,
+            } = options;
+            let villages = &generator.artifacts.villages;
+            let mut traffic = vec![0.; generator.map.len()];
+            for (i, a) in villages.iter().enumerate() {
+                for b in &villages[i + 1..] {
+                    let trips = ((a.buildings.len() + 1) * (b.buildings.len() + 1)) as f64;
+                    let route = generator.path_by(a.center, b.center, |_, next| match generator.map[next] {
+                        value if value == road || value == highway || value == well => Some(1),
+                        _ => Some(10),
+                    });
+                    for (x, y) in route.unwrap_or_default() {
+                        traffic[x + y * generator.width] += trips;
+                    }
+                }
+            }
+            let busiest = traffic.iter().copied().fold(0., f64::max);
+            if busiest > 0. {
+                traffic.iter_mut().for_each(|value| *value /= busiest);
+            }
+            for (value, &amount) in generator.map.iter_mut().zip(&traffic) {
+                if *value == road && amount > 0. && amount >= share {
+                    *value = highway;
+                }
+            }
+            generator.set_field("traffic", traffic);
+            generator
+        })
+    }
+}
+
+// farthest point selection: starts at a random candidate, then keeps adding the
+// candidate whose distance to the closest picked point is largest, stopping after
+// `n` points or once that distance drops below `min_distance`
+pub(crate) fn maximin(candidates: &[(usize, usize)], n: usize, min_distance: f64, rng: &mut dyn RngCore) -> Vec<(usize, usize)> {
+    let mut points = Vec::with_capacity(n);
+    if candidates.is_empty() || n == 0 {
+        return points;
+    }
+    let mut closest = vec![f64::INFINITY; candidates.len()];
+    let mut next = rng.gen_range(0, candidates.len());
+    while points.len() < n {
+        let point = candidates[next];
+        points.push(point);
+        for (distance, &candidate) in closest.iter_mut().zip(candidates) {
+            *distance = distance.min(euclidean(point, candidate));
+        }
+        let mut farthest = 0;
+        for (i, &distance) in closest.iter().enumerate() {
+            if distance > closest[farthest] {
+                farthest = i;
+            }
+        }
+        // a distance of 0 means every candidate has been picked
+        if closest[farthest] < min_distance || closest[farthest] == 0. {
+            break;
+        }
+        next = farthest;
+    }
+    points
+}
+
+fn euclidean(a: (usize, usize), b: (usize, usize)) -> f64 {
+    let dx = a.0 as f64 - b.0 as f64;
+    let dy = a.1 as f64 - b.1 as f64;
+    (dx * dx + dy * dy).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spawn_points() {
+        let generator = Generator::new().with_size(20, 20).with_seed(4);
+        let spawns = generator.place_spawn_points(4, 10., |_, _, _| true).unwrap();
+        assert_eq!(spawns.len(), 4);
+        for (i, &a) in spawns.iter().enumerate() {
+            for &b in &spawns[i + 1..] {
+                assert!(euclidean(a, b) >= 10.);
+            }
+        }
+        assert_eq!(generator.place_spawn_points(4, 10., |_, _, _| true), Some(spawns));
+        assert_eq!(generator.place_spawn_points(2, 30., |_, _, _| true), None);
+        assert_eq!(generator.place_spawn_points(1, 0., |_, _, value| value == 1), None);
+    }
+
+    #[test]
+    fn place_landmarks() {
+        let generator = Generator::new().with_size(21, 21).with_seed(2);
+        let landmarks = generator.place_landmarks(5, |value| value == 0);
+        assert_eq!(landmarks.len(), 5);
+        // a corner is always among the first few picks of farthest point selection
+        assert!(landmarks.iter().any(|&(x, y)| (x == 0 || x == 20) && (y == 0 || y == 20)));
+        assert_eq!(generator.place_landmarks(500, |value| value == 0).len(), 21 * 21);
+        assert!(generator.place_landmarks(3, |value| value == 1).is_empty());
+    }
+
+    #[test]
+    fn generate_patrols() {
+        let generator = Generator::new()
+            .with_size(12, 12)
+            .fill_rect(1, 1, 10, 10, 1)
+            .generate_patrols(2, 18, 1);
+        let patrols = &generator.artifacts().patrols;
+        assert_eq!(patrols.len(), 2);
+        for patrol in patrols {
+            assert_eq!(patrol.first(), patrol.last());
+            assert!(patrol.len() > 6);
+            assert!(patrol.iter().all(|&(x, y)| generator.get(x, y) == 1));
+            // every step moves to an orthogonal neighbor
+            assert!(patrol.windows(2).all(|step| {
+                let (a, b) = (step[0], step[1]);
+                (a.0 as isize - b.0 as isize).abs() + (a.1 as isize - b.1 as isize).abs() == 1
+            }));
+        }
+    }
+
+    #[test]
+    fn scatter_loot() {
+        // a long corridor, loot should end up far from the start on average
+        let generator = Generator::new()
+            .with_size(100, 1)
+            .with_seed(1)
+            .scatter_loot((0, 0), 0, 2, 10);
+        let loot = generator.positions_of(2);
+        assert_eq!(loot.len(), 10);
+        assert!(loot.iter().map(|&(x, _)| x).sum::<usize>() > 10 * 50);
+        assert_eq!(generator.get(0, 0), 0);
+    }
+
+    #[test]
+    fn place_encounters() {
+        let generator = Generator::new()
+            .with_size(20, 5)
+            .fill_rect(0, 0, 5, 5, 1)
+            .fill_rect(5, 2, 10, 1, 1)
+            .fill_rect(15, 0, 5, 5, 1)
+            .place_encounters((0, 0), 1, |distance, _| if distance >= 10 { vec![distance as usize, 7] } else { vec![] });
+        let encounters = &generator.artifacts().encounters;
+        assert_eq!(encounters.len(), 2);
+        assert!(encounters.iter().all(|encounter| encounter.position.0 >= 15));
+        assert_eq!(encounters[1].tier, 7);
+        assert!(generator.with_size(20, 5).artifacts().encounters.is_empty());
+    }
+
+    #[test]
+    fn place_exit() {
+        let constraints = ExitConstraints { min_steps: 6, ..ExitConstraints::default() };
+        // the start is walled in, so a corridor has to be dug
+        let generator = Generator::new()
+            .with_size(8, 8)
+            .fill_rect(0, 0, 2, 2, 1)
+            .place_exit((0, 0), constraints.clone());
+        let exit = generator.artifacts().exit.unwrap();
+        assert_eq!(generator.get(exit.0, exit.1), 2);
+        assert!(generator.steps_between((0, 0), exit, |value| value != 0).unwrap() >= 6);
+
+        let error = Generator::new()
+            .with_size(3, 3)
+            .try_place_exit((0, 0), ExitConstraints { min_steps: 5, ..constraints });
+        assert_eq!(error.unwrap_err(), GenerationError::ExitTooFar { min_steps: 5 });
+    }
+
+    #[test]
+    fn place_traps() {
+        let options = TrapOptions {
+            chance: 1.,
+            ..TrapOptions::default()
+        };
+        // a corridor with a side room, only the side room may get traps
+        let generator = Generator::new()
+            .with_size(6, 3)
+            .fill_rect(0, 0, 6, 1, 1)
+            .fill_rect(2, 1, 2, 2, 1)
+            .place_traps((0, 0), (5, 0), options);
+        assert_eq!(generator.positions_of(3), vec![(2, 1), (3, 1), (2, 2), (3, 2)]);
+        assert!(generator.is_reachable((0, 0), (5, 0), |value| value == 1));
+    }
+
+    #[test]
+    fn spawn_villages() {
+        let options = VillageOptions::default();
+        let generator = Generator::new()
+            .with_size(40, 20)
+            .fill_rect(0, 0, 40, 20, 1)
+            .spawn_villages(2, options.clone());
+        let villages = &generator.artifacts().villages;
+        assert_eq!(villages.len(), 2);
+        for village in villages {
+            assert_eq!(generator.get(village.center.0, village.center.1), options.well);
+            assert!(!village.buildings.is_empty());
+        }
+        assert!(generator.is_reachable(villages[0].center, villages[1].center, |value| value == 3 || value == 4));
+    }
+
+    #[test]
+    fn village_roads_follow_contours() {
+        // a ridge down the middle with a pass at the top
+        let (width, height) = (40, 15);
+        let elevation: Vec<f64> = (0..width * height)
+            .map(|pos| if pos % width == 20 && pos / width > 1 { 1. } else { 0. })
+            .collect();
+        let roads = |slope_cost, max_grade| {
+            let mut generator = Generator::new().with_size(width, height).with_seed(3).fill_rect(0, 0, width, height, 1);
+            generator.set_field("elevation", elevation.clone());
+            let options = VillageOptions {
+                buildings: 0,
+                radius: 2,
+                slope_cost,
+                max_grade,
+                ..VillageOptions::default()
+            };
+            let generator = generator.spawn_villages(2, options);
+            let villages = &generator.artifacts().villages;
+            assert!(villages[0].center.0.min(villages[1].center.0) < 20 && villages[0].center.0.max(villages[1].center.0) > 20);
+            generator.get_2d_map().iter().map(|row| row[20]).collect::<Vec<usize>>()
+        };
+        let crossing = |column: Vec<usize>| column.iter().position(|&value| value == 4).unwrap();
+        assert!(crossing(roads(100., None)) < 2);
+        assert!(crossing(roads(0., Some(0.5))) < 2);
+        assert!(crossing(roads(0., None)) >= 2);
+    }
+
+    #[test]
+    fn grade_roads() {
+        // two big villages on the left and a small one on the right
+        let mut generator = Generator::new().with_size(21, 1).fill_rect(0, 0, 21, 1, 4);
+        for (x, buildings) in [(0, 4), (10, 4), (20, 0)] {
+            generator.set(x, 0, 3);
+            generator.artifacts.villages.push(Village {
+                center: (x, 0),
+                buildings: vec![(0, 0); buildings],
+            });
+        }
+        let generator = generator.grade_roads(TrafficOptions::default());
+        let traffic = generator.field("traffic").unwrap();
+        assert_eq!(traffic[10], 1.);
+        assert!(traffic[5] > 0.5);
+        assert!(traffic[15] > 0. && traffic[15] < 0.5);
+        // only the road between the big villages becomes a highway
+        assert_eq!(generator.positions_of(7), (1..10).map(|x| (x, 0)).collect::<Vec<_>>());
+        assert_eq!(generator.positions_of(4).len(), 9);
+    }
+}
@@ -0,0 +1,127 @@
+//! Hand-made structures stamped onto generated maps.
+
+use crate::{Generator, Stage, Stamp};
+use rand::prelude::*;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A small hand-made structure, such as a ruin or a shrine, stamped onto the map
+/// by [Generator::stamp_poisson](struct.Generator.html#method.stamp_poisson).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Prefab {
+    /// Name the prefab is recorded under in `artifacts().stamps`.
+    pub name: String,
+    pub width: usize,
+    pub height: usize,
+    /// Cells in row-major order, `None` leaves the cell of the map as it is.
+    pub cells: Vec<Option<usize>>,
+}
+
+impl Prefab {
+    /// Creates a `width` by `height` prefab called `name`. Panics if `cells`
+    /// doesn't have `width * height` cells.
+    pub fn new(name: &str, width: usize, height: usize, cells: Vec<Option<usize>>) -> Self {
+        assert!(
+            cells.len() == width * height,
+            "a {}x{} prefab must have {} cells",
+            width,
+            height,
+            width * height
+        );
+        Self {
+            name: name.to_string(),
+            width,
+            height,
+            cells,
+        }
+    }
+}
+
+impl Generator {
+    /// Stamps `prefab` at well spaced places, with Poisson disk sampling. A place
+    /// is valid when `valid(x, y, value)` returns true for every cell the prefab
+    /// covers, and the top left corners of any two stamps are at least `radius`
+    /// cells apart. Valid places are tried in random order and taken whenever they
+    /// are far enough from the ones taken so far, until none are left, so stamps
+    /// end up spread evenly without lining up. Every stamp is listed in
+    /// `artifacts().stamps`.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     // ruins on grassland, at least 12 cells apart
+    ///     let wall = Some(2);
+    ///     let ruin = Prefab::new("ruin", 3, 3, vec![wall, wall, wall, wall, None, None, wall, None, wall]);
+    ///     Generator::new()
+    ///         .with_size(60, 20)
+    ///         .spawn_perlin_bands(&[(0.4, 1)])
+    ///         .stamp_poisson(&ruin, 12., |_, _, value| value == 1)
+    ///         .show();
+    /// }
+    /// ```
+    pub fn stamp_poisson<F: Fn(usize, usize, usize) -> bool>(self, prefab: &Prefab, radius: f64, valid: F) -> Self {
+        self.stage(Stage::Custom { name: "stamp_poisson".into() }, |mut generator| {
+            if prefab.width > generator.width || prefab.height > generator.height {
+                return generator;
+            }
+            let mut candidates: Vec<(usize, usize)> = (0..=generator.height - prefab.height)
+                .flat_map(|y| (0..=generator.width - prefab.width).map(move |x| (x, y)))
+                .filter(|&(x, y)| {
+                    (0..prefab.height).all(|dy| {
+                        (0..prefab.width).all(|dx| valid(x + dx, y + dy, generator.get(x + dx, y + dy)))
+                    })
+                })
+                .collect();
+            generator.with_stage_rng("stamp_poisson", |_, rng| candidates.shuffle(rng));
+            let mut placed: Vec<(usize, usize)> = vec![];
+            for (x, y) in candidates {
+                let far_enough = placed.iter().all(|&(px, py)| {
+                    let (dx, dy) = (px as f64 - x as f64, py as f64 - y as f64);
+                    (dx * dx + dy * dy).sqrt() >= radius
+                });
+                if far_enough {
+                    placed.push((x, y));
+                }
+            }
+            for &(x, y) in &placed {
+                for (i, cell) in prefab.cells.iter().enumerate() {
+                    if let Some(value) = cell {
+                        generator.set(x + i % prefab.width, y + i / prefab.width, *value);
+                    }
+                }
+                generator.artifacts.stamps.push(Stamp {
+                    prefab: prefab.name.clone(),
+                    position: (x, y),
+                });
+            }
+            generator
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stamp_poisson() {
+        let post = Prefab::new("post", 2, 1, vec![Some(2), None]);
+        let generator = Generator::new()
+            .with_size(30, 30)
+            .fill_rect(0, 0, 15, 30, 1)
+            .stamp_poisson(&post, 5., |_, _, value| value == 1);
+        let stamps = &generator.artifacts().stamps;
+        assert!(stamps.len() > 5);
+        assert_eq!(generator.positions_of(2).len(), stamps.len());
+        for (i, a) in stamps.iter().enumerate() {
+            // the whole prefab lies on the left half
+            assert!(a.prefab == "post" && a.position.0 < 14);
+            for b in &stamps[i + 1..] {
+                let (dx, dy) = (a.position.0 as f64 - b.position.0 as f64, a.position.1 as f64 - b.position.1 as f64);
+                assert!((dx * dx + dy * dy).sqrt() >= 5.);
+            }
+        }
+    }
+}
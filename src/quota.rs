@@ -0,0 +1,150 @@
+//! Post-adjusting the map so values cover a guaranteed share of it.
+
+use crate::{GenerationError, Generator, Stage};
+use rand::Rng;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// How [Generator::ensure_quota](struct.Generator.html#method.ensure_quota) picks
+/// the cells it converts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum QuotaStrategy {
+    /// Grows existing areas of the value outwards, one random neighbour at a time.
+    Grow,
+    /// Converts the cells where fresh noise is highest, as if a noise threshold had
+    /// been lowered. Makes new blobs instead of widening the existing ones. The noise
+    /// follows the noise options of the generator, like `spawn_perlin(...)`.
+    Rethreshold,
+}
+
+impl Generator {
+    /// Converts cells until at least `min_fraction` of the map holds `value`, for
+    /// guarantees like "at least 30% water". Maps that already meet the quota are
+    /// left alone. Panics if `min_fraction` is not between 0 and 1, see
+    /// `try_ensure_quota(...)`.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let generator = Generator::new()
+    ///         .with_size(40, 20)
+    ///         .spawn_perlin_bands(&[(0.5, 1)])
+    ///         .ensure_quota(0, 0.3, QuotaStrategy::Grow);
+    ///     assert!(generator.positions_of(0).len() >= 240);
+    /// }
+    /// ```
+    pub fn ensure_quota(self, value: usize, min_fraction: f64, strategy: QuotaStrategy) -> Self {
+        match self.try_ensure_quota(value, min_fraction, strategy) {
+            Ok(generator) => generator,
+            Err(error) => panic!("{}", error),
+        }
+    }
+    /// Same as `ensure_quota(...)`, except it fails with
+    /// [GenerationError::InvalidQuota](enum.GenerationError.html) when `min_fraction`
+    /// is not between 0 and 1, and with `EmptyMap` when the map has no cells.
+    pub fn try_ensure_quota(
+        self,
+        value: usize,
+        min_fraction: f64,
+        strategy: QuotaStrategy,
+    ) -> Result<Self, GenerationError> {
+        if !(0. ..=1.).contains(&min_fraction) {
+            return Err(GenerationError::InvalidQuota(min_fraction));
+        }
+        if self.map.is_empty() {
+            return Err(GenerationError::EmptyMap);
+        }
+        let stage = Stage::Quota {
+            value,
+            min_fraction,
+            strategy,
+        };
+        let needed = (min_fraction * self.map.len() as f64).ceil() as usize;
+        Ok(self.stage(stage, |mut generator| {
+            let missing = needed.saturating_sub(generator.map.iter().filter(|&&cell| cell == value).count());
+            if missing > 0 {
+                match strategy {
+                    QuotaStrategy::Grow => {
+                        generator.with_stage_rng("quota", |generator, rng| generator.grow_quota(value, missing, rng))
+                    }
+                    QuotaStrategy::Rethreshold => generator.rethreshold_quota(value, missing),
+                }
+            }
+            generator
+        }))
+    }
+    fn grow_quota(&mut self, value: usize, mut missing: usize, rng: &mut dyn rand::RngCore) {
+        let mut frontier: Vec<usize> = (0..self.map.len())
+            .filter(|&pos| self.map[pos] != value && self.touches(pos, value))
+            .collect();
+        if frontier.is_empty() {
+            // nothing to grow from, start a new area somewhere
+            frontier.push(rng.gen_range(0, self.map.len()));
+        }
+        while missing > 0 && !frontier.is_empty() {
+            let pos = frontier.swap_remove(rng.gen_range(0, frontier.len()));
+            if self.map[pos] == value {
+                continue;
+            }
+            self.map[pos] = value;
+            missing -= 1;
+            let (x, y) = (pos % self.width, pos / self.width);
+            frontier.extend(
+                self.neighbors4(x, y)
+                    .filter(|&(_, _, neighbor)| neighbor != value)
+                    .map(|(x, y, _)| x + y * self.width),
+            );
+        }
+    }
+    fn rethreshold_quota(&mut self, value: usize, missing: usize) {
+        // normalizing the noise keeps the order of the cells, so raw noise ranks them
+        let noise = self.noise_field("quota");
+        let mut candidates: Vec<usize> = (0..self.map.len()).filter(|&pos| self.map[pos] != value).collect();
+        candidates.sort_by(|&a, &b| noise[b].total_cmp(&noise[a]));
+        for &pos in candidates.iter().take(missing) {
+            self.map[pos] = value;
+        }
+    }
+    // true if one of the 4 neighbours of `pos` holds `value`
+    fn touches(&self, pos: usize, value: usize) -> bool {
+        self.neighbors4(pos % self.width, pos / self.width)
+            .any(|(_, _, neighbor)| neighbor == value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quota() {
+        for &strategy in [QuotaStrategy::Grow, QuotaStrategy::Rethreshold].iter() {
+            let generator = Generator::new()
+                .with_size(20, 10)
+                .fill_rect(0, 0, 2, 2, 1)
+                .ensure_quota(1, 0.25, strategy);
+            assert_eq!(generator.positions_of(1).len(), 50);
+        }
+        // rethresholding converts the highest cells of the noise field for the stage
+        let options = crate::NoiseOptions {
+            sampling: crate::Sampling::PerAxis,
+            warp: 0.5,
+            ..Default::default()
+        };
+        let tall = || Generator::new().with_size(10, 40).with_seed(1).with_options(options.clone());
+        let noise = tall().noise_field("quota");
+        let topped = tall().ensure_quota(1, 0.1, QuotaStrategy::Rethreshold);
+        let lowest = (0..400).filter(|&pos| topped.map[pos] == 1).map(|pos| noise[pos]).fold(f64::INFINITY, f64::min);
+        assert!((0..400).all(|pos| topped.map[pos] == 1 || noise[pos] <= lowest));
+        let grown = Generator::new().with_size(20, 10).ensure_quota(1, 0.1, QuotaStrategy::Grow);
+        assert_eq!(grown.positions_of(1).len(), 20);
+        assert_eq!(grown.label_regions().values.iter().filter(|&&value| value == 1).count(), 1);
+        assert_eq!(
+            Generator::new().with_size(2, 2).try_ensure_quota(1, 1.5, QuotaStrategy::Grow).unwrap_err(),
+            GenerationError::InvalidQuota(1.5)
+        );
+    }
+}
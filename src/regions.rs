@@ -0,0 +1,321 @@
+//! Connected-component labeling of the map.
+
+use crate::Generator;
+use rayon::prelude::*;
+
+/// Amount of rows each worker labels on its own before the strips are merged.
+const STRIP_HEIGHT: usize = 64;
+
+/// Regions of 4-connected cells sharing the same value, see
+/// [Generator::label_regions](struct.Generator.html#method.label_regions).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Regions {
+    /// Region label of every cell, laid out like `Generator::map`.
+    pub labels: Vec<usize>,
+    /// Value of the cells in each region, indexed by label.
+    pub values: Vec<usize>,
+    /// Amount of cells in each region, indexed by label.
+    pub sizes: Vec<usize>,
+}
+
+impl Regions {
+    /// Amount of regions.
+    pub fn count(&self) -> usize {
+        self.sizes.len()
+    }
+}
+
+/// How well connected the cells of one value are, see
+/// [Generator::connectivity_report](struct.Generator.html#method.connectivity_report).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConnectivityReport {
+    /// Size of every region of the value, largest first.
+    pub sizes: Vec<usize>,
+    /// Amount of cells holding the value.
+    pub cells: usize,
+}
+
+impl ConnectivityReport {
+    /// Amount of separate regions.
+    pub fn regions(&self) -> usize {
+        self.sizes.len()
+    }
+    /// Fraction of the cells that belong to the largest region, 0 when there are none.
+    pub fn coverage(&self) -> f64 {
+        match self.sizes.first() {
+            Some(&largest) => largest as f64 / self.cells as f64,
+            None => 0.,
+        }
+    }
+}
+
+impl Generator {
+    /// Reports how the cells holding `floor` split into 4-connected regions, so
+    /// tests can assert things like "one region covers at least 90% of the floor"
+    /// before shipping a level.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let size = Size::new((4, 4), (10, 10));
+    ///     let report = Generator::new()
+    ///         .with_size(30, 20)
+    ///         .spawn_rooms(1, 5, &size)
+    ///         .connectivity_report(1);
+    ///     println!("{} regions, largest covers {:.0}%", report.regions(), report.coverage() * 100.);
+    /// }
+    /// ```
+    pub fn connectivity_report(&self, floor: usize) -> ConnectivityReport {
+        let regions = self.label_regions();
+        let mut sizes: Vec<usize> = regions
+            .values
+            .iter()
+            .zip(&regions.sizes)
+            .filter(|&(&value, _)| value == floor)
+            .map(|(_, &size)| size)
+            .collect();
+        sizes.sort_unstable_by(|a, b| b.cmp(a));
+        ConnectivityReport {
+            cells: sizes.iter().sum(),
+            sizes,
+        }
+    }
+    /// Finds narrow passages: passable cells whose removal would split the region
+    /// they belong to, such as every cell of a corridor that is one cell wide. Good
+    /// spots for doors, bridges or ambushes. `passable(value)` tells which cells can
+    /// be walked on, cells are 4-connected and returned in row-major order.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let size = Size::new((4, 4), (10, 10));
+    ///     let generator = Generator::new()
+    ///         .with_size(40, 20)
+    ///         .spawn_rooms(1, 5, &size);
+    ///     for (x, y) in generator.chokepoints(|value| value == 1) {
+    ///         println!("door at ({}, {})", x, y);
+    ///     }
+    /// }
+    /// ```
+    pub fn chokepoints<F: Fn(usize) -> bool>(&self, passable: F) -> Vec<(usize, usize)> {
+        let width = self.width;
+        let passable: Vec<bool> = self.map.iter().map(|&value| passable(value)).collect();
+        // discovery order and lowest discovery order reachable through a back edge,
+        // the usual way of finding articulation points, with an explicit stack
+        let mut order = vec![usize::MAX; self.map.len()];
+        let mut low = vec![0; self.map.len()];
+        let mut articulation = vec![false; self.map.len()];
+        let mut counter = 0;
+        for root in 0..self.map.len() {
+            if !passable[root] || order[root] != usize::MAX {
+                continue;
+            }
+            order[root] = counter;
+            low[root] = counter;
+            counter += 1;
+            let mut root_children = 0;
+            // (cell, parent, next neighbor to visit)
+            let mut stack = vec![(root, usize::MAX, 0)];
+            while let Some(&mut (pos, parent, ref mut next)) = stack.last_mut() {
+                let neighbor = self
+                    .neighbors4(pos % width, pos / width)
+                    .map(|(x, y, _)| x + y * width)
+                    .nth(*next);
+                *next += 1;
+                match neighbor {
+                    Some(neighbor) if !passable[neighbor] || neighbor == parent => {}
+                    Some(neighbor) if order[neighbor] != usize::MAX => low[pos] = low[pos].min(order[neighbor]),
+                    Some(neighbor) => {
+                        order[neighbor] = counter;
+                        low[neighbor] = counter;
+                        counter += 1;
+                        if pos == root {
+                            root_children += 1;
+                        }
+                        stack.push((neighbor, pos, 0));
+                    }
+                    None => {
+                        stack.pop();
+                        if parent != usize::MAX {
+                            low[parent] = low[parent].min(low[pos]);
+                            if parent != root && low[pos] >= order[parent] {
+                                articulation[parent] = true;
+                            }
+                        }
+                    }
+                }
+            }
+            articulation[root] = root_children > 1;
+        }
+        (0..self.map.len())
+            .filter(|&pos| articulation[pos])
+            .map(|pos| (pos % width, pos / width))
+            .collect()
+    }
+    /// Labels every group of 4-connected cells sharing the same value. Labels
+    /// start at 0 and are handed out in row-major order of each region's first cell.
+    ///
+    /// The map is split into strips which are labeled in parallel, then the
+    /// strips are stitched together with a union-find, so this stays fast on huge maps.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let size = Size::new((4, 4), (10, 10));
+    ///     let regions = Generator::new()
+    ///         .with_size(30, 20)
+    ///         .spawn_rooms(1, 3, &size)
+    ///         .label_regions();
+    ///     println!("{} regions", regions.count());
+    /// }
+    /// ```
+    pub fn label_regions(&self) -> Regions {
+        let width = self.width;
+        let map = &self.map;
+        if map.is_empty() {
+            return Regions::default();
+        }
+
+        // label each strip on its own, every cell ends up pointing at the root of its
+        // component within the strip
+        let mut parent = vec![0; map.len()];
+        parent
+            .par_chunks_mut(STRIP_HEIGHT * width)
+            .enumerate()
+            .for_each(|(strip, parent)| {
+                let offset = strip * STRIP_HEIGHT * width;
+                let map = &map[offset..offset + parent.len()];
+                for (i, p) in parent.iter_mut().enumerate() {
+                    *p = i;
+                }
+                for i in 0..map.len() {
+                    if i % width > 0 && map[i - 1] == map[i] {
+                        union(parent, i, i - 1);
+                    }
+                    if i >= width && map[i - width] == map[i] {
+                        union(parent, i, i - width);
+                    }
+                }
+                for i in 0..parent.len() {
+                    parent[i] = find(parent, i);
+                }
+                for p in parent.iter_mut() {
+                    *p += offset;
+                }
+            });
+
+        // stitch strips together along their borders
+        for y in (STRIP_HEIGHT..self.height).step_by(STRIP_HEIGHT) {
+            for x in 0..width {
+                let i = x + y * width;
+                if map[i - width] == map[i] {
+                    union(&mut parent, i, i - width);
+                }
+            }
+        }
+
+        let roots: Vec<usize> = (0..map.len())
+            .into_par_iter()
+            .map(|i| {
+                let mut root = i;
+                while parent[root] != root {
+                    root = parent[root];
+                }
+                root
+            })
+            .collect();
+
+        // roots are always the first cell of their region, so they are labeled
+        // before any other cell refers to them
+        let mut regions = Regions {
+            labels: vec![0; map.len()],
+            ..Regions::default()
+        };
+        for (i, &root) in roots.iter().enumerate() {
+            let label = if root == i {
+                regions.values.push(map[i]);
+                regions.sizes.push(0);
+                regions.sizes.len() - 1
+            } else {
+                regions.labels[root]
+            };
+            regions.labels[i] = label;
+            regions.sizes[label] += 1;
+        }
+        regions
+    }
+}
+
+fn find(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+// the smaller index always becomes the root, so a root is the first cell of its region
+fn union(parent: &mut [usize], a: usize, b: usize) {
+    let a = find(parent, a);
+    let b = find(parent, b);
+    if a < b {
+        parent[b] = a;
+    } else {
+        parent[a] = b;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn label_regions() {
+        let mut generator = Generator::new().with_size(4, 3);
+        generator.map = vec![
+            1,1,0,2,
+            0,1,0,2,
+            0,0,0,1,
+        ];
+        let regions = generator.label_regions();
+        assert_eq!(regions.labels, vec![
+            0,0,1,2,
+            1,0,1,2,
+            1,1,1,3,
+        ]);
+        assert_eq!(regions.values, vec![1, 0, 2, 1]);
+        assert_eq!(regions.sizes, vec![3, 6, 2, 1]);
+
+        let report = generator.connectivity_report(1);
+        assert_eq!(report.sizes, vec![3, 1]);
+        assert_eq!(report.regions(), 2);
+        assert_eq!(report.coverage(), 0.75);
+        assert_eq!(generator.connectivity_report(5).coverage(), 0.);
+    }
+
+    #[test]
+    fn chokepoints() {
+        let mut generator = Generator::new().with_size(7, 3);
+        // two rooms joined by a corridor
+        generator.map = vec![
+            1,1,0,0,0,1,1,
+            1,1,1,1,1,1,1,
+            1,1,0,0,0,1,1,
+        ];
+        let chokepoints = generator.chokepoints(|value| value == 1);
+        assert_eq!(chokepoints, vec![(1, 1), (2, 1), (3, 1), (4, 1), (5, 1)]);
+        assert!(Generator::new().with_size(4, 4).chokepoints(|value| value == 0).is_empty());
+    }
+
+    #[test]
+    fn label_regions_across_strips() {
+        // a single column spanning several strips must stay one region
+        let generator = Generator::new().with_size(3, STRIP_HEIGHT * 3 + 5);
+        let regions = generator.label_regions();
+        assert_eq!(regions.count(), 1);
+        assert_eq!(regions.sizes[0], 3 * (STRIP_HEIGHT * 3 + 5));
+    }
+}
@@ -0,0 +1,135 @@
+//! Named recipes and spawners that can be picked at runtime.
+
+use crate::{GenerationError, Generator, Recipe, Spawner, Stage};
+use std::collections::HashMap;
+use std::fmt;
+
+enum Entry {
+    Recipe(Recipe),
+    Spawner(Box<dyn Spawner + Send + Sync>),
+}
+
+/// Recipes and spawners registered under names, so modding systems and
+/// data-driven games can choose generators at runtime.
+///
+/// ```rust
+/// use procedural_generation::*;
+///
+/// fn main() {
+///     let mut registry = Registry::new();
+///     let caverns = Generator::new()
+///         .with_size(40, 10)
+///         .spawn_perlin_bands(&[(0.5, 1)])
+///         .recipe();
+///     registry.register_recipe("cavern_small", caverns);
+///
+///     registry.generate("cavern_small").unwrap().show();
+///     // or run it on top of an existing generator
+///     Generator::new()
+///         .with_size(20, 5)
+///         .run(&registry, "cavern_small")
+///         .unwrap()
+///         .show();
+/// }
+/// ```
+#[derive(Default)]
+pub struct Registry {
+    entries: HashMap<String, Entry>,
+}
+
+impl fmt::Debug for Registry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.names()).finish()
+    }
+}
+
+impl Registry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Registers `recipe` under `name`, replacing whatever was registered there.
+    pub fn register_recipe(&mut self, name: &str, recipe: Recipe) -> &mut Self {
+        self.entries.insert(name.to_string(), Entry::Recipe(recipe));
+        self
+    }
+    /// Registers `spawner` under `name`, replacing whatever was registered there.
+    pub fn register_spawner<S: Spawner + Send + Sync + 'static>(&mut self, name: &str, spawner: S) -> &mut Self {
+        self.entries.insert(name.to_string(), Entry::Spawner(Box::new(spawner)));
+        self
+    }
+    /// Names of everything registered, in no particular order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(|name| name.as_str())
+    }
+    /// Generates the map of the recipe registered under `name`, with the recipe's
+    /// own size and seed.
+    pub fn generate(&self, name: &str) -> Result<Generator, GenerationError> {
+        match self.entries.get(name) {
+            Some(Entry::Recipe(recipe)) => self.replay(recipe),
+            Some(Entry::Spawner(_)) => Err(GenerationError::NotARecipe(name.to_string())),
+            None => Err(GenerationError::UnknownGenerator(name.to_string())),
+        }
+    }
+    /// Same as `Generator::from_recipe(...)`, except custom stages are looked up
+    /// in the registry by name, so recipes using registered spawners can be replayed.
+    pub fn replay(&self, recipe: &Recipe) -> Result<Generator, GenerationError> {
+        Generator::new()
+            .with_size(recipe.width, recipe.height)
+            .with_seed(recipe.seed)
+            .replay_stages(recipe, Some(self))
+    }
+}
+
+impl Generator {
+    /// Runs whatever is registered under `name` in `registry` on this generator:
+    /// a spawner runs as a single stage, and a recipe has its stages replayed with
+    /// this generator's size and seed.
+    pub fn run(self, registry: &Registry, name: &str) -> Result<Self, GenerationError> {
+        match registry.entries.get(name) {
+            Some(Entry::Recipe(recipe)) => self.replay_stages(recipe, Some(registry)),
+            // recorded under the registered name, so the recipe can be replayed
+            Some(Entry::Spawner(spawner)) => Ok(self.stage(Stage::Custom { name: name.to_string() }, |mut generator| {
+                spawner.spawn(&mut generator);
+                generator
+            })),
+            None => Err(GenerationError::UnknownGenerator(name.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Border;
+
+    impl Spawner for Border {
+        fn spawn(&self, generator: &mut Generator) {
+            let (width, height) = (generator.width, generator.height);
+            *generator = std::mem::take(generator).draw_rect(0, 0, width, height, 1);
+        }
+        fn name(&self) -> &str {
+            "border"
+        }
+    }
+
+    #[test]
+    fn registry() {
+        let mut registry = Registry::new();
+        registry.register_spawner("border", Border);
+        let generator = Generator::new().with_size(4, 4).spawn_with(&Border).fill_rect(1, 1, 2, 2, 2);
+        registry.register_recipe("walled", generator.recipe());
+
+        assert_eq!(generator.stages().len(), 2);
+        assert_eq!(registry.generate("walled").unwrap().map, generator.map);
+        assert_eq!(
+            Generator::new().with_size(4, 4).run(&registry, "missing").unwrap_err(),
+            GenerationError::UnknownGenerator("missing".into())
+        );
+        assert_eq!(
+            registry.generate("border").unwrap_err(),
+            GenerationError::NotARecipe("border".into())
+        );
+    }
+}
@@ -0,0 +1,365 @@
+//! Alternative ways of printing the map to the terminal.
+
+use crate::Generator;
+use owo_colors::OwoColorize;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::thread;
+use std::time::Duration;
+
+// prints the cells of a rectangle, with rulers if the generator has them on
+pub(crate) struct Window<'a> {
+    generator: &'a Generator,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+    rulers: bool,
+}
+
+impl<'a> Window<'a> {
+    // clamps the rectangle to the map
+    pub(crate) fn new(generator: &'a Generator, x: usize, y: usize, width: usize, height: usize) -> Self {
+        let x = x.min(generator.width);
+        let y = y.min(generator.height);
+        Self {
+            generator,
+            x,
+            y,
+            width: width.min(generator.width - x),
+            height: height.min(generator.height - y),
+            rulers: generator.rulers,
+        }
+    }
+}
+
+impl fmt::Display for Window<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let columns = self.x..self.x + self.width;
+        let rows = self.y..self.y + self.height;
+        let margin = if self.rulers { digits(rows.end.saturating_sub(1)) + 1 } else { 0 };
+        if self.rulers {
+            // most significant digit first, higher digits only where they change
+            for place in (0..digits(columns.end.saturating_sub(1)) as u32).rev() {
+                write!(f, "{:margin$}", "", margin = margin)?;
+                for x in columns.clone() {
+                    if place == 0 || x % 10usize.pow(place) == 0 || x == self.x {
+                        write!(f, "{} ", x / 10usize.pow(place) % 10)?;
+                    } else {
+                        write!(f, "  ")?;
+                    }
+                }
+                writeln!(f)?;
+            }
+        }
+        for y in rows.clone() {
+            if self.rulers {
+                write!(f, "{:>width$} ", y, width = margin - 1)?;
+            }
+            for x in columns.clone() {
+                self.generator.palette.write(f, self.generator.get(x, y))?;
+            }
+            if y + 1 < rows.end {
+                writeln!(f)?
+            }
+        }
+        Ok(())
+    }
+}
+
+// number of decimal digits in `n`
+fn digits(n: usize) -> usize {
+    n.to_string().len()
+}
+
+// terminal size from the environment, 80 by 24 if unknown
+fn terminal_size() -> (usize, usize) {
+    let read = |name: &str, default: usize| {
+        std::env::var(name)
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(default)
+    };
+    (read("COLUMNS", 80), read("LINES", 24))
+}
+
+// two rows per line, upper half block colored as the top cell on top of the
+// bottom cell's color
+struct Compact<'a>(&'a Generator);
+
+impl fmt::Display for Compact<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Compact(generator) = self;
+        let palette = &generator.palette;
+        for y in (0..generator.height).step_by(2) {
+            for x in 0..generator.width {
+                let (r, g, b) = palette.rgb(generator.get(x, y));
+                if y + 1 < generator.height {
+                    let (br, bg, bb) = palette.rgb(generator.get(x, y + 1));
+                    write!(f, "{}", '▀'.truecolor(r, g, b).on_truecolor(br, bg, bb))?;
+                } else {
+                    write!(f, "{}", '▀'.truecolor(r, g, b))?;
+                }
+            }
+            if y + 2 < generator.height {
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// field of values printed as shades of gray, darkest at the lowest value
+struct Shaded<'a> {
+    width: usize,
+    field: &'a [f64],
+}
+
+impl fmt::Display for Shaded<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let min = self.field.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = self.field.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let range = if max > min { max - min } else { 1. };
+        for (i, row) in self.field.chunks(self.width.max(1)).enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            for value in row {
+                let gray = ((value - min) / range * 255.).round() as u8;
+                write!(f, "{}", "  ".on_truecolor(gray, gray, gray))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// layers printed next to each other with their names above them
+struct SideBySide<'a> {
+    generator: &'a Generator,
+    layers: Vec<(&'a str, &'a [usize])>,
+}
+
+impl fmt::Display for SideBySide<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = self.generator.width;
+        for (i, (name, _)) in self.layers.iter().enumerate() {
+            let separator = if i == 0 { "" } else { "  " };
+            write!(f, "{}{:width$}", separator, name, width = width * 2)?;
+        }
+        for y in 0..self.generator.height {
+            writeln!(f)?;
+            for (i, (_, values)) in self.layers.iter().enumerate() {
+                if i > 0 {
+                    write!(f, "  ")?;
+                }
+                for value in &values[y * width..(y + 1) * width] {
+                    self.generator.palette.write(f, *value)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Generator {
+    /// Prints the map with one colored half block per cell, two rows per line,
+    /// so maps a few hundred cells wide still fit on screen. Values are told
+    /// apart by color alone, the background of a swatch wins over its text color.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     Generator::new()
+    ///         .with_size(200, 100)
+    ///         .spawn_perlin_bands(&[(0.66, 2), (0.33, 1)])
+    ///         .show_compact();
+    /// }
+    /// ```
+    pub fn show_compact(&self) {
+        println!("{}", Compact(self));
+    }
+    /// Prints `field`, one value per cell in the same order as `map`, as shades
+    /// of gray from black at its lowest value to white at its highest. Useful for
+    /// eyeballing noise or a `density_map()` before turning it into tiles.
+    /// Panics if `field` doesn't have one value per cell.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let generator = Generator::new().with_size(40, 10).spawn_perlin_bands(&[(0.5, 1)]);
+    ///     let density: Vec<f64> = generator.density_map(1, 2).into_iter().map(f64::from).collect();
+    ///     generator.show_shaded(&density);
+    /// }
+    /// ```
+    pub fn show_shaded(&self, field: &[f64]) {
+        assert_eq!(field.len(), self.map.len(), "field must have one value per cell");
+        println!("{}", Shaded { width: self.width, field });
+    }
+    /// Same as `show_shaded(...)` with the field called `name`, for inspecting the
+    /// intermediate fields stages publish. Panics if there is no such field.
+    pub fn show_field(&self, name: &str) {
+        match self.field(name) {
+            Some(field) => self.show_shaded(field),
+            None => panic!("there is no field called `{}`", name),
+        }
+    }
+    /// Prints the layers called `names` next to each other, with their names
+    /// above them, for comparing the layers of a multi-layer pipeline cell by
+    /// cell. Panics if there is no layer with one of the names.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let mut generator = Generator::new().with_size(20, 10).spawn_perlin_bands(&[(0.5, 1)]);
+    ///     let terrain = generator.map.clone();
+    ///     generator.set_layer("terrain", terrain);
+    ///     let generator = generator.classify_depth((0, 0), 1, 3);
+    ///     generator.show_layers(&["terrain", "depth"]);
+    /// }
+    /// ```
+    pub fn show_layers(&self, names: &[&str]) {
+        let layers = names
+            .iter()
+            .map(|&name| match self.layer(name) {
+                Some(values) => (name, values),
+                None => panic!("there is no layer called `{}`", name),
+            })
+            .collect();
+        println!("{}", SideBySide { generator: self, layers });
+    }
+    /// Redraws the map in the terminal after every iteration of iterative stages,
+    /// such as each step of `rewrite()`, and waits `delay` before going on. Makes
+    /// it easy to see how many iterations a stage actually needs.
+    ///
+    /// ```rust,no_run
+    /// use procedural_generation::*;
+    /// use std::time::Duration;
+    ///
+    /// fn main() {
+    ///     let grow = Rule::new(3, 1, vec![Some(1), Some(0), Some(0)], vec![None, Some(1), Some(1)]);
+    ///     Generator::new()
+    ///         .with_size(41, 21)
+    ///         .with_animation(Duration::from_millis(100))
+    ///         .draw_line((20, 10), (20, 10), 1)
+    ///         .rewrite(&grow.rotations(), 10);
+    /// }
+    /// ```
+    pub fn with_animation(mut self, delay: Duration) -> Self {
+        self.animation = Some(delay);
+        self
+    }
+    // clears the terminal and prints the current frame, if animating
+    pub(crate) fn animate(&self) {
+        if let Some(delay) = self.animation {
+            println!("\u{1b}[2J\u{1b}[H{}", self);
+            thread::sleep(delay);
+        }
+    }
+    /// Prints the `width` by `height` rectangle with its top left corner at `x`,
+    /// `y`, the parts outside of the map are left out. Rulers, see `with_rulers()`,
+    /// show coordinates in the map rather than in the rectangle.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     Generator::new()
+    ///         .with_size(400, 200)
+    ///         .with_rulers(true)
+    ///         .spawn_perlin_bands(&[(0.66, 2), (0.33, 1)])
+    ///         .show_viewport(180, 90, 40, 10);
+    /// }
+    /// ```
+    pub fn show_viewport(&self, x: usize, y: usize, width: usize, height: usize) {
+        println!("{}", Window::new(self, x, y, width, height));
+    }
+    /// Prints the map one screen at a time, left to right and then top to
+    /// bottom, with rulers on. Pressing enter shows the next page, `q` stops.
+    /// The screen size is read from the `COLUMNS` and `LINES` environment
+    /// variables, 80 by 24 if they're not set.
+    pub fn show_paged(&self) -> io::Result<()> {
+        let (columns, lines) = terminal_size();
+        let margin = digits(self.height.saturating_sub(1)) + 1;
+        let ruler = digits(self.width.saturating_sub(1));
+        let page_width = (columns.saturating_sub(margin) / 2).max(1);
+        let page_height = lines.saturating_sub(ruler + 1).max(1);
+        let across = self.width.div_ceil(page_width);
+        let pages = across * self.height.div_ceil(page_height);
+
+        let stdin = io::stdin();
+        let mut stdout = io::stdout();
+        for page in 0..pages {
+            let x = page % across * page_width;
+            let y = page / across * page_height;
+            let window = Window {
+                rulers: true,
+                ..Window::new(self, x, y, page_width, page_height)
+            };
+            writeln!(stdout, "{}", window)?;
+            if page + 1 == pages {
+                break;
+            }
+            write!(stdout, "-- page {}/{}, enter for next, q to quit -- ", page + 1, pages)?;
+            stdout.flush()?;
+            let mut answer = String::new();
+            if stdin.lock().read_line(&mut answer)? == 0 || answer.trim() == "q" {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Palette;
+
+    #[test]
+    fn viewport() {
+        let generator = Generator::new()
+            .with_size(12, 12)
+            .with_palette(Palette::new().with_glyph(0, '.').with_glyph(1, '#'))
+            .draw_line((9, 10), (9, 10), 1)
+            .with_rulers(true);
+        let printed = Window::new(&generator, 8, 9, 3, 10).to_string();
+        let lines: Vec<&str> = printed.lines().collect();
+        assert_eq!(lines, vec!["   0   1 ", "   8 9 0 ", " 9 . . . ", "10 . # . ", "11 . . . "]);
+    }
+    #[test]
+    fn shaded() {
+        let printed = Shaded { width: 3, field: &[0., 0.5, 1., 1., 1., 1.] }.to_string();
+        let lines: Vec<&str> = printed.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("\u{1b}[48;2;0;0;0m"));
+        assert!(lines[0].contains("\u{1b}[48;2;128;128;128m"));
+        assert!(lines[1].starts_with("\u{1b}[48;2;255;255;255m"));
+    }
+    #[test]
+    fn side_by_side() {
+        let mut generator = Generator::new()
+            .with_size(2, 1)
+            .with_palette(Palette::new().with_glyph(0, '.').with_glyph(1, '#'));
+        generator.set_layer("a", vec![0, 1]);
+        generator.set_layer("b", vec![1, 1]);
+        let layers = vec![("b", generator.layer("b").unwrap()), ("a", generator.layer("a").unwrap())];
+        let printed = SideBySide { generator: &generator, layers }.to_string();
+        assert_eq!(printed, "b     a   \n# #   . # ");
+    }
+    #[test]
+    fn compact() {
+        let generator = Generator::new()
+            .with_size(2, 3)
+            .draw_line((0, 1), (1, 1), 1)
+            .with_palette(Palette::new().with_color(0, (1, 2, 3)).with_background(1, (4, 5, 6)));
+        let printed = Compact(&generator).to_string();
+        let lines: Vec<&str> = printed.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].matches('▀').count(), 2);
+        assert!(lines[0].starts_with("\u{1b}[48;2;4;5;6m\u{1b}[38;2;1;2;3m▀"));
+        assert!(!lines[1].contains("\u{1b}[48"));
+    }
+}
@@ -0,0 +1,166 @@
+//! Pattern-based rewrite rules, a small cellular rewriting engine.
+
+use crate::{Generator, Stage};
+use rand::seq::SliceRandom;
+use rayon::prelude::*;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Replaces every occurrence of a small pattern of tile values with another
+/// pattern, see [Generator::rewrite](struct.Generator.html#method.rewrite).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Rule {
+    pub width: usize,
+    pub height: usize,
+    /// Pattern to look for in row-major order, `None` matches any value.
+    pub from: Vec<Option<usize>>,
+    /// Replacement in row-major order, `None` leaves the cell as it is.
+    pub to: Vec<Option<usize>>,
+}
+
+impl Rule {
+    /// Creates a `width` by `height` rule. Panics if a pattern doesn't have
+    /// `width * height` cells.
+    pub fn new(width: usize, height: usize, from: Vec<Option<usize>>, to: Vec<Option<usize>>) -> Self {
+        assert!(
+            from.len() == width * height && to.len() == width * height,
+            "patterns of a {}x{} rule must have {} cells",
+            width,
+            height,
+            width * height
+        );
+        Self { width, height, from, to }
+    }
+    /// Returns this rule rotated by 0, 90, 180 and 270 degrees, for rules that
+    /// should apply in every direction.
+    pub fn rotations(&self) -> Vec<Rule> {
+        let mut rules = vec![self.clone()];
+        for _ in 0..3 {
+            let last = rules.last().unwrap();
+            rules.push(last.rotated());
+        }
+        rules
+    }
+    // rotates 90 degrees clockwise
+    fn rotated(&self) -> Rule {
+        let rotate = |pattern: &[Option<usize>]| {
+            let mut rotated = Vec::with_capacity(pattern.len());
+            for y in 0..self.width {
+                for x in 0..self.height {
+                    rotated.push(pattern[y + (self.height - 1 - x) * self.width]);
+                }
+            }
+            rotated
+        };
+        Rule::new(self.height, self.width, rotate(&self.from), rotate(&self.to))
+    }
+    fn matches(&self, generator: &Generator, x: usize, y: usize) -> bool {
+        self.from.iter().enumerate().all(|(i, expected)| match expected {
+            Some(value) => generator.get(x + i % self.width, y + i / self.width) == *value,
+            None => true,
+        })
+    }
+}
+
+impl Generator {
+    /// Rewrites the map with `rules`, MarkovJunior style. Each step takes the first
+    /// rule that matches anywhere, and replaces as many of its matches as possible
+    /// without overlaps, in random order. This repeats until no rule matches or
+    /// `steps` steps have run.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     // grow corridors out of a single seed cell
+    ///     let grow = Rule::new(3, 1, vec![Some(1), Some(0), Some(0)], vec![None, Some(1), Some(1)]);
+    ///     Generator::new()
+    ///         .with_size(41, 21)
+    ///         .draw_line((20, 10), (20, 10), 1)
+    ///         .rewrite(&grow.rotations(), 10)
+    ///         .show();
+    /// }
+    /// ```
+    pub fn rewrite(self, rules: &[Rule], steps: usize) -> Self {
+        let stage = Stage::Rewrite {
+            rules: rules.to_vec(),
+            steps,
+        };
+        self.stage(stage, |mut generator| {
+            generator.with_stage_rng("rewrite", |generator, rng| {
+                for step in 0..steps {
+                    if generator.is_cancelled() || generator.is_out_of_time() {
+                        break;
+                    }
+                    generator.report(step as f32 / steps as f32);
+                    let found = rules.iter().find_map(|rule| {
+                        let matches = generator.find_matches(rule);
+                        if matches.is_empty() {
+                            None
+                        } else {
+                            Some((rule, matches))
+                        }
+                    });
+                    let (rule, mut matches) = match found {
+                        Some(found) => found,
+                        None => break,
+                    };
+                    matches.shuffle(rng);
+                    let mut touched = vec![false; generator.map.len()];
+                    for (x, y) in matches {
+                        let cells: Vec<usize> = (0..rule.from.len())
+                            .map(|i| x + i % rule.width + (y + i / rule.width) * generator.width)
+                            .collect();
+                        if cells.iter().any(|&pos| touched[pos]) {
+                            continue;
+                        }
+                        for (&pos, replacement) in cells.iter().zip(rule.to.iter()) {
+                            touched[pos] = true;
+                            if let Some(value) = replacement {
+                                generator.map[pos] = *value;
+                            }
+                        }
+                    }
+                    generator.animate();
+                }
+            });
+            generator
+        })
+    }
+    // top left corners of every place `rule` matches
+    fn find_matches(&self, rule: &Rule) -> Vec<(usize, usize)> {
+        if rule.width > self.width || rule.height > self.height {
+            return vec![];
+        }
+        let columns = self.width - rule.width + 1;
+        let rows = self.height - rule.height + 1;
+        (0..columns * rows)
+            .into_par_iter()
+            .map(|pos| (pos % columns, pos / columns))
+            .filter(|&(x, y)| rule.matches(self, x, y))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewrite() {
+        let grow = Rule::new(2, 1, vec![Some(1), Some(0)], vec![None, Some(1)]);
+        let rotations = grow.rotations();
+        let generator = Generator::new()
+            .with_size(5, 1)
+            .draw_line((0, 0), (0, 0), 1)
+            .rewrite(&rotations, 2);
+        assert_eq!(generator.map, vec![1, 1, 1, 0, 0]);
+
+        assert_eq!(rotations[1].width, 1);
+        assert_eq!(rotations[1].from, vec![Some(1), Some(0)]);
+        assert_eq!(rotations[2].from, vec![Some(0), Some(1)]);
+        assert_eq!(rotations[3].from, vec![Some(0), Some(1)]);
+        assert_eq!(rotations[3].height, 2);
+    }
+}
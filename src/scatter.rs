@@ -0,0 +1,204 @@
+//! Stages that scatter tile values at random.
+
+use crate::{Generator, Stage};
+use rand::{Rng, RngCore};
+
+// picks a value from `(value, weight)` pairs in proportion to the weights, None
+// if no weight is positive
+pub(crate) fn pick_weighted(choices: &[(usize, f64)], rng: &mut dyn RngCore) -> Option<usize> {
+    let total: f64 = choices.iter().map(|&(_, weight)| weight.max(0.)).sum();
+    if total <= 0. {
+        return None;
+    }
+    let mut roll = rng.gen::<f64>() * total;
+    for &(value, weight) in choices {
+        roll -= weight.max(0.);
+        if roll < 0. {
+            return Some(value);
+        }
+    }
+    // rounding can leave a sliver at the end
+    choices.iter().rev().find(|&&(_, weight)| weight > 0.).map(|&(value, _)| value)
+}
+
+impl Generator {
+    /// Fills every cell with a value drawn from `choices`, `(value, weight)` pairs
+    /// where each value is picked in proportion to its weight. A quick base layer
+    /// of rubble, grass variants or noise to run structure passes on. Restrict it
+    /// to part of the map with `with_mask`. Leaves the map as it is if no weight is
+    /// positive.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     Generator::new()
+    ///         .with_size(40, 20)
+    ///         // mostly grass, some flowers, a few rocks
+    ///         .fill_weighted(&[(1, 8.), (2, 2.), (3, 0.5)])
+    ///         .show();
+    /// }
+    /// ```
+    pub fn fill_weighted(self, choices: &[(usize, f64)]) -> Self {
+        let stage = Stage::FillWeighted {
+            choices: choices.to_vec(),
+        };
+        self.stage(stage, |mut generator| {
+            generator.with_stage_rng("fill_weighted", |generator, rng| {
+                for value in generator.map.iter_mut() {
+                    match pick_weighted(choices, rng) {
+                        Some(picked) => *value = picked,
+                        None => break,
+                    }
+                }
+            });
+            generator
+        })
+    }
+    /// Turns cells holding `from` into other values at random. `choices` lists
+    /// `(to, probability)` pairs, so `&[(3, 0.1), (4, 0.02)]` turns 10% of the cells
+    /// into 3 and 2% into 4, the rest stay `from`. Probabilities adding up to more
+    /// than 1 are scaled down so they add up to exactly 1.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let size = Size::new((4, 4), (10, 10));
+    ///     Generator::new()
+    ///         .with_size(30, 20)
+    ///         .spawn_rooms(1, 5, &size)
+    ///         // rubble and cracks on the floor
+    ///         .replace_weighted(1, &[(2, 0.1), (3, 0.02)])
+    ///         .show();
+    /// }
+    /// ```
+    pub fn replace_weighted(self, from: usize, choices: &[(usize, f64)]) -> Self {
+        let stage = Stage::ReplaceWeighted {
+            from,
+            choices: choices.to_vec(),
+        };
+        let total: f64 = choices.iter().map(|&(_, weight)| weight).sum();
+        let scale = if total > 1. { 1. / total } else { 1. };
+        self.stage(stage, |mut generator| {
+            generator.with_stage_rng("replace_weighted", |generator, rng| {
+                for value in generator.map.iter_mut().filter(|value| **value == from) {
+                    let mut roll: f64 = rng.gen();
+                    for &(to, weight) in choices {
+                        roll -= weight * scale;
+                        if roll < 0. {
+                            *value = to;
+                            break;
+                        }
+                    }
+                }
+            });
+            generator
+        })
+    }
+    /// Grows `clusters` blobs of `value` of about `cluster_size` cells each, such as
+    /// berry bushes, ruins or enemy camps, which plain white noise can't make.
+    /// Blobs grow outwards from random centers one random neighbor at a time, and
+    /// `spread` between 0 and 1 controls how loose they are: 0 makes compact blobs,
+    /// 1 lets cells skip up to two cells away from the blob.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     Generator::new()
+    ///         .with_size(40, 20)
+    ///         .spawn_clusters(2, 5, 12, 0.3)
+    ///         .show();
+    /// }
+    /// ```
+    pub fn spawn_clusters(self, value: usize, clusters: usize, cluster_size: usize, spread: f64) -> Self {
+        let stage = Stage::Clusters {
+            value,
+            clusters,
+            cluster_size,
+            spread,
+        };
+        self.stage(stage, |mut generator| {
+            if generator.map.is_empty() {
+                return generator;
+            }
+            let (width, height) = (generator.width as isize, generator.height as isize);
+            generator.with_stage_rng("clusters", |generator, rng| {
+                for _ in 0..clusters {
+                    let center = (rng.gen_range(0, width), rng.gen_range(0, height));
+                    let mut cells = vec![center];
+                    // every attempt either adds a cell or lands on one already taken,
+                    // the limit stops blobs squeezed against the edges
+                    for _ in 0..cluster_size * 8 {
+                        if cells.len() >= cluster_size {
+                            break;
+                        }
+                        let (x, y) = cells[rng.gen_range(0, cells.len())];
+                        let reach = if rng.gen::<f64>() < spread { 2 } else { 1 };
+                        let (dx, dy) = (rng.gen_range(-reach, reach + 1), rng.gen_range(-reach, reach + 1));
+                        let next = (x + dx, y + dy);
+                        if next.0 >= 0 && next.1 >= 0 && next.0 < width && next.1 < height && !cells.contains(&next) {
+                            cells.push(next);
+                        }
+                    }
+                    for (x, y) in cells {
+                        generator.set(x as usize, y as usize, value);
+                    }
+                }
+            });
+            generator
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fill_weighted() {
+        let generator = Generator::new()
+            .with_size(100, 100)
+            .with_seed(0)
+            .fill_weighted(&[(1, 3.), (2, 1.)]);
+        let histogram = generator.histogram();
+        assert!((7300..7700).contains(&histogram[&1]));
+        assert!((2300..2700).contains(&histogram[&2]));
+        let untouched = Generator::new().with_size(10, 10).fill_weighted(&[(1, 0.)]);
+        assert!(untouched.map.iter().all(|&value| value == 0));
+    }
+
+    #[test]
+    fn replace_weighted() {
+        let generator = Generator::new()
+            .with_size(100, 100)
+            .with_seed(0)
+            .replace_weighted(0, &[(1, 0.1), (2, 0.5)]);
+        let histogram = generator.histogram();
+        assert!((900..1100).contains(&histogram[&1]));
+        assert!((4800..5200).contains(&histogram[&2]));
+        let all = Generator::new().with_size(10, 10).replace_weighted(0, &[(1, 3.), (2, 3.)]);
+        assert!(!all.map.contains(&0));
+    }
+
+    #[test]
+    fn spawn_clusters() {
+        let generator = Generator::new().with_size(50, 50).spawn_clusters(1, 1, 20, 0.);
+        assert_eq!(generator.positions_of(1).len(), 20);
+        // without spread the blob is one component, allowing diagonal steps
+        let cells = generator.positions_of(1);
+        let mut reached = vec![cells[0]];
+        let mut stack = vec![cells[0]];
+        while let Some((x, y)) = stack.pop() {
+            for &(dx, dy) in crate::NEIGHBORS_8.iter() {
+                let next = ((x as isize + dx) as usize, (y as isize + dy) as usize);
+                if cells.contains(&next) && !reached.contains(&next) {
+                    reached.push(next);
+                    stack.push(next);
+                }
+            }
+        }
+        assert_eq!(reached.len(), cells.len());
+    }
+}
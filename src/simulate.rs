@@ -0,0 +1,208 @@
+//! Simple physical simulations over the map, such as sand falling and water pooling.
+
+use crate::{Generator, Stage, NEIGHBORS_4};
+use rand::prelude::*;
+
+impl Generator {
+    /// Lets cells of `material` fall through cells of `empty`, falling sand style,
+    /// with y pointing down as in a side view. Each step a cell falls one cell
+    /// straight down if it can, or else diagonally down. A `liquid` also flows
+    /// sideways when it can't fall, so it levels out and pools like water. Runs for
+    /// up to `steps` steps, stopping early once nothing moves.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     // water poured into a cave settles at the bottom
+    ///     Generator::new()
+    ///         .with_size(30, 12)
+    ///         .spawn_perlin_bands(&[(0.55, 1)])
+    ///         .fill_rect(10, 0, 10, 2, 2)
+    ///         .settle(2, 0, true, 100)
+    ///         .show();
+    /// }
+    /// ```
+    pub fn settle(self, material: usize, empty: usize, liquid: bool, steps: usize) -> Self {
+        let stage = Stage::Settle {
+            material,
+            empty,
+            liquid,
+            steps,
+        };
+        self.stage(stage, |mut generator| {
+            let (width, height) = (generator.width, generator.height);
+            generator.with_stage_rng("settle", |generator, rng| {
+                let mut columns: Vec<usize> = (0..width).collect();
+                for step in 0..steps {
+                    if generator.is_cancelled() || generator.is_out_of_time() {
+                        break;
+                    }
+                    generator.report(step as f32 / steps as f32);
+                    let mut moved = vec![false; generator.map.len()];
+                    let mut any = false;
+                    // bottom up, so a cell falls at most once per step
+                    for y in (0..height).rev() {
+                        columns.shuffle(rng);
+                        for &x in &columns {
+                            let pos = x + y * width;
+                            if generator.map[pos] != material || moved[pos] {
+                                continue;
+                            }
+                            let sides = if rng.gen() { [-1, 1] } else { [1, -1] };
+                            let mut targets: Vec<(isize, isize)> = vec![(0, 1), (sides[0], 1), (sides[1], 1)];
+                            if liquid {
+                                targets.extend_from_slice(&[(sides[0], 0), (sides[1], 0)]);
+                            }
+                            let target = targets.into_iter().find_map(|(dx, dy)| {
+                                let (nx, ny) = (x as isize + dx, y as isize + dy);
+                                if nx < 0 || nx >= width as isize || ny >= height as isize {
+                                    return None;
+                                }
+                                let target = nx as usize + ny as usize * width;
+                                if generator.map[target] == empty {
+                                    Some(target)
+                                } else {
+                                    None
+                                }
+                            });
+                            if let Some(target) = target {
+                                generator.map.swap(pos, target);
+                                moved[target] = true;
+                                any = true;
+                            }
+                        }
+                    }
+                    generator.animate();
+                    if !any {
+                        break;
+                    }
+                }
+            });
+            generator
+        })
+    }
+    /// Lets cells of `water` flow downhill over cells of `ground`, following the
+    /// `"elevation"` field of a heightmap source such as `spawn_faults()`. Each step
+    /// every water cell moves to the lowest ground cell next to it or next to the
+    /// water it touches, if that lies lower, leaving ground behind, so water runs
+    /// into basins and spreads across them as lakes. Cells of other values block the
+    /// water. Runs for up to `steps` steps, stopping early once nothing moves, and
+    /// leaves the map as it is without an elevation field.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     // rain on a tenth of the land gathers into lakes
+    ///     Generator::new()
+    ///         .with_size(40, 20)
+    ///         .spawn_faults(100)
+    ///         .fill_rect(0, 0, 40, 20, 1)
+    ///         .spawn_clusters(2, 20, 4, 0.5)
+    ///         .flow_water(2, 1, 100)
+    ///         .show();
+    /// }
+    /// ```
+    pub fn flow_water(self, water: usize, ground: usize, steps: usize) -> Self {
+        self.stage(Stage::FlowWater { water, ground, steps }, |mut generator| {
+            let elevation = match generator.fields.remove("elevation") {
+                Some(elevation) => elevation,
+                None => return generator,
+            };
+            let (width, height) = (generator.width, generator.height);
+            generator.with_stage_rng("flow_water", |generator, rng| {
+                let mut visited = vec![0; width * height];
+                let mut stamp = 0;
+                for step in 0..steps {
+                    if generator.is_cancelled() || generator.is_out_of_time() {
+                        break;
+                    }
+                    generator.report(step as f32 / steps as f32);
+                    let mut cells = generator.positions_of(water);
+                    cells.shuffle(rng);
+                    let mut any = false;
+                    for (x, y) in cells {
+                        let pos = x + y * width;
+                        // water crosses the pool it touches to reach lower ground, so
+                        // basins fill evenly instead of drops piling up on their rims
+                        stamp += 1;
+                        visited[pos] = stamp;
+                        let mut pool = vec![pos];
+                        let mut lowest: Option<usize> = None;
+                        while let Some(cell) = pool.pop() {
+                            let (cx, cy) = (cell % width, cell / width);
+                            for &(dx, dy) in NEIGHBORS_4.iter() {
+                                let (nx, ny) = (cx as isize + dx, cy as isize + dy);
+                                if nx < 0 || ny < 0 || nx >= width as isize || ny >= height as isize {
+                                    continue;
+                                }
+                                let target = nx as usize + ny as usize * width;
+                                if visited[target] == stamp || elevation[target] > elevation[pos] {
+                                    continue;
+                                }
+                                visited[target] = stamp;
+                                if generator.map[target] == water {
+                                    pool.push(target);
+                                } else if generator.map[target] == ground
+                                    && elevation[target] < elevation[pos]
+                                    && lowest.is_none_or(|lowest| elevation[target] < elevation[lowest])
+                                {
+                                    lowest = Some(target);
+                                }
+                            }
+                        }
+                        if let Some(target) = lowest {
+                            generator.map[target] = water;
+                            generator.map[pos] = ground;
+                            any = true;
+                        }
+                    }
+                    generator.animate();
+                    if !any {
+                        break;
+                    }
+                }
+            });
+            generator.fields.insert("elevation".into(), elevation);
+            generator
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settle() {
+        // a cup of walls with sand or water above it
+        let cup = |material, liquid| {
+            Generator::new()
+                .with_size(5, 5)
+                .draw_line((0, 2), (0, 4), 1)
+                .draw_line((4, 2), (4, 4), 1)
+                .draw_line((0, 4), (4, 4), 1)
+                .fill_rect(1, 0, 3, 1, material)
+                .settle(material, 0, liquid, 20)
+        };
+        let sand = cup(2, false);
+        assert_eq!(sand.positions_of(2).len(), 3);
+        assert!(sand.positions_of(2).iter().all(|&(_, y)| y >= 2));
+        let water = cup(3, true);
+        assert_eq!(water.positions_of(3), vec![(1, 3), (2, 3), (3, 3)]);
+    }
+    #[test]
+    fn flow_water() {
+        let mut generator = Generator::new().with_size(5, 1).fill_rect(0, 0, 5, 1, 1).draw_line((4, 0), (4, 0), 2);
+        generator.set_field("elevation", vec![0.1, 0.0, 0.5, 0.6, 0.7]);
+        let generator = generator.flow_water(2, 1, 10);
+        assert_eq!(generator.map, vec![1, 2, 1, 1, 1]);
+        assert!(generator.field("elevation").is_some());
+
+        // the second drop crosses the first to fill the basin evenly
+        let mut generator = Generator::new().with_size(6, 1).fill_rect(0, 0, 6, 1, 1).draw_line((0, 0), (1, 0), 2);
+        generator.set_field("elevation", vec![0.9, 0.8, 0.0, 0.0, 0.5, 0.9]);
+        assert_eq!(generator.flow_water(2, 1, 10).map, vec![1, 1, 2, 2, 1, 1]);
+    }
+}
@@ -0,0 +1,303 @@
+//! Summaries of what a map is made of, for automated tests and tuning.
+
+use crate::Generator;
+use rayon::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+
+/// How often every value appears in a map, and a summary of every float layer,
+/// see [Generator::stats](struct.Generator.html#method.stats).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stats {
+    /// Amount of cells in the map.
+    pub cells: usize,
+    /// Amount of cells holding each value, ordered by value.
+    pub counts: BTreeMap<usize, usize>,
+    /// Summary of every field, such as `"elevation"`, by name.
+    pub fields: BTreeMap<String, FieldStats>,
+}
+
+impl Stats {
+    /// Amount of cells holding `value`.
+    pub fn count(&self, value: usize) -> usize {
+        self.counts.get(&value).copied().unwrap_or(0)
+    }
+    /// Fraction of the map holding `value`, between 0 and 1.
+    pub fn fraction(&self, value: usize) -> f64 {
+        if self.cells == 0 {
+            0.
+        } else {
+            self.count(value) as f64 / self.cells as f64
+        }
+    }
+    /// Shannon entropy of the values in bits, 0 for a map holding a single value
+    /// and `log2(n)` for `n` values covering equal shares.
+    pub fn entropy(&self) -> f64 {
+        entropy(self.counts.values().copied(), self.cells)
+    }
+}
+
+/// How often each value borders each other value, see
+/// [Generator::adjacency_matrix](struct.Generator.html#method.adjacency_matrix).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Adjacency {
+    /// Times `(a, b)` appears with `b` directly to the right of `a`.
+    pub horizontal: BTreeMap<(usize, usize), usize>,
+    /// Times `(a, b)` appears with `b` directly below `a`.
+    pub vertical: BTreeMap<(usize, usize), usize>,
+}
+
+impl Adjacency {
+    /// Times `a` and `b` are orthogonal neighbors, in any direction.
+    pub fn count(&self, a: usize, b: usize) -> usize {
+        let get = |pairs: &BTreeMap<(usize, usize), usize>, key| pairs.get(&key).copied().unwrap_or(0);
+        let total = get(&self.horizontal, (a, b)) + get(&self.vertical, (a, b));
+        if a == b {
+            total
+        } else {
+            total + get(&self.horizontal, (b, a)) + get(&self.vertical, (b, a))
+        }
+    }
+}
+
+/// Minimum, maximum, mean and standard deviation of a float layer such as raw noise
+/// or elevation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FieldStats {
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub std_dev: f64,
+}
+
+impl FieldStats {
+    /// Summarizes `values`, returns `None` if there are none.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let stats = FieldStats::from_values(&[0.25, 0.75]).unwrap();
+    ///     assert_eq!(stats.mean, 0.5);
+    ///     assert_eq!(stats.std_dev, 0.25);
+    /// }
+    /// ```
+    pub fn from_values(values: &[f64]) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        let variance = values.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / values.len() as f64;
+        Some(Self {
+            min,
+            max,
+            mean,
+            std_dev: variance.sqrt(),
+        })
+    }
+}
+
+impl Generator {
+    /// Counts every value in the map, for checks like "water covers between 20% and
+    /// 40% of the map", and summarizes every field with its minimum, maximum, mean
+    /// and standard deviation, see [FieldStats](struct.FieldStats.html).
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let stats = Generator::new()
+    ///         .with_size(40, 20)
+    ///         .spawn_perlin_bands(&[(0.5, 1)])
+    ///         .stats();
+    ///     for (value, count) in &stats.counts {
+    ///         println!("{}: {} cells, {:.1}%", value, count, stats.fraction(*value) * 100.);
+    ///     }
+    /// }
+    /// ```
+    pub fn stats(&self) -> Stats {
+        Stats {
+            cells: self.map.len(),
+            counts: self.histogram().into_iter().collect(),
+            fields: self
+                .fields
+                .iter()
+                .filter_map(|(name, values)| Some((name.clone(), FieldStats::from_values(values)?)))
+                .collect(),
+        }
+    }
+    /// Mean entropy in bits of every `window` by `window` block of the map, a variety
+    /// score for rejecting boring maps without a human looking at them. Unlike
+    /// `stats().entropy()` it tells a noisy map apart from one split into two big
+    /// halves. Blocks at the edges may be smaller.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let generator = Generator::new()
+    ///         .with_size(60, 30)
+    ///         .spawn_perlin_bands(&[(0.66, 2), (0.33, 1)]);
+    ///     if generator.local_entropy(8) < 0.2 {
+    ///         println!("boring map, try another seed");
+    ///     }
+    /// }
+    /// ```
+    pub fn local_entropy(&self, window: usize) -> f64 {
+        let window = window.max(1);
+        let columns = self.width.div_ceil(window);
+        let blocks = columns * self.height.div_ceil(window);
+        if blocks == 0 {
+            return 0.;
+        }
+        let total: f64 = (0..blocks)
+            .into_par_iter()
+            .map(|block| {
+                let (x, y) = (block % columns * window, block / columns * window);
+                let mut counts = HashMap::new();
+                for row in y..(y + window).min(self.height) {
+                    for col in x..(x + window).min(self.width) {
+                        *counts.entry(self.get(col, row)).or_insert(0) += 1;
+                    }
+                }
+                let cells = counts.values().sum();
+                entropy(counts.into_values(), cells)
+            })
+            .sum();
+        total / blocks as f64
+    }
+    /// Tallies how often each value borders each other value, by direction. Both a
+    /// sanity metric ("lava never touches water") and the training data for
+    /// adjacency based stages, taken from an example map.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let adjacency = Generator::new()
+    ///         .with_size(40, 20)
+    ///         .spawn_perlin_bands(&[(0.66, 2), (0.33, 1)])
+    ///         .adjacency_matrix();
+    ///     println!("deep water touches mountains {} times", adjacency.count(0, 2));
+    /// }
+    /// ```
+    pub fn adjacency_matrix(&self) -> Adjacency {
+        let mut adjacency = Adjacency::default();
+        for (x, y, value) in self.iter_cells() {
+            if x + 1 < self.width {
+                *adjacency.horizontal.entry((value, self.get(x + 1, y))).or_insert(0) += 1;
+            }
+            if y + 1 < self.height {
+                *adjacency.vertical.entry((value, self.get(x, y + 1))).or_insert(0) += 1;
+            }
+        }
+        adjacency
+    }
+    /// Fraction of cells holding `value` in the square of `radius` cells around each
+    /// cell, laid out like `map`. Squares are clipped at the edges of the map. Useful
+    /// for finding forest cores, open plazas or over-clustered resources.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let generator = Generator::new()
+    ///         .with_size(40, 20)
+    ///         .spawn_perlin_bands(&[(0.5, 1)]);
+    ///     let density = generator.density_map(1, 3);
+    ///     let cores = density.iter().filter(|&&density| density > 0.9).count();
+    ///     println!("{} cells deep inside forests", cores);
+    /// }
+    /// ```
+    pub fn density_map(&self, value: usize, radius: usize) -> Vec<f32> {
+        let (width, height) = (self.width, self.height);
+        // summed-area table with an extra row and column of zeros, so every square
+        // is counted in constant time
+        let mut sums = vec![0u32; (width + 1) * (height + 1)];
+        for y in 0..height {
+            let mut row = 0;
+            for x in 0..width {
+                row += (self.get(x, y) == value) as u32;
+                sums[x + 1 + (y + 1) * (width + 1)] = sums[x + 1 + y * (width + 1)] + row;
+            }
+        }
+        (0..self.map.len())
+            .into_par_iter()
+            .map(|pos| {
+                let (x, y) = (pos % width, pos / width);
+                let (x1, y1) = (x.saturating_sub(radius), y.saturating_sub(radius));
+                let (x2, y2) = ((x + radius + 1).min(width), (y + radius + 1).min(height));
+                let at = |x: usize, y: usize| sums[x + y * (width + 1)];
+                let count = at(x2, y2) + at(x1, y1) - at(x1, y2) - at(x2, y1);
+                count as f32 / ((x2 - x1) * (y2 - y1)) as f32
+            })
+            .collect()
+    }
+}
+
+// shannon entropy in bits of a distribution given as counts
+fn entropy<I: Iterator<Item = usize>>(counts: I, cells: usize) -> f64 {
+    counts
+        .filter(|&count| count > 0)
+        .map(|count| {
+            let p = count as f64 / cells as f64;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats() {
+        let stats = Generator::new().with_size(4, 5).fill_rect(0, 0, 4, 1, 2).stats();
+        assert_eq!(stats.cells, 20);
+        assert_eq!(stats.counts.iter().collect::<Vec<_>>(), vec![(&0, &16), (&2, &4)]);
+        assert_eq!(stats.fraction(2), 0.2);
+        assert_eq!(stats.fraction(7), 0.);
+        assert!(stats.fields.is_empty());
+
+        let mut generator = Generator::new().with_size(2, 2);
+        generator.set_field("elevation", vec![1., 3., 2., 2.]);
+        let elevation = generator.stats().fields["elevation"];
+        assert_eq!((elevation.min, elevation.max, elevation.mean), (1., 3., 2.));
+
+        let field = FieldStats::from_values(&[1., 3., 2., 2.]).unwrap();
+        assert_eq!((field.min, field.max, field.mean), (1., 3., 2.));
+        assert!((field.std_dev - 0.5f64.sqrt()).abs() < 1e-12);
+        assert_eq!(FieldStats::from_values(&[]), None);
+    }
+
+    #[test]
+    fn entropy() {
+        let halves = Generator::new().with_size(8, 8).fill_rect(0, 0, 4, 8, 1);
+        assert_eq!(halves.stats().entropy(), 1.);
+        assert_eq!(halves.local_entropy(4), 0.);
+        assert_eq!(halves.local_entropy(8), 1.);
+        assert_eq!(Generator::new().with_size(8, 8).stats().entropy(), 0.);
+    }
+
+    #[test]
+    fn density_map() {
+        let generator = Generator::new().with_size(4, 3).fill_rect(0, 0, 2, 3, 1);
+        let density = generator.density_map(1, 1);
+        assert_eq!(density[0], 1.);
+        assert_eq!(density[1 + 4], 2. / 3.);
+        assert_eq!(density[3 + 8], 0.);
+        assert_eq!(generator.density_map(1, 0)[1], 1.);
+    }
+
+    #[test]
+    fn adjacency_matrix() {
+        let mut generator = Generator::new().with_size(2, 2);
+        generator.map = vec![1, 2, 2, 2];
+        let adjacency = generator.adjacency_matrix();
+        assert_eq!(adjacency.horizontal.iter().collect::<Vec<_>>(), vec![(&(1, 2), &1), (&(2, 2), &1)]);
+        assert_eq!(adjacency.vertical.iter().collect::<Vec<_>>(), vec![(&(1, 2), &1), (&(2, 2), &1)]);
+        assert_eq!(adjacency.count(2, 1), 2);
+        assert_eq!(adjacency.count(2, 2), 2);
+    }
+}
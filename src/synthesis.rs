@@ -0,0 +1,114 @@
+//! Texture synthesis, new maps that look locally like an example map.
+
+use crate::scatter::pick_weighted;
+use crate::{Generator, Stage};
+use std::collections::BTreeMap;
+
+// how often each value follows a context in the example, BTreeMaps keep the
+// sampling the same for the same seed
+type Counts = BTreeMap<usize, usize>;
+
+// transition statistics of an example, from the most specific context (the
+// cells to the left and above) down to plain value frequencies
+#[derive(Default)]
+struct Chain {
+    both: BTreeMap<(Option<usize>, Option<usize>), Counts>,
+    left: BTreeMap<Option<usize>, Counts>,
+    up: BTreeMap<Option<usize>, Counts>,
+    all: Counts,
+}
+
+impl Chain {
+    fn learn(rows: &[Vec<usize>]) -> Self {
+        let mut chain = Chain::default();
+        for (y, row) in rows.iter().enumerate() {
+            for (x, &value) in row.iter().enumerate() {
+                let left = x.checked_sub(1).map(|x| row[x]);
+                let up = y.checked_sub(1).and_then(|y| rows[y].get(x).copied());
+                *chain.both.entry((left, up)).or_default().entry(value).or_default() += 1;
+                *chain.left.entry(left).or_default().entry(value).or_default() += 1;
+                *chain.up.entry(up).or_default().entry(value).or_default() += 1;
+                *chain.all.entry(value).or_default() += 1;
+            }
+        }
+        chain
+    }
+    // falls back to a looser context when the example never saw this one
+    fn counts(&self, left: Option<usize>, up: Option<usize>) -> &Counts {
+        self.both
+            .get(&(left, up))
+            .or_else(|| self.left.get(&left))
+            .or_else(|| self.up.get(&up))
+            .unwrap_or(&self.all)
+    }
+}
+
+impl Generator {
+    /// Fills the map with a texture like the one in `example`, usually a small
+    /// hand drawn map. Learns how often each value follows the values to its left
+    /// and above it in the example, then fills the map row by row, picking each
+    /// cell from what followed the same neighbors in the example. Much lighter
+    /// than wave function collapse and good at organic patterns such as grass,
+    /// rubble or cracked stone, though it doesn't keep larger shapes intact.
+    /// Leaves the map as it is if the example is empty.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     // diagonal stripes of water and sand
+    ///     let example = Generator::new()
+    ///         .with_size(8, 8)
+    ///         .apply(|x, y, _| if (x + y) % 4 < 2 { 0 } else { 6 });
+    ///     Generator::new()
+    ///         .with_size(40, 20)
+    ///         .synthesize(&example)
+    ///         .show();
+    /// }
+    /// ```
+    pub fn synthesize(self, example: &Generator) -> Self {
+        self.synthesize_rows(example.get_2d_map())
+    }
+    // same as `synthesize()`, with the example as rows so it can be recorded
+    pub(crate) fn synthesize_rows(self, example: Vec<Vec<usize>>) -> Self {
+        let chain = Chain::learn(&example);
+        self.stage(Stage::Synthesize { example }, |mut generator| {
+            if chain.all.is_empty() {
+                return generator;
+            }
+            let width = generator.width;
+            generator.with_stage_rng("synthesize", |generator, rng| {
+                for i in 0..generator.map.len() {
+                    let (x, y) = (i % width, i / width);
+                    let left = x.checked_sub(1).map(|_| generator.map[i - 1]);
+                    let up = y.checked_sub(1).map(|_| generator.map[i - width]);
+                    let choices: Vec<(usize, f64)> = chain
+                        .counts(left, up)
+                        .iter()
+                        .map(|(&value, &count)| (value, count as f64))
+                        .collect();
+                    if let Some(value) = pick_weighted(&choices, rng) {
+                        generator.map[i] = value;
+                    }
+                }
+            });
+            generator
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn synthesize() {
+        // a checkerboard has only one way to continue
+        let example = Generator::new().with_size(4, 4).apply(|x, y, _| (x + y) % 2 + 3);
+        let generator = Generator::new().with_size(20, 10).synthesize(&example);
+        assert!(generator.iter_cells().all(|(x, y, value)| value == (x + y) % 2 + 3));
+
+        let recipe = generator.recipe();
+        assert_eq!(Generator::from_recipe(&recipe).unwrap().map, generator.map);
+    }
+}
@@ -0,0 +1,222 @@
+//! Derivatives of heightmaps stored as fields.
+
+use crate::{Generator, Stage};
+
+// change of `values` per cell along x and y, central differences inside the map
+// and one-sided ones along its edges
+pub(crate) fn gradient(values: &[f64], width: usize, height: usize) -> Vec<(f64, f64)> {
+    let at = |x: usize, y: usize| values[x + y * width];
+    let difference = |low: usize, high: usize, sample: &dyn Fn(usize) -> f64| {
+        if high == low {
+            0.
+        } else {
+            (sample(high) - sample(low)) / (high - low) as f64
+        }
+    };
+    (0..values.len())
+        .map(|pos| {
+            let (x, y) = (pos % width, pos / width);
+            let dx = difference(x.saturating_sub(1), (x + 1).min(width - 1), &|x| at(x, y));
+            let dy = difference(y.saturating_sub(1), (y + 1).min(height - 1), &|y| at(x, y));
+            (dx, dy)
+        })
+        .collect()
+}
+
+// steepness of `values` per cell, the length of the gradient
+pub(crate) fn slope(values: &[f64], width: usize, height: usize) -> Vec<f64> {
+    gradient(values, width, height)
+        .into_iter()
+        .map(|(dx, dy)| dx.hypot(dy))
+        .collect()
+}
+
+impl Generator {
+    /// Returns how steep the `"elevation"` field is at every cell, as the change in
+    /// elevation per cell in the steepest direction, for finding flat ground to
+    /// build on, pricing roads or placing cliffs. Returns `None` without an
+    /// elevation field.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let generator = Generator::new().with_size(40, 20).spawn_faults(100);
+    ///     let slope = generator.slope_map().unwrap();
+    ///     let buildable = slope.iter().filter(|&&slope| slope < 0.01).count();
+    ///     println!("{} cells flat enough to build on", buildable);
+    /// }
+    /// ```
+    pub fn slope_map(&self) -> Option<Vec<f64>> {
+        let elevation = self.field("elevation")?;
+        Some(slope(elevation, self.width, self.height))
+    }
+    /// Returns which way every cell of the `"elevation"` field faces, as the
+    /// compass direction of downhill in degrees clockwise from the top of the map,
+    /// so 0 faces north and 180 faces south. Flat cells don't face anywhere and
+    /// get `None`. Useful for vegetation that depends on sunlight, such as vineyards
+    /// on south facing slopes and snow lasting on north facing ones. Returns `None`
+    /// without an elevation field.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let generator = Generator::new().with_size(40, 20).spawn_faults(100);
+    ///     let aspect = generator.aspect_map().unwrap();
+    ///     let sunny = aspect.iter().flatten().filter(|&&facing| (135. ..=225.).contains(&facing)).count();
+    ///     println!("{} cells face south", sunny);
+    /// }
+    /// ```
+    pub fn aspect_map(&self) -> Option<Vec<Option<f64>>> {
+        let elevation = self.field("elevation")?;
+        let aspect = gradient(elevation, self.width, self.height)
+            .into_iter()
+            .map(|(dx, dy)| {
+                if dx == 0. && dy == 0. {
+                    return None;
+                }
+                // downhill is against the gradient, and north is against y
+                let degrees = (-dx).atan2(dy).to_degrees();
+                Some((degrees + 360.) % 360.)
+            })
+            .collect();
+        Some(aspect)
+    }
+    /// Returns the surface normal of every cell of the `"elevation"` field, as unit
+    /// `[x, y, z]` vectors with y pointing down the map like cells do and z up out
+    /// of it, so engines can light the terrain without working it out again. `scale`
+    /// is how many cells high an elevation of 1 is, higher values give steeper
+    /// terrain. Returns `None` without an elevation field, see also
+    /// `normal_map_rgb(...)`.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let generator = Generator::new().with_size(40, 20).spawn_faults(100);
+    ///     let normals = generator.normal_map(20.).unwrap();
+    ///     let facing_up = normals.iter().filter(|normal| normal[2] > 0.99).count();
+    ///     println!("{} flat cells", facing_up);
+    /// }
+    /// ```
+    pub fn normal_map(&self, scale: f64) -> Option<Vec<[f64; 3]>> {
+        let elevation = self.field("elevation")?;
+        let normals = gradient(elevation, self.width, self.height)
+            .into_iter()
+            .map(|(dx, dy)| {
+                let (x, y) = (-dx * scale, -dy * scale);
+                let length = (x * x + y * y + 1.).sqrt();
+                [x / length, y / length, 1. / length]
+            })
+            .collect();
+        Some(normals)
+    }
+    /// Same as `normal_map(...)`, packed into the red, green and blue bytes of each
+    /// cell in row-major order the way normal map images store them, ready to be
+    /// written out as an RGB image one pixel per cell.
+    pub fn normal_map_rgb(&self, scale: f64) -> Option<Vec<u8>> {
+        let normals = self.normal_map(scale)?;
+        let byte = |component: f64| ((component + 1.) / 2. * 255.).round() as u8;
+        Some(normals.iter().flat_map(|normal| normal.iter().map(|&component| byte(component))).collect())
+    }
+    /// Shades the `"elevation"` field as lit by a sun `altitude` degrees above the
+    /// horizon, shining from `azimuth` degrees clockwise from the top of the map,
+    /// and stores how brightly every cell is lit as the `"hillshade"` field, between
+    /// 0 and 1. Elevation is scaled so the steepest slope of the map is shaded as 45
+    /// degrees, so the relief reads well whatever the range of the heights. Show it
+    /// with `show_field("hillshade")`. Does nothing without an elevation field.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     Generator::new()
+    ///         .with_size(60, 20)
+    ///         .spawn_faults(200)
+    ///         .hillshade(315., 45.)
+    ///         .show_field("hillshade");
+    /// }
+    /// ```
+    pub fn hillshade(self, azimuth: f64, altitude: f64) -> Self {
+        self.stage(Stage::Hillshade { azimuth, altitude }, |mut generator| {
+            let steepest = match generator.field("elevation") {
+                Some(elevation) => slope(elevation, generator.width, generator.height)
+                    .into_iter()
+                    .fold(0., f64::max),
+                None => return generator,
+            };
+            let scale = if steepest > 0. { 1. / steepest } else { 1. };
+            let (azimuth, altitude) = (azimuth.to_radians(), altitude.to_radians());
+            // the top of the map is north, so y points south
+            let sun = [azimuth.sin() * altitude.cos(), -azimuth.cos() * altitude.cos(), altitude.sin()];
+            let shade = generator
+                .normal_map(scale)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|normal| (normal[0] * sun[0] + normal[1] * sun[1] + normal[2] * sun[2]).max(0.))
+                .collect();
+            generator.set_field("hillshade", shade);
+            generator
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slope() {
+        // a ramp rising 0.5 per column
+        let ramp = [0., 0.5, 1., 0., 0.5, 1.];
+        assert_eq!(gradient(&ramp, 3, 2), vec![(0.5, 0.); 6]);
+        assert_eq!(super::slope(&[1.], 1, 1), vec![0.]);
+    }
+    #[test]
+    fn slope_map() {
+        let mut generator = Generator::new().with_size(3, 2);
+        assert_eq!(generator.slope_map(), None);
+        generator.set_field("elevation", vec![0., 0.5, 1., 0., 0.5, 1.]);
+        assert_eq!(generator.slope_map().unwrap(), vec![0.5; 6]);
+    }
+    #[test]
+    fn aspect_map() {
+        let mut generator = Generator::new().with_size(3, 1);
+        assert_eq!(generator.aspect_map(), None);
+        // rising to the east, so facing west
+        generator.set_field("elevation", vec![0., 0.5, 1.]);
+        assert_eq!(generator.aspect_map().unwrap(), vec![Some(270.); 3]);
+        generator.set_field("elevation", vec![1.; 3]);
+        assert_eq!(generator.aspect_map().unwrap(), vec![None; 3]);
+        // rising to the north, so facing south
+        let mut generator = generator.with_size(1, 2);
+        generator.set_field("elevation", vec![1., 0.]);
+        assert_eq!(generator.aspect_map().unwrap(), vec![Some(180.); 2]);
+    }
+    #[test]
+    fn normal_map() {
+        let mut generator = Generator::new().with_size(3, 2);
+        assert_eq!(generator.normal_map(1.), None);
+        generator.set_field("elevation", vec![0., 0.5, 1., 0., 0.5, 1.]);
+        // rising to the right, so the surface faces left and up
+        let normals = generator.normal_map(2.).unwrap();
+        let half = 0.5f64.sqrt();
+        for normal in normals {
+            assert!((normal[0] + half).abs() < 1e-9 && normal[1] == 0. && (normal[2] - half).abs() < 1e-9);
+        }
+        assert_eq!(&generator.normal_map_rgb(2.).unwrap()[..3], &[37, 128, 218]);
+    }
+    #[test]
+    fn hillshade() {
+        let mut generator = Generator::new().with_size(3, 2);
+        generator.set_field("elevation", vec![0., 0.5, 1., 0., 0.5, 1.]);
+        // the slope faces west, into a sun low in the west and away from one in the east
+        let west = generator.hillshade(270., 45.);
+        assert!(west.field("hillshade").unwrap().iter().all(|&shade| (shade - 1.).abs() < 1e-9));
+        let mut generator = west.with_size(3, 2);
+        generator.set_field("elevation", vec![0., 0.5, 1., 0., 0.5, 1.]);
+        let east = generator.hillshade(90., 45.);
+        assert!(east.field("hillshade").unwrap().iter().all(|&shade| shade.abs() < 1e-9));
+    }
+}
@@ -0,0 +1,114 @@
+//! Regions growing outwards from seeds and competing for cells.
+
+use crate::{Generator, Stage, NEIGHBORS_4};
+use rand::prelude::*;
+
+impl Generator {
+    /// Grows a region out of every `(position, value)` seed for up to `iterations`
+    /// rounds. Each round every region tries to claim the unclaimed cells next to
+    /// it, succeeding with probability `weight(value)` of the cell, so regions race
+    /// across cheap cells and stall at expensive ones. Claimed cells take the value
+    /// of their region, and the region of every cell is stored as the `"regions"`
+    /// layer, 1 for the first seed and so on, 0 for cells no region reached.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     // three kingdoms spreading quickly over plains, slowly over mountains
+    ///     let generator = Generator::new()
+    ///         .with_size(40, 20)
+    ///         .spawn_perlin_bands(&[(0.6, 1)])
+    ///         .grow_regions(
+    ///             &[((5, 5), 2), ((35, 5), 3), ((20, 15), 4)],
+    ///             |value| if value == 1 { 0.1 } else { 0.9 },
+    ///             100,
+    ///         );
+    ///     generator.show();
+    /// }
+    /// ```
+    pub fn grow_regions<F: Fn(usize) -> f64>(
+        self,
+        seeds: &[((usize, usize), usize)],
+        weight: F,
+        iterations: usize,
+    ) -> Self {
+        self.stage(Stage::Custom { name: "grow_regions".into() }, |mut generator| {
+            let (width, height) = (generator.width, generator.height);
+            let original = generator.map.clone();
+            let mut owner = vec![0; original.len()];
+            let mut frontiers: Vec<Vec<usize>> = vec![];
+            for (i, &((x, y), value)) in seeds.iter().enumerate() {
+                if x >= width || y >= height || owner[x + y * width] != 0 {
+                    frontiers.push(vec![]);
+                    continue;
+                }
+                owner[x + y * width] = i + 1;
+                generator.map[x + y * width] = value;
+                frontiers.push(vec![x + y * width]);
+            }
+            generator.with_stage_rng("grow_regions", |generator, rng| {
+                let mut order: Vec<usize> = (0..frontiers.len()).collect();
+                for iteration in 0..iterations {
+                    if generator.is_cancelled() || generator.is_out_of_time() || frontiers.iter().all(Vec::is_empty) {
+                        break;
+                    }
+                    generator.report(iteration as f32 / iterations as f32);
+                    // shuffled so no region always gets the first pick
+                    order.shuffle(rng);
+                    for &region in &order {
+                        let mut next = vec![];
+                        for &pos in &frontiers[region] {
+                            let (x, y) = (pos % width, pos / width);
+                            let mut open = false;
+                            for &(dx, dy) in &NEIGHBORS_4 {
+                                let (nx, ny) = (x as isize + dx, y as isize + dy);
+                                if nx < 0 || ny < 0 || nx >= width as isize || ny >= height as isize {
+                                    continue;
+                                }
+                                let neighbor = nx as usize + ny as usize * width;
+                                if owner[neighbor] != 0 {
+                                    continue;
+                                }
+                                if rng.gen::<f64>() < weight(original[neighbor]) {
+                                    owner[neighbor] = region + 1;
+                                    generator.map[neighbor] = seeds[region].1;
+                                    next.push(neighbor);
+                                } else {
+                                    open = true;
+                                }
+                            }
+                            // cells keep trying until they're surrounded
+                            if open {
+                                next.push(pos);
+                            }
+                        }
+                        frontiers[region] = next;
+                    }
+                    generator.animate();
+                }
+            });
+            generator.set_layer("regions", owner);
+            generator
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grow_regions() {
+        // the left half is cheap, the right half can't be entered
+        let generator = Generator::new()
+            .with_size(10, 4)
+            .fill_rect(5, 0, 5, 4, 1)
+            .grow_regions(&[((0, 0), 2), ((4, 3), 3)], |value| if value == 0 { 1. } else { 0. }, 20);
+        assert!(generator.positions_of(1).iter().all(|&(x, _)| x >= 5));
+        assert_eq!(generator.positions_of(1).len(), 20);
+        assert_eq!(generator.positions_of(2).len() + generator.positions_of(3).len(), 20);
+        let regions = generator.layer("regions").unwrap();
+        assert_eq!((regions[0], regions[4 + 3 * 10], regions[9]), (1, 2, 0));
+    }
+}
@@ -0,0 +1,206 @@
+//! Restyling the map, so one layout comes in many looks.
+
+use crate::{Generator, Stage};
+use rand::Rng;
+use std::collections::BTreeMap;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Which values a theme swaps for which, such as floor for ice and walls for
+/// frozen rock, see
+/// [Generator::theme_regions](struct.Generator.html#method.theme_regions).
+/// Values without a swap stay as they are.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ThemeMap {
+    swaps: BTreeMap<usize, usize>,
+}
+
+impl ThemeMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Turns cells holding `from` into `to`.
+    pub fn with_swap(mut self, from: usize, to: usize) -> Self {
+        self.swaps.insert(from, to);
+        self
+    }
+    /// What `value` turns into.
+    pub fn apply(&self, value: usize) -> usize {
+        self.swaps.get(&value).copied().unwrap_or(value)
+    }
+}
+
+/// One rule of a [Variant](struct.Variant.html).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Change {
+    pub from: usize,
+    pub to: usize,
+    /// Fraction of the matching cells that change.
+    pub share: f64,
+    /// Only cells whose `"elevation"` lies above this change, if set.
+    pub above: Option<f64>,
+}
+
+/// What a season or time of day does to a map, such as freezing lakes and
+/// covering the hills in snow in winter, see
+/// [Generator::variant](struct.Generator.html#method.variant).
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Variant {
+    /// Name of the variant, which also picks the cells that change.
+    pub name: String,
+    /// Applied in order.
+    pub changes: Vec<Change>,
+}
+
+impl Variant {
+    pub fn new(name: &str) -> Self {
+        Variant {
+            name: name.to_string(),
+            changes: vec![],
+        }
+    }
+    /// Turns a `share` of the cells holding `from` into `to`, 1 for all of them.
+    pub fn with_change(mut self, from: usize, to: usize, share: f64) -> Self {
+        self.changes.push(Change {
+            from,
+            to,
+            share,
+            above: None,
+        });
+        self
+    }
+    /// Same as `with_change(...)`, for cells higher than `elevation` only, such as
+    /// snow above the tree line.
+    pub fn with_change_above(mut self, from: usize, to: usize, share: f64, elevation: f64) -> Self {
+        self.changes.push(Change {
+            from,
+            to,
+            share,
+            above: Some(elevation),
+        });
+        self
+    }
+}
+
+impl Generator {
+    /// Restyles every region of the layer called `layer` with the theme returned
+    /// by `theme(region)`, so the same caves can be an ice cave, a lava cave and a
+    /// crypt. `theme` is called once per region, with the value the layer holds
+    /// for it, such as the `"regions"` of `grow_regions(...)` or the labels of
+    /// `label_regions()`. Leaves the map as it is if there's no such layer.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let themes = [
+    ///         ThemeMap::new().with_swap(1, 3),
+    ///         ThemeMap::new().with_swap(1, 4),
+    ///         ThemeMap::new().with_swap(1, 5),
+    ///     ];
+    ///     let size = Size::new((4, 4), (10, 10));
+    ///     let mut generator = Generator::new().with_size(40, 20).spawn_rooms(1, 6, &size);
+    ///     // every room is a region of its own
+    ///     let labels = generator.label_regions().labels;
+    ///     generator.set_layer("rooms", labels);
+    ///     generator
+    ///         .theme_regions("rooms", |room| themes[room % 3].clone())
+    ///         .show();
+    /// }
+    /// ```
+    pub fn theme_regions<F: Fn(usize) -> ThemeMap>(self, layer: &str, theme: F) -> Self {
+        self.stage(Stage::Custom { name: "theme_regions".into() }, |mut generator| {
+            if let Some(regions) = generator.layers.get(layer) {
+                let mut themes = BTreeMap::new();
+                for (value, &region) in generator.map.iter_mut().zip(regions) {
+                    *value = themes.entry(region).or_insert_with(|| theme(region)).apply(*value);
+                }
+            }
+            generator
+        })
+    }
+    /// Applies the changes of a seasonal or time of day `variant`. Which cells
+    /// change depends only on the seed and the name of the variant, so building
+    /// the same map again and applying each variant gives a winter, a summer and
+    /// so on of one world, where a cell that's snowed over in one winter is
+    /// snowed over in every winter.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let world = || {
+    ///         Generator::new()
+    ///             .with_size(40, 20)
+    ///             .with_seed(4)
+    ///             .spawn_faults(100)
+    ///             .classify("elevation", &[(0.6, 3), (0.3, 1)])
+    ///     };
+    ///     // lakes freeze, hills get snow and some grass withers
+    ///     let winter = Variant::new("winter")
+    ///         .with_change(0, 5, 1.)
+    ///         .with_change_above(1, 5, 0.8, 0.5)
+    ///         .with_change(1, 6, 0.3);
+    ///     // lakes dry up into mud
+    ///     let summer = Variant::new("summer").with_change(0, 4, 0.7);
+    ///     world().variant(&winter).show();
+    ///     world().variant(&summer).show();
+    /// }
+    /// ```
+    pub fn variant(self, variant: &Variant) -> Self {
+        self.stage(Stage::Variant { variant: variant.clone() }, |mut generator| {
+            let elevation = generator.fields.remove("elevation");
+            let name = format!("variant {}", variant.name);
+            generator.with_stage_rng(&name, |generator, rng| {
+                for change in &variant.changes {
+                    for (pos, value) in generator.map.iter_mut().enumerate() {
+                        // rolled for every cell, so changes don't shift each other
+                        let roll: f64 = rng.gen();
+                        let high_enough = match (change.above, &elevation) {
+                            (Some(above), Some(elevation)) => elevation[pos] > above,
+                            (Some(_), None) => false,
+                            (None, _) => true,
+                        };
+                        if *value == change.from && high_enough && roll < change.share {
+                            *value = change.to;
+                        }
+                    }
+                }
+            });
+            if let Some(elevation) = elevation {
+                generator.fields.insert("elevation".into(), elevation);
+            }
+            generator
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn theme_regions() {
+        let mut generator = Generator::new().with_size(4, 2).fill_rect(0, 1, 4, 1, 1);
+        generator.set_layer("halves", vec![0, 0, 1, 1, 0, 0, 1, 1]);
+        let generator = generator.theme_regions("halves", |half| ThemeMap::new().with_swap(1, 2 + half));
+        assert_eq!(generator.map, vec![0, 0, 0, 0, 2, 2, 3, 3]);
+    }
+
+    #[test]
+    fn variant() {
+        let world = || Generator::new().with_size(20, 20).with_seed(1).spawn_faults(50);
+        let winter = Variant::new("winter").with_change(0, 1, 0.5).with_change_above(0, 2, 1., 0.5);
+        let first = world().variant(&winter);
+        assert_eq!(first.map, world().variant(&winter).map);
+        let elevation = first.field("elevation").unwrap();
+        for (&value, &height) in first.map.iter().zip(elevation) {
+            assert!(value == 1 || (value == 2) == (height > 0.5));
+        }
+        // another variant picks other cells
+        let other = world().variant(&Variant { name: "spring".into(), ..winter });
+        assert_ne!(first.map, other.map);
+    }
+}
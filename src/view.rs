@@ -0,0 +1,189 @@
+//! Borrowed windows into the map.
+
+use crate::Generator;
+
+/// Read-only rectangular window into a map, see
+/// [Generator::view](struct.Generator.html#method.view). Coordinates are relative
+/// to the top left corner of the window.
+#[derive(Debug, Clone, Copy)]
+pub struct View<'a> {
+    map: &'a [usize],
+    stride: usize,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+/// Same as [View](struct.View.html), except values can be modified.
+#[derive(Debug)]
+pub struct ViewMut<'a> {
+    map: &'a mut [usize],
+    stride: usize,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+impl<'a> View<'a> {
+    /// Width of the window.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    /// Height of the window.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+    /// Returns value at (x, y) within the window. Panics outside the window.
+    pub fn get(&self, x: usize, y: usize) -> usize {
+        assert!(x < self.width && y < self.height, "({}, {}) is outside the view", x, y);
+        self.map[self.x + x + (self.y + y) * self.stride]
+    }
+    /// Same as `get(...)`, except it returns `None` outside the window.
+    pub fn get_checked(&self, x: usize, y: usize) -> Option<usize> {
+        if x < self.width && y < self.height {
+            Some(self.get(x, y))
+        } else {
+            None
+        }
+    }
+    /// Iterates over every cell in the window as `(x, y, value)`, in row-major order.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize, usize)> + 'a {
+        let View { map, stride, x, y, width, height } = *self;
+        (0..height).flat_map(move |row| {
+            let start = x + (y + row) * stride;
+            map[start..start + width]
+                .iter()
+                .enumerate()
+                .map(move |(col, &value)| (col, row, value))
+        })
+    }
+}
+
+impl<'a> ViewMut<'a> {
+    /// Width of the window.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    /// Height of the window.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+    /// Returns value at (x, y) within the window. Panics outside the window.
+    pub fn get(&self, x: usize, y: usize) -> usize {
+        self.as_view().get(x, y)
+    }
+    /// Same as `get(...)`, except it returns `None` outside the window.
+    pub fn get_checked(&self, x: usize, y: usize) -> Option<usize> {
+        self.as_view().get_checked(x, y)
+    }
+    /// Same as `get(...)`, except sets value.
+    pub fn set(&mut self, x: usize, y: usize, value: usize) {
+        assert!(x < self.width && y < self.height, "({}, {}) is outside the view", x, y);
+        self.map[self.x + x + (self.y + y) * self.stride] = value;
+    }
+    /// Iterates over every cell in the window as `(x, y, value)`, in row-major order.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize, usize)> + '_ {
+        self.as_view().iter()
+    }
+    /// Same as `iter()`, except values can be modified.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (usize, usize, &mut usize)> + '_ {
+        let (x, y, width, height, stride) = (self.x, self.y, self.width, self.height, self.stride);
+        self.map
+            .chunks_mut(stride.max(1))
+            .skip(y)
+            .take(height)
+            .enumerate()
+            .flat_map(move |(row, cells)| {
+                cells[x..x + width]
+                    .iter_mut()
+                    .enumerate()
+                    .map(move |(col, value)| (col, row, value))
+            })
+    }
+    /// Borrows the window as a read-only [View](struct.View.html).
+    pub fn as_view(&self) -> View<'_> {
+        View {
+            map: self.map,
+            stride: self.stride,
+            x: self.x,
+            y: self.y,
+            width: self.width,
+            height: self.height,
+        }
+    }
+}
+
+impl Generator {
+    fn check_view(&self, x: usize, y: usize, width: usize, height: usize) {
+        assert!(
+            x + width <= self.width && y + height <= self.height,
+            "view at ({}, {}) of size {}x{} doesn't fit inside the map",
+            x,
+            y,
+            width,
+            height
+        );
+    }
+    /// Borrows the `width` by `height` window whose top left corner is at (x, y),
+    /// without copying any cells. Panics if the window doesn't fit inside the map.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     let generator = Generator::new()
+    ///         .with_size(40, 10)
+    ///         .spawn_perlin(|value| if value > 0.5 { 1 } else { 0 });
+    ///     let view = generator.view(10, 2, 5, 5);
+    ///     let land = view.iter().filter(|&(_, _, value)| value == 1).count();
+    ///     println!("{} land cells in view", land);
+    /// }
+    /// ```
+    pub fn view(&self, x: usize, y: usize, width: usize, height: usize) -> View<'_> {
+        self.check_view(x, y, width, height);
+        View {
+            map: &self.map,
+            stride: self.width,
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+    /// Same as `view(...)`, except cells can be modified through the window.
+    pub fn view_mut(&mut self, x: usize, y: usize, width: usize, height: usize) -> ViewMut<'_> {
+        self.check_view(x, y, width, height);
+        ViewMut {
+            map: &mut self.map,
+            stride: self.width,
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn views() {
+        let mut generator = Generator::new().with_size(4, 3);
+        let mut view = generator.view_mut(1, 1, 2, 2);
+        view.set(1, 1, 7);
+        view.iter_mut().for_each(|(x, _, value)| *value += x);
+        assert_eq!(generator.map, vec![
+            0,0,0,0,
+            0,0,1,0,
+            0,0,8,0,
+        ]);
+        let view = generator.view(2, 0, 2, 3);
+        assert_eq!(view.get(0, 2), 8);
+        assert_eq!(view.get_checked(2, 0), None);
+        assert_eq!(view.iter().map(|(_, _, value)| value).sum::<usize>(), 9);
+    }
+}
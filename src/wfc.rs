@@ -0,0 +1,344 @@
+//! Wave function collapse over tiles and adjacency rules declared by hand.
+
+use crate::scatter::pick_weighted;
+use crate::{GenerationError, Generator, Stage, NEIGHBORS_4};
+use rand::prelude::*;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// Side of a tile, y points down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    const ALL: [Direction; 4] = [Direction::Up, Direction::Left, Direction::Right, Direction::Down];
+
+    fn opposite(self) -> Self {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+    // same order as NEIGHBORS_4
+    fn index(self) -> usize {
+        Direction::ALL.iter().position(|&direction| direction == self).unwrap()
+    }
+}
+
+/// Tiles and which tiles may be next to each other, for
+/// [Generator::collapse_tiles](struct.Generator.html#method.collapse_tiles).
+/// Tiles are tile values with a weight, heavier tiles are picked more often.
+/// Rules about values that weren't added as tiles are ignored.
+///
+/// ```rust
+/// use procedural_generation::*;
+///
+/// fn main() {
+///     // water borders sand, sand borders grass, but water never touches grass
+///     let tileset = Tileset::new()
+///         .with_tile(0, 1.)
+///         .with_tile(6, 0.5)
+///         .with_tile(2, 1.)
+///         .with_neighbors(0, 0)
+///         .with_neighbors(0, 6)
+///         .with_neighbors(6, 6)
+///         .with_neighbors(6, 2)
+///         .with_neighbors(2, 2);
+///     Generator::new()
+///         .with_size(40, 20)
+///         .collapse_tiles(&tileset)
+///         .show();
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Tileset {
+    /// Value and weight of every tile.
+    pub tiles: Vec<(usize, f64)>,
+    /// `(a, direction, b)` allows `b` on the `direction` side of `a`, and so `a`
+    /// on the opposite side of `b`.
+    pub rules: Vec<(usize, Direction, usize)>,
+}
+
+impl Tileset {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Adds a tile of `value`, picked in proportion to `weight`.
+    pub fn with_tile(mut self, value: usize, weight: f64) -> Self {
+        self.tiles.push((value, weight));
+        self
+    }
+    /// Allows `b` on the `direction` side of `a`, for tiles that only fit together
+    /// one way, such as the ends of a pipe.
+    pub fn with_neighbor(mut self, a: usize, direction: Direction, b: usize) -> Self {
+        self.rules.push((a, direction, b));
+        self
+    }
+    /// Allows `a` and `b` next to each other on every side.
+    pub fn with_neighbors(self, a: usize, b: usize) -> Self {
+        Direction::ALL
+            .iter()
+            .fold(self, |tileset, &direction| tileset.with_neighbor(a, direction, b))
+    }
+    // allowed[direction][a * tiles + b] when tile b may lie on that side of tile a,
+    // tiles being indices into `self.tiles`
+    fn allowed(&self) -> Vec<Vec<bool>> {
+        let count = self.tiles.len();
+        let index = |value: usize| self.tiles.iter().position(|&(tile, _)| tile == value);
+        let mut allowed = vec![vec![false; count * count]; 4];
+        for &(a, direction, b) in &self.rules {
+            if let (Some(a), Some(b)) = (index(a), index(b)) {
+                allowed[direction.index()][a * count + b] = true;
+                allowed[direction.opposite().index()][b * count + a] = true;
+            }
+        }
+        allowed
+    }
+}
+
+// Amount of cells collapsed between checks for cancellation and time running out.
+const CHECK_EVERY: usize = 64;
+
+// one attempt at collapsing the map of `generator`, None on a contradiction. when
+// stopped early the cells collapsed so far are filled in and the rest keep their value
+fn solve(generator: &Generator, tileset: &Tileset, rng: &mut dyn RngCore) -> Option<Vec<usize>> {
+    let (width, height) = (generator.width, generator.height);
+    let count = tileset.tiles.len();
+    let allowed = tileset.allowed();
+    let cells = width * height;
+    let mut possible = vec![true; cells * count];
+    let mut options = vec![count; cells];
+
+    // removes options that no longer fit next to the cells in `changed`, until
+    // nothing changes, false on a contradiction
+    let mut in_queue = vec![false; cells];
+    let mut propagate = |changed: &mut Vec<usize>,
+                         possible: &mut Vec<bool>,
+                         options: &mut Vec<usize>,
+                         heap: &mut BinaryHeap<Reverse<(usize, u32, usize)>>,
+                         rng: &mut dyn RngCore| {
+        changed.iter().for_each(|&cell| in_queue[cell] = true);
+        while let Some(cell) = changed.pop() {
+            in_queue[cell] = false;
+            let (x, y) = (cell % width, cell / width);
+            for (side, &(dx, dy)) in NEIGHBORS_4.iter().enumerate() {
+                let (nx, ny) = (x as isize + dx, y as isize + dy);
+                if nx < 0 || ny < 0 || nx >= width as isize || ny >= height as isize {
+                    continue;
+                }
+                let neighbor = nx as usize + ny as usize * width;
+                let before = options[neighbor];
+                for b in 0..count {
+                    let fits = |a: usize| possible[cell * count + a] && allowed[side][a * count + b];
+                    if possible[neighbor * count + b] && !(0..count).any(fits) {
+                        possible[neighbor * count + b] = false;
+                        options[neighbor] -= 1;
+                    }
+                }
+                if options[neighbor] == 0 {
+                    return false;
+                }
+                if options[neighbor] < before {
+                    // the old heap entry is skipped once it no longer matches
+                    heap.push(Reverse((options[neighbor], rng.gen(), neighbor)));
+                    if !in_queue[neighbor] {
+                        in_queue[neighbor] = true;
+                        changed.push(neighbor);
+                    }
+                }
+            }
+        }
+        true
+    };
+
+    // cells by fewest options left, ties broken at random
+    let mut heap = BinaryHeap::new();
+    for cell in 0..cells {
+        heap.push(Reverse((count, rng.gen(), cell)));
+    }
+    // rules can rule tiles out before anything is picked, such as a lone tile
+    // that may not lie next to itself
+    let mut changed: Vec<usize> = (0..cells).collect();
+    if !propagate(&mut changed, &mut possible, &mut options, &mut heap, rng) {
+        return None;
+    }
+    // every cell is popped once with a single option left, when it collapses
+    let mut collapsed = 0;
+    while let Some(Reverse((left, _, cell))) = heap.pop() {
+        if left != options[cell] {
+            continue;
+        }
+        if collapsed % CHECK_EVERY == 0 {
+            if generator.is_cancelled() || generator.is_out_of_time() {
+                break;
+            }
+            generator.report(collapsed as f32 / cells as f32);
+        }
+        collapsed += 1;
+        if left == 1 {
+            continue;
+        }
+        let choices: Vec<(usize, f64)> = (0..count)
+            .filter(|&tile| possible[cell * count + tile])
+            .map(|tile| (tile, tileset.tiles[tile].1))
+            .collect();
+        let tile = pick_weighted(&choices, rng).unwrap_or(choices[0].0);
+        for other in 0..count {
+            possible[cell * count + other] = other == tile;
+        }
+        options[cell] = 1;
+        if !propagate(&mut vec![cell], &mut possible, &mut options, &mut heap, rng) {
+            return None;
+        }
+    }
+    Some(
+        (0..cells)
+            .map(|cell| match options[cell] {
+                1 => tileset.tiles[(0..count).find(|&tile| possible[cell * count + tile]).unwrap()].0,
+                _ => generator.map[cell],
+            })
+            .collect(),
+    )
+}
+
+impl Generator {
+    /// Fills the map with the tiles of `tileset`, so that every pair of
+    /// neighboring cells is allowed by its rules, with wave function collapse. Over
+    /// and over the cell with the fewest tiles left to choose from gets a tile, and
+    /// tiles that no longer fit are ruled out of the cells around it. When a cell
+    /// runs out of tiles it starts over, and it panics after 10 attempts, see
+    /// `try_collapse_tiles(...)`. When cancelled or out of time it stops partway,
+    /// and cells that haven't collapsed yet keep their value.
+    pub fn collapse_tiles(self, tileset: &Tileset) -> Self {
+        match self.try_collapse_tiles(tileset) {
+            Ok(generator) => generator,
+            Err(error) => panic!("{}", error),
+        }
+    }
+    /// Same as `collapse_tiles(...)`, except it fails with
+    /// [GenerationError::AttemptsExhausted](enum.GenerationError.html) when every
+    /// attempt ran into a cell no tile fits in, usually because the rules leave a
+    /// tile without any neighbor on some side.
+    pub fn try_collapse_tiles(self, tileset: &Tileset) -> Result<Self, GenerationError> {
+        const ATTEMPTS: usize = 10;
+        let stage = Stage::CollapseTiles {
+            tileset: tileset.clone(),
+        };
+        let mut solved = true;
+        let generator = self.stage(stage, |mut generator| {
+            if tileset.tiles.is_empty() || generator.map.is_empty() {
+                return generator;
+            }
+            generator.with_stage_rng("collapse_tiles", |generator, rng| {
+                for _ in 0..ATTEMPTS {
+                    if generator.is_cancelled() || generator.is_out_of_time() {
+                        return;
+                    }
+                    if let Some(map) = solve(generator, tileset, rng) {
+                        generator.map = map;
+                        return;
+                    }
+                }
+                solved = false;
+            });
+            generator
+        });
+        if solved {
+            Ok(generator)
+        } else {
+            Err(GenerationError::AttemptsExhausted(ATTEMPTS))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CancelToken;
+    use std::time::Duration;
+
+    #[test]
+    fn collapse_tiles() {
+        let tileset = Tileset::new()
+            .with_tile(0, 1.)
+            .with_tile(1, 1.)
+            .with_tile(2, 1.)
+            .with_neighbors(0, 0)
+            .with_neighbors(0, 1)
+            .with_neighbors(1, 1)
+            .with_neighbors(1, 2)
+            .with_neighbors(2, 2);
+        let generator = Generator::new().with_size(30, 20).collapse_tiles(&tileset);
+        // 0 and 2 are never next to each other
+        for (x, y, value) in generator.iter_cells() {
+            assert!(generator.neighbors4(x, y).all(|(_, _, other)| value.max(other) - value.min(other) < 2));
+        }
+
+        // 2 and 3 may only lie next to themselves sideways and next to each other
+        // up and down, so rows alternate
+        let stripes = Tileset::new()
+            .with_tile(2, 1.)
+            .with_tile(3, 1.)
+            .with_neighbor(2, Direction::Down, 3)
+            .with_neighbor(2, Direction::Left, 2)
+            .with_neighbor(3, Direction::Right, 3)
+            .with_neighbor(3, Direction::Down, 2);
+        let rows = Generator::new().with_size(6, 4).collapse_tiles(&stripes).get_2d_map();
+        assert!(rows.iter().all(|row| row == &vec![row[0]; 6]));
+        assert!(rows.windows(2).all(|pair| pair[0][0] != pair[1][0]));
+
+        let impossible = Tileset::new().with_tile(0, 1.).with_tile(1, 1.);
+        let error = Generator::new().with_size(2, 2).try_collapse_tiles(&impossible).unwrap_err();
+        assert_eq!(error, GenerationError::AttemptsExhausted(10));
+        // a lone tile that may not lie next to itself
+        let lonely = Tileset::new().with_tile(3, 1.);
+        let error = Generator::new().with_size(2, 2).try_collapse_tiles(&lonely).unwrap_err();
+        assert_eq!(error, GenerationError::AttemptsExhausted(10));
+        let alone = Generator::new().with_size(1, 1).collapse_tiles(&lonely);
+        assert_eq!(alone.map, vec![3]);
+    }
+
+    #[test]
+    fn stop_early() {
+        let tileset = Tileset::new()
+            .with_tile(0, 1.)
+            .with_tile(1, 1.)
+            .with_neighbors(0, 0)
+            .with_neighbors(0, 1)
+            .with_neighbors(1, 1);
+        let generator = Generator::new()
+            .with_size(20, 20)
+            .fill_rect(0, 0, 20, 20, 5)
+            .with_time_budget(Duration::from_secs(0))
+            .collapse_tiles(&tileset);
+        assert!(generator.map.iter().all(|&value| value == 5));
+
+        // cancelled at the first progress report after some cells collapsed
+        let token = CancelToken::new();
+        let canceller = token.clone();
+        let generator = Generator::new()
+            .with_size(20, 20)
+            .fill_rect(0, 0, 20, 20, 5)
+            .with_cancel_token(token)
+            .with_progress(move |_, fraction| {
+                if fraction > 0. && fraction < 1. {
+                    canceller.cancel();
+                }
+            })
+            .collapse_tiles(&tileset);
+        let left = generator.positions_of(5).len();
+        assert!(left > 0 && left < 400);
+    }
+}
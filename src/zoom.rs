@@ -0,0 +1,117 @@
+//! Growing small maps into larger ones.
+
+use crate::{Artifacts, Generator, Stage};
+use rand::prelude::*;
+
+impl Generator {
+    /// Doubles the width and height of the map `times` times, so a small map of
+    /// land and water becomes a large one with natural, detailed coasts. Every
+    /// doubling turns each cell into four. The top left one keeps the value of the
+    /// cell, the others lie between two or four cells and take the value of one of
+    /// them at random with a chance of `roughness`, between 0 and 1. Only cells on
+    /// a border between values can change, so every doubling adds finer wiggles to
+    /// the coastlines while keeping their overall shape, like midpoint displacement.
+    /// A roughness of 0 scales the map up as it is.
+    ///
+    /// Layers, fields, rooms and artifacts are dropped, since they no longer line
+    /// up with the map. A mask set with `with_mask` is dropped without applying,
+    /// since every cell moves.
+    ///
+    /// ```rust
+    /// use procedural_generation::*;
+    ///
+    /// fn main() {
+    ///     Generator::new()
+    ///         .with_size(10, 5)
+    ///         .spawn_perlin_bands(&[(0.5, 1)])
+    ///         .subdivide(3, 0.8)
+    ///         .show();
+    /// }
+    /// ```
+    pub fn subdivide(mut self, times: usize, roughness: f64) -> Self {
+        self.mask = None;
+        self.stage(Stage::Subdivide { times, roughness }, |mut generator| {
+            generator.rooms.clear();
+            generator.artifacts = Artifacts::default();
+            generator.layers.clear();
+            generator.fields.clear();
+            generator.with_stage_rng("subdivide", |generator, rng| {
+                for time in 0..times {
+                    if generator.is_cancelled() || generator.is_out_of_time() {
+                        // record the doublings that ran, so the recipe keeps the right size
+                        if let Some(Stage::Subdivide { times, .. }) = generator.stages.last_mut() {
+                            *times = time;
+                        }
+                        break;
+                    }
+                    generator.report(time as f32 / times as f32);
+                    let (width, height) = (generator.width, generator.height);
+                    let old = |x: usize, y: usize| generator.map[x.min(width - 1) + y.min(height - 1) * width];
+                    let mut map = Vec::with_capacity(width * height * 4);
+                    for y in 0..height * 2 {
+                        for x in 0..width * 2 {
+                            let (px, py) = (x / 2, y / 2);
+                            // the cells this one lies between
+                            let parents = match (x % 2, y % 2) {
+                                (0, 0) => vec![old(px, py)],
+                                (1, 0) => vec![old(px, py), old(px + 1, py)],
+                                (0, 1) => vec![old(px, py), old(px, py + 1)],
+                                _ => vec![old(px, py), old(px + 1, py), old(px, py + 1), old(px + 1, py + 1)],
+                            };
+                            let value = if parents.len() > 1 && rng.gen::<f64>() < roughness {
+                                *parents.choose(rng).unwrap()
+                            } else {
+                                parents[0]
+                            };
+                            map.push(value);
+                        }
+                    }
+                    generator.map = map;
+                    generator.width = width * 2;
+                    generator.height = height * 2;
+                }
+            });
+            generator
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subdivide() {
+        let island = || Generator::new().with_size(6, 6).fill_rect(2, 2, 2, 2, 1);
+        let scaled = island().subdivide(2, 0.);
+        assert_eq!((scaled.width, scaled.height), (24, 24));
+        assert_eq!(scaled.positions_of(1).len(), 64);
+
+        let rough = island().subdivide(2, 1.);
+        assert_eq!(rough.map.len(), 24 * 24);
+        // the coast moves, but the middle of the island and the open sea don't
+        assert_ne!(rough.map, scaled.map);
+        assert_eq!(rough.get(12, 12), 1);
+        assert_eq!(rough.get(1, 1), 0);
+        assert!(rough.map.iter().all(|&value| value < 2));
+        assert_eq!(Generator::from_recipe(&rough.recipe()).unwrap().map, rough.map);
+
+        // a mask doesn't hold cells back where they were before growing
+        let masked = Generator::new()
+            .with_size(4, 2)
+            .fill_rect(0, 0, 2, 2, 1)
+            .with_mask(|_, _, _| false)
+            .subdivide(1, 0.);
+        assert_eq!(&masked.map[..8], &[1, 1, 1, 1, 0, 0, 0, 0]);
+        assert_eq!(Generator::from_recipe(&masked.recipe()).unwrap().map, masked.map);
+
+        // out of time before the first doubling
+        let stopped = Generator::new()
+            .with_size(4, 2)
+            .with_time_budget(std::time::Duration::from_secs(0))
+            .subdivide(2, 0.);
+        assert_eq!((stopped.width, stopped.height), (4, 2));
+        assert_eq!(stopped.stages(), &[Stage::Subdivide { times: 0, roughness: 0. }]);
+        assert_eq!(stopped.recipe().width, 4);
+    }
+}
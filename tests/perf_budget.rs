@@ -0,0 +1,83 @@
+//! Throughput floors for the spawners, so a regression fails a test instead of
+//! hiding in benchmark reports, see `benches/spawn.rs` for the full numbers.
+//! Timings depend on the machine, so the floors sit well below what a single
+//! core manages and the tests are ignored by default. Run them optimized:
+//!
+//! ```text
+//! cargo test --release --test perf_budget -- --ignored
+//! ```
+
+use procedural_generation::*;
+use std::time::Instant;
+
+// fails if `stage` handles fewer than `floor` million cells of the map it returns
+// per second, best of 3 runs on maps made by `setup`, which isn't timed
+fn budget<S: Fn() -> Generator, F: Fn(Generator) -> Generator>(name: &str, floor: f64, setup: S, stage: F) {
+    let (best, cells) = (0..3)
+        .map(|_| {
+            let input = setup();
+            let start = Instant::now();
+            let output = stage(input);
+            (start.elapsed().as_secs_f64(), output.map.len())
+        })
+        .fold((f64::INFINITY, 0), |best, run| if run.0 < best.0 { run } else { best });
+    let throughput = cells as f64 / best / 1e6;
+    println!("{}: {:.2} Mcells/s, floor {}", name, throughput, floor);
+    assert!(throughput >= floor, "{} handles {:.2} Mcells/s, below its floor of {}", name, throughput, floor);
+}
+
+fn empty(size: usize) -> impl Fn() -> Generator {
+    move || Generator::new().with_size(size, size)
+}
+
+fn bands(size: usize) -> Generator {
+    Generator::new().with_size(size, size).spawn_perlin_bands(&[(0.66, 2), (0.33, 1)])
+}
+
+#[test]
+#[ignore]
+fn spawn() {
+    let size = Size::new((10, 10), (100, 100));
+    let perlin = |generator: Generator| generator.spawn_perlin_bands(&[(0.66, 2), (0.33, 1)]);
+    budget("perlin", 4., empty(512), perlin);
+    budget("rooms", 100., empty(512), |generator| generator.spawn_rooms(1, 1000, &size));
+    budget("label_regions", 8., || bands(512), |generator| {
+        generator.label_regions();
+        generator
+    });
+}
+
+#[test]
+#[ignore]
+fn heightmap() {
+    budget("faults", 0.8, empty(256), |generator| generator.spawn_faults(100));
+    budget("deposition", 10., empty(256), |generator| {
+        generator.spawn_deposition(256 * 256 / 16, DepositionOptions::default())
+    });
+    let faults = || empty(256)().spawn_faults(100);
+    budget("carve_rivers", 1., faults, |generator| generator.carve_rivers(RiverOptions::default()));
+    // counts the cells of the grown map
+    budget("subdivide", 5., || bands(128), |generator| generator.subdivide(2, 0.5));
+}
+
+#[test]
+#[ignore]
+fn iterative() {
+    let tileset = Tileset::new()
+        .with_tile(0, 1.)
+        .with_tile(1, 1.)
+        .with_tile(2, 1.)
+        .with_neighbors(0, 0)
+        .with_neighbors(0, 1)
+        .with_neighbors(1, 1)
+        .with_neighbors(1, 2)
+        .with_neighbors(2, 2);
+    let grow = Rule::new(3, 1, vec![Some(1), Some(0), Some(0)], vec![None, Some(1), Some(1)]).rotations();
+    budget("collapse_tiles", 0.4, empty(128), |generator| generator.collapse_tiles(&tileset));
+    budget("rewrite", 0.05, || empty(64)().draw_line((32, 32), (32, 32), 1), |generator| {
+        generator.rewrite(&grow, 64)
+    });
+    budget("settle", 6., || bands(128), |generator| generator.settle(2, 0, true, 128));
+    let terrain = || empty(64)().spawn_faults(100).classify("elevation", &[(0.5, 2), (0., 1)]);
+    budget("flow_water", 0.03, terrain, |generator| generator.flow_water(2, 1, 64));
+}